@@ -0,0 +1,259 @@
+use crate::build_order::ActionType::{self, Action, Building, Unit, Upgrade, Worker};
+use crate::build_order::BuildOrder;
+use crate::build_order::Race::{self, Any, Protoss, Terran, Zerg};
+use std::collections::HashMap;
+
+/// A known StarCraft II action and the race it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub name: &'static str,
+    pub action_type: ActionType,
+    pub race: Race,
+}
+
+const fn entry(name: &'static str, action_type: ActionType, race: Race) -> CatalogEntry {
+    CatalogEntry {
+        name,
+        action_type,
+        race,
+    }
+}
+
+/// Actions appearing in Legacy of the Void build orders
+pub static CATALOG: &[CatalogEntry] = &[
+    // Terran
+    entry("SCV", Worker, Terran),
+    entry("MULE", Worker, Terran),
+    entry("Marine", Unit, Terran),
+    entry("Marauder", Unit, Terran),
+    entry("Reaper", Unit, Terran),
+    entry("Ghost", Unit, Terran),
+    entry("Hellion", Unit, Terran),
+    entry("Hellbat", Unit, Terran),
+    entry("Widow Mine", Unit, Terran),
+    entry("Cyclone", Unit, Terran),
+    entry("Siege Tank", Unit, Terran),
+    entry("Thor", Unit, Terran),
+    entry("Viking", Unit, Terran),
+    entry("Medivac", Unit, Terran),
+    entry("Liberator", Unit, Terran),
+    entry("Raven", Unit, Terran),
+    entry("Banshee", Unit, Terran),
+    entry("Battlecruiser", Unit, Terran),
+    entry("Command Center", Building, Terran),
+    entry("Orbital Command", Building, Terran),
+    entry("Planetary Fortress", Building, Terran),
+    entry("Supply Depot", Building, Terran),
+    entry("Refinery", Building, Terran),
+    entry("Barracks", Building, Terran),
+    entry("Barracks Reactor", Building, Terran),
+    entry("Barracks Tech Lab", Building, Terran),
+    entry("Engineering Bay", Building, Terran),
+    entry("Bunker", Building, Terran),
+    entry("Missile Turret", Building, Terran),
+    entry("Sensor Tower", Building, Terran),
+    entry("Factory", Building, Terran),
+    entry("Factory Reactor", Building, Terran),
+    entry("Factory Tech Lab", Building, Terran),
+    entry("Ghost Academy", Building, Terran),
+    entry("Armory", Building, Terran),
+    entry("Starport", Building, Terran),
+    entry("Starport Reactor", Building, Terran),
+    entry("Starport Tech Lab", Building, Terran),
+    entry("Fusion Core", Building, Terran),
+    entry("Reactor", Building, Terran),
+    entry("Tech Lab", Building, Terran),
+    entry("Stimpack", Upgrade, Terran),
+    entry("Combat Shield", Upgrade, Terran),
+    entry("Concussive Shells", Upgrade, Terran),
+    entry("Infernal Pre-Igniter", Upgrade, Terran),
+    entry("Hurricane Engines", Upgrade, Terran),
+    entry("Drilling Claws", Upgrade, Terran),
+    entry("Smart Servos", Upgrade, Terran),
+    entry("Cloaking Field", Upgrade, Terran),
+    entry("Hyperflight Rotors", Upgrade, Terran),
+    entry("Caduceus Reactor", Upgrade, Terran),
+    entry("Hi-Sec Auto Tracking", Upgrade, Terran),
+    entry("Neosteel Armor", Upgrade, Terran),
+    entry("Terran Infantry Weapons Level 1", Upgrade, Terran),
+    entry("Terran Infantry Weapons Level 2", Upgrade, Terran),
+    entry("Terran Infantry Weapons Level 3", Upgrade, Terran),
+    entry("Terran Infantry Armor Level 1", Upgrade, Terran),
+    entry("Terran Infantry Armor Level 2", Upgrade, Terran),
+    entry("Terran Infantry Armor Level 3", Upgrade, Terran),
+    entry("Terran Vehicle Weapons Level 1", Upgrade, Terran),
+    entry("Terran Vehicle and Ship Plating Level 1", Upgrade, Terran),
+    entry("Terran Ship Weapons Level 1", Upgrade, Terran),
+    entry("Calldown: MULE", Action, Terran),
+    entry("Calldown: Extra Supplies", Action, Terran),
+    entry("Scanner Sweep", Action, Terran),
+    // Protoss
+    entry("Probe", Worker, Protoss),
+    entry("Zealot", Unit, Protoss),
+    entry("Adept", Unit, Protoss),
+    entry("Stalker", Unit, Protoss),
+    entry("Sentry", Unit, Protoss),
+    entry("High Templar", Unit, Protoss),
+    entry("Dark Templar", Unit, Protoss),
+    entry("Archon", Unit, Protoss),
+    entry("Observer", Unit, Protoss),
+    entry("Warp Prism", Unit, Protoss),
+    entry("Immortal", Unit, Protoss),
+    entry("Colossus", Unit, Protoss),
+    entry("Disruptor", Unit, Protoss),
+    entry("Phoenix", Unit, Protoss),
+    entry("Oracle", Unit, Protoss),
+    entry("Void Ray", Unit, Protoss),
+    entry("Tempest", Unit, Protoss),
+    entry("Carrier", Unit, Protoss),
+    entry("Mothership", Unit, Protoss),
+    entry("Nexus", Building, Protoss),
+    entry("Pylon", Building, Protoss),
+    entry("Assimilator", Building, Protoss),
+    entry("Gateway", Building, Protoss),
+    entry("Warp Gate", Building, Protoss),
+    entry("Forge", Building, Protoss),
+    entry("Cybernetics Core", Building, Protoss),
+    entry("Photon Cannon", Building, Protoss),
+    entry("Shield Battery", Building, Protoss),
+    entry("Twilight Council", Building, Protoss),
+    entry("Robotics Facility", Building, Protoss),
+    entry("Robotics Bay", Building, Protoss),
+    entry("Stargate", Building, Protoss),
+    entry("Fleet Beacon", Building, Protoss),
+    entry("Templar Archives", Building, Protoss),
+    entry("Dark Shrine", Building, Protoss),
+    entry("Warp Gate Research", Upgrade, Protoss),
+    entry("Blink", Upgrade, Protoss),
+    entry("Charge", Upgrade, Protoss),
+    entry("Resonating Glaives", Upgrade, Protoss),
+    entry("Psionic Storm", Upgrade, Protoss),
+    entry("Shadow Stride", Upgrade, Protoss),
+    entry("Extended Thermal Lance", Upgrade, Protoss),
+    entry("Gravitic Boosters", Upgrade, Protoss),
+    entry("Gravitic Drive", Upgrade, Protoss),
+    entry("Anion Pulse-Crystals", Upgrade, Protoss),
+    entry("Protoss Ground Weapons Level 1", Upgrade, Protoss),
+    entry("Protoss Ground Weapons Level 2", Upgrade, Protoss),
+    entry("Protoss Ground Weapons Level 3", Upgrade, Protoss),
+    entry("Protoss Ground Armor Level 1", Upgrade, Protoss),
+    entry("Protoss Ground Armor Level 2", Upgrade, Protoss),
+    entry("Protoss Ground Armor Level 3", Upgrade, Protoss),
+    entry("Protoss Shields Level 1", Upgrade, Protoss),
+    entry("Protoss Air Weapons Level 1", Upgrade, Protoss),
+    entry("Protoss Air Armor Level 1", Upgrade, Protoss),
+    entry("Chrono Boost", Action, Protoss),
+    // Zerg
+    entry("Drone", Worker, Zerg),
+    entry("Overlord", Unit, Zerg),
+    entry("Queen", Unit, Zerg),
+    entry("Zergling", Unit, Zerg),
+    entry("Baneling", Unit, Zerg),
+    entry("Roach", Unit, Zerg),
+    entry("Ravager", Unit, Zerg),
+    entry("Hydralisk", Unit, Zerg),
+    entry("Lurker", Unit, Zerg),
+    entry("Infestor", Unit, Zerg),
+    entry("Swarm Host", Unit, Zerg),
+    entry("Ultralisk", Unit, Zerg),
+    entry("Mutalisk", Unit, Zerg),
+    entry("Corruptor", Unit, Zerg),
+    entry("Brood Lord", Unit, Zerg),
+    entry("Viper", Unit, Zerg),
+    entry("Overseer", Unit, Zerg),
+    entry("Hatchery", Building, Zerg),
+    entry("Lair", Building, Zerg),
+    entry("Hive", Building, Zerg),
+    entry("Extractor", Building, Zerg),
+    entry("Spawning Pool", Building, Zerg),
+    entry("Evolution Chamber", Building, Zerg),
+    entry("Roach Warren", Building, Zerg),
+    entry("Baneling Nest", Building, Zerg),
+    entry("Spine Crawler", Building, Zerg),
+    entry("Spore Crawler", Building, Zerg),
+    entry("Hydralisk Den", Building, Zerg),
+    entry("Lurker Den", Building, Zerg),
+    entry("Infestation Pit", Building, Zerg),
+    entry("Spire", Building, Zerg),
+    entry("Greater Spire", Building, Zerg),
+    entry("Nydus Network", Building, Zerg),
+    entry("Ultralisk Cavern", Building, Zerg),
+    entry("Metabolic Boost", Upgrade, Zerg),
+    entry("Adrenal Glands", Upgrade, Zerg),
+    entry("Glial Reconstitution", Upgrade, Zerg),
+    entry("Tunneling Claws", Upgrade, Zerg),
+    entry("Centrifugal Hooks", Upgrade, Zerg),
+    entry("Burrow", Upgrade, Zerg),
+    entry("Pneumatized Carapace", Upgrade, Zerg),
+    entry("Grooved Spines", Upgrade, Zerg),
+    entry("Muscular Augments", Upgrade, Zerg),
+    entry("Chitinous Plating", Upgrade, Zerg),
+    entry("Pathogen Glands", Upgrade, Zerg),
+    entry("Zerg Missile Weapons Level 1", Upgrade, Zerg),
+    entry("Zerg Missile Weapons Level 2", Upgrade, Zerg),
+    entry("Zerg Missile Weapons Level 3", Upgrade, Zerg),
+    entry("Zerg Melee Weapons Level 1", Upgrade, Zerg),
+    entry("Zerg Melee Weapons Level 2", Upgrade, Zerg),
+    entry("Zerg Melee Weapons Level 3", Upgrade, Zerg),
+    entry("Zerg Ground Carapace Level 1", Upgrade, Zerg),
+    entry("Zerg Ground Carapace Level 2", Upgrade, Zerg),
+    entry("Zerg Ground Carapace Level 3", Upgrade, Zerg),
+    entry("Zerg Flyer Attacks Level 1", Upgrade, Zerg),
+    entry("Zerg Flyer Carapace Level 1", Upgrade, Zerg),
+    entry("Inject Larva", Action, Zerg),
+    entry("Creep Tumor", Action, Zerg),
+    // Any race
+    entry("Scout", Action, Any),
+    entry("Attack", Action, Any),
+];
+
+/// Removes a trailing multiplier such as ` x2` from an action name.
+pub fn strip_multiplier(name: &str) -> &str {
+    let trimmed = name.trim();
+    match trimmed.rsplit_once(' ') {
+        Some((base, count))
+            if count.len() > 1
+                && count.starts_with(['x', 'X'])
+                && count[1..].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            base.trim_end()
+        }
+        _ => trimmed,
+    }
+}
+
+/// Looks up an action by name, ignoring case, plurals and trailing multipliers.
+pub fn lookup(name: &str) -> Option<&'static CatalogEntry> {
+    let name = strip_multiplier(name);
+    let find = |candidate: &str| {
+        CATALOG
+            .iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(candidate))
+    };
+    find(name)
+        .or_else(|| name.strip_suffix('s').and_then(find))
+        .or_else(|| name.strip_suffix("es").and_then(find))
+}
+
+/// Most common race among the catalogued actions of a build order.
+pub fn infer_race(build_order: &BuildOrder) -> Option<Race> {
+    let mut counts: HashMap<String, (Race, usize)> = HashMap::new();
+    for action in build_order
+        .get_entries()
+        .iter()
+        .flat_map(|e| e.get_actions())
+    {
+        if let Some(entry) = lookup(action.get_name())
+            && entry.race != Race::Any
+        {
+            counts
+                .entry(entry.race.to_string())
+                .or_insert((entry.race.clone(), 0))
+                .1 += 1;
+        }
+    }
+    counts
+        .into_values()
+        .max_by_key(|(_, count)| *count)
+        .map(|(race, _)| race)
+}
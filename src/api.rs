@@ -1,46 +1,1476 @@
-use crate::build_parser::fetch_build_order;
-use crate::handlers::{fetch_latest, fetch_segment};
-use crate::index_manager::get_st_highest_index;
+use crate::auth::{AuthError, Authenticator, Caller, ClientId};
+use crate::build_diff::{BuildDiff, diff_builds};
+use crate::build_import::{ImportFormat, import_build};
+use crate::build_order::{BuildOrder, BuildOrderError};
+use crate::build_parser::fetch_build_order_async;
+use crate::build_store::BuildStore;
+use crate::classifier::{ClassifiedBuild, OpenerClassifier};
+use crate::collections::{
+    BuildNotes, Collection, CollectionBundle, CollectionError, LOCAL_OWNER, LibraryStore,
+};
+use crate::config::{ServerConfig, current};
+use crate::fetch_observer::LogObserver;
+use crate::graphql::{self, BuildWarrenSchema, Corpus};
+use crate::handlers::{fetch_ids_async, fetch_latest_async, fetch_segment_async, segment_len};
+use crate::http_cache::{ServedTags, conditional_get};
+use crate::http_client::AsyncHttpClient;
+use crate::index_manager::get_st_highest_index_async;
+use crate::jobs::{Job, JobKind, JobManager, JobResults};
+use crate::metrics::{METRICS_CONTENT_TYPE, record_request, render};
+use crate::projection::{FieldSet, project, project_all};
+use crate::query::Query;
+use crate::salt::encode_salt;
+use crate::search::{SearchFilter, SearchHit, StoreIndex, parse_build_type, parse_race};
+use crate::similarity::{DUPLICATE_THRESHOLD, SimilarBuild, duplicate_clusters, most_similar};
+use crate::stats::{CorpusStats, StatsOptions, compute_stats};
+use actix_cors::Cors;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{
+    AUTHORIZATION, CONTENT_TYPE, ETAG, HeaderName, HeaderValue, IF_NONE_MATCH, RETRY_AFTER,
+    WWW_AUTHENTICATE,
+};
+use actix_web::middleware::{Compress, Condition, Next, from_fn};
 use actix_web::web::Json;
-use actix_web::{App, HttpServer, Responder, get, web};
+use actix_web::{
+    App, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder,
+    ResponseError, delete, get, post, web,
+};
+use async_graphql::http::GraphiQLSource;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::{Ready, ready};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{Instrument, Span, field, info_span};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
 
+/// Header carrying the ID of a request, taken from the client or generated,
+/// and logged with everything done to serve it
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest request ID accepted from a client
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Paths served to anyone without counting against a quota
+const OPEN_PATHS: [&str; 4] = ["/healthz", "/readyz", "/openapi.json", "/docs"];
+/// Most build IDs looked up by one batch request
+pub const MAX_BATCH_SIZE: usize = 100;
+/// Most stored build orders listed by one request
+pub const MAX_BUILDS_LIMIT: usize = 500;
+
+/// How the handlers serialize a `Result`: `{"Ok": value}` on success and
+/// `{"Err": error}` on failure. Only used to describe responses.
+#[derive(ToSchema)]
+pub enum ApiResult<T> {
+    Ok(T),
+    Err(BuildOrderError),
+}
+
+/// Status of a response reporting that a build order could not be fetched.
+pub fn error_status(error: &BuildOrderError) -> StatusCode {
+    match error {
+        BuildOrderError::NotFound | BuildOrderError::Redirected { .. } => StatusCode::NOT_FOUND,
+        BuildOrderError::Cloaked => StatusCode::FORBIDDEN,
+        BuildOrderError::RateLimited { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        BuildOrderError::ParseError(_)
+        | BuildOrderError::InvalidData(_)
+        | BuildOrderError::HttpError(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// `{"Ok": value}` with status 200, or `{"Err": error}` with the status of
+/// the error, so that failures are never cached as successes.
+fn result_response<T: Serialize>(result: Result<T, BuildOrderError>) -> HttpResponse {
+    match result {
+        Ok(value) => HttpResponse::Ok().json(Ok::<_, ()>(value)),
+        Err(e) => error_response(error_status(&e), e),
+    }
+}
+
+/// `{"Err": error}` with the given status, telling the client when to retry
+/// if Spawning Tool rate limited the fetch.
+fn error_response(status: StatusCode, error: BuildOrderError) -> HttpResponse {
+    let mut response = HttpResponse::build(status);
+    if let BuildOrderError::RateLimited {
+        retry_after: Some(seconds),
+    } = error
+    {
+        response.insert_header((RETRY_AFTER, seconds));
+    }
+    response.json(Err::<(), _>(error))
+}
+
+/// Declares the handlers of the REST API once, in the order the server tries
+/// them, so that [`ApiDoc`] documents exactly the routes [`routes`] serves.
+macro_rules! api {
+    ($($handler:ident),* $(,)?) => {
+        /// OpenAPI document of the REST API, served at `/openapi.json`.
+        #[derive(OpenApi)]
+        #[openapi(
+            info(
+                title = "Build Warren",
+                description = "StarCraft II build orders from Spawning Tool"
+            ),
+            paths($($handler),*)
+        )]
+        pub struct ApiDoc;
+
+        /// Registers the handlers of the REST API.
+        pub fn routes(config: &mut web::ServiceConfig) {
+            $(config.service($handler);)*
+        }
+    };
+}
+
+api!(
+    get_salt_by_id,
+    get_by_id,
+    get_builds,
+    post_batch,
+    get_latest,
+    get_latest_default,
+    get_segment,
+    get_count,
+    get_diff,
+    get_similar,
+    get_duplicates,
+    get_search,
+    get_stats,
+    get_build_openers,
+    get_openers,
+    post_import,
+    post_job,
+    get_jobs,
+    get_job,
+    get_job_result,
+    delete_job,
+    get_favourites,
+    post_favourite,
+    delete_favourite,
+    get_collections,
+    post_collection,
+    post_bundle,
+    get_collection,
+    delete_collection,
+    get_collection_builds,
+    post_collection_builds,
+    delete_collection_build,
+    get_bundle,
+    get_notes,
+    post_note,
+    delete_notes,
+    get_metrics,
+    get_healthz,
+    get_readyz,
+    post_graphql,
+    get_graphiql,
+);
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FieldsQuery {
+    /// Fields of the build orders to return, such as `id,name,player_race,votes`
+    #[param(value_type = Option<String>)]
+    fields: Option<FieldSet>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BuildsQuery {
+    /// Query the build orders must match, such as `race:Z vs:P votes>=60 has:"Roach Warren"<3:00`
+    q: Option<String>,
+    offset: Option<usize>,
+    /// Build orders returned, 100 by default and at most 500
+    limit: Option<usize>,
+    /// Fields of the build orders to return, such as `id,name,player_race,votes`
+    #[param(value_type = Option<String>)]
+    fields: Option<FieldSet>,
+}
+
+/// Build IDs looked up together.
+#[derive(Deserialize, ToSchema)]
+pub struct BatchRequest {
+    pub ids: Vec<u32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SimilarQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DuplicatesQuery {
+    threshold: Option<f64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchQuery {
+    /// Words to look for, such as `roach ravager timing`
+    q: String,
+    /// Race of the builds (`T`, `P`, `Z` or its name)
+    race: Option<String>,
+    /// Race the builds are played against
+    opponent_race: Option<String>,
+    /// Type of the builds, such as `timing` or `all-in`
+    build_type: Option<String>,
+    limit: Option<usize>,
+}
+
+impl SearchQuery {
+    fn filter(&self) -> Result<SearchFilter, String> {
+        Ok(SearchFilter {
+            race: self.race.as_deref().map(parse_race).transpose()?,
+            opponent_race: self.opponent_race.as_deref().map(parse_race).transpose()?,
+            build_type: self
+                .build_type
+                .as_deref()
+                .map(parse_build_type)
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct OpenersQuery {
+    label: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ResultsQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+    /// Fields of the build orders to return, such as `id,name,player_race,votes`
+    #[param(value_type = Option<String>)]
+    fields: Option<FieldSet>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReadyQuery {
+    /// Also check that Spawning Tool can be reached
+    upstream: Option<bool>,
+}
+
+/// Result of the readiness checks, `ok` or the reason a check failed.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ImportQuery {
+    format: Option<ImportFormat>,
+    save: Option<bool>,
+    /// Fields of the build order to return, such as `id,name,player_race,votes`
+    #[param(value_type = Option<String>)]
+    fields: Option<FieldSet>,
+}
+
+#[utoipa::path(
+    description = "Fetches a build order from Spawning Tool.",
+    params(("id" = u32, Path, description = "Spawning Tool build ID"), FieldsQuery),
+    responses(
+        (status = 200, body = ApiResult<BuildOrder>),
+        (status = 404, body = ApiResult<BuildOrder>, description = "The build order does not exist or has moved"),
+        (status = 403, body = ApiResult<BuildOrder>, description = "The build order is cloaked"),
+        (status = 502, body = ApiResult<BuildOrder>, description = "The build order could not be fetched"),
+        (status = 503, body = ApiResult<BuildOrder>, description = "Spawning Tool rate limited the fetch"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
 #[get("/build/{id}")]
-async fn get_by_id(name: web::Path<u32>) -> impl Responder {
-    Json(fetch_build_order(name.into_inner()))
+async fn get_by_id(
+    name: web::Path<u32>,
+    query: web::Query<FieldsQuery>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.charge_fetches(1)?;
+    let result = fetch_build_order_async(&client, name.into_inner()).await;
+    Ok(result_response(result.map(|build_order| {
+        project(&build_order, query.fields.as_ref())
+    })))
 }
 
+#[utoipa::path(
+    description = "Stored build orders matching a query, by ID.",
+    params(BuildsQuery),
+    responses((status = 200, body = Vec<BuildOrder>), (status = 400, description = "Invalid query"))
+)]
+#[get("/builds")]
+async fn get_builds(
+    query: web::Query<BuildsQuery>,
+    store: web::Data<BuildStore>,
+) -> Result<HttpResponse, Error> {
+    let filter = match Query::parse(query.q.as_deref().unwrap_or_default()) {
+        Ok(filter) => filter,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string())),
+    };
+    let store = store.get_ref().clone();
+    let corpus = web::block(move || store.load_all()).await?;
+    let matches: Vec<BuildOrder> = filter
+        .filter(&corpus)
+        .into_iter()
+        .skip(query.offset.unwrap_or(0))
+        .take(query.limit.unwrap_or(100).min(MAX_BUILDS_LIMIT))
+        .cloned()
+        .collect();
+    Ok(HttpResponse::Ok().json(project_all(&matches, query.fields.as_ref())))
+}
+
+#[utoipa::path(
+    description = "Looks up build orders by ID, from the local collection or else from Spawning Tool, and returns the build order or the error of each ID.",
+    params(FieldsQuery),
+    request_body = BatchRequest,
+    responses(
+        (status = 200, body = Object, description = "`{\"Ok\": build}` or `{\"Err\": error}` by build ID"),
+        (status = 400, description = "Too many IDs"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[post("/builds/batch")]
+async fn post_batch(
+    request: Json<BatchRequest>,
+    query: web::Query<FieldsQuery>,
+    store: web::Data<BuildStore>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let ids: BTreeSet<u32> = request.ids.iter().copied().collect();
+    if ids.len() > MAX_BATCH_SIZE {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "At most {} build IDs can be looked up at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+    let mut results = BTreeMap::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match store.load(id) {
+            Some(build_order) => {
+                results.insert(id, Ok(build_order));
+            }
+            None => missing.push(id),
+        }
+    }
+    caller.charge_fetches(missing.len() as u64)?;
+    results.extend(fetch_ids_async(&client, &missing, &LogObserver).await);
+    let projected: BTreeMap<u32, Result<_, BuildOrderError>> = results
+        .into_iter()
+        .map(|(id, result)| {
+            let result = result.map(|build_order| project(&build_order, query.fields.as_ref()));
+            (id, result)
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(projected))
+}
+
+#[utoipa::path(
+    description = "Fetches a build order as a SALT string.",
+    params(("id" = u32, Path, description = "Spawning Tool build ID")),
+    responses(
+        (status = 200, body = String, content_type = "text/plain"),
+        (status = 404, body = ApiResult<BuildOrder>, description = "The build order does not exist or has moved"),
+        (status = 403, body = ApiResult<BuildOrder>, description = "The build order is cloaked"),
+        (status = 502, body = ApiResult<BuildOrder>, description = "The build order could not be fetched"),
+        (status = 503, body = ApiResult<BuildOrder>, description = "Spawning Tool rate limited the fetch"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[get("/build/{id}.salt")]
+async fn get_salt_by_id(
+    id: web::Path<u32>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.charge_fetches(1)?;
+    Ok(
+        match fetch_build_order_async(&client, id.into_inner()).await {
+            Ok(build_order) => HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(encode_salt(&build_order)),
+            Err(e) => error_response(error_status(&e), e),
+        },
+    )
+}
+
+#[utoipa::path(
+    description = "Fetches the latest build orders, skipping unavailable ones.",
+    params(("count" = u32, Path, description = "Number of build orders"), FieldsQuery),
+    responses((status = 200, body = Vec<BuildOrder>), (status = 429, description = "Quota of upstream fetches exceeded"))
+)]
 #[get("/latest/{count}")]
-async fn get_latest(count: web::Path<u32>) -> impl Responder {
-    Json(fetch_latest(count.into_inner()))
+async fn get_latest(
+    count: web::Path<u32>,
+    query: web::Query<FieldsQuery>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let count = count.into_inner();
+    caller.charge_fetches(count as u64)?;
+    let build_orders = fetch_latest_async(&client, count, &LogObserver).await;
+    Ok(Json(project_all(&build_orders, query.fields.as_ref())))
 }
 
+#[utoipa::path(
+    description = "Fetches the latest build order.",
+    params(FieldsQuery),
+    responses((status = 200, body = Vec<BuildOrder>), (status = 429, description = "Quota of upstream fetches exceeded"))
+)]
 #[get("/latest")]
-async fn get_latest_default() -> impl Responder {
-    Json(fetch_latest(1)) // Default to 1 if no count is provided
+async fn get_latest_default(
+    query: web::Query<FieldsQuery>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.charge_fetches(1)?;
+    let build_orders = fetch_latest_async(&client, 1, &LogObserver).await; // Default to 1 if no count is provided
+    Ok(Json(project_all(&build_orders, query.fields.as_ref())))
 }
 
+#[utoipa::path(
+    description = "Fetches the available build orders between two IDs.",
+    params(
+        ("start" = u32, Path, description = "First build ID"),
+        ("end" = u32, Path, description = "Last build ID"),
+        FieldsQuery
+    ),
+    responses((status = 200, body = Vec<BuildOrder>), (status = 429, description = "Quota of upstream fetches exceeded"))
+)]
 #[get("/segment/{start}/{end}")]
-async fn get_segment(segment: web::Path<(u32, u32)>) -> impl Responder {
+async fn get_segment(
+    segment: web::Path<(u32, u32)>,
+    query: web::Query<FieldsQuery>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
     let (start, end) = segment.into_inner();
-    Json(fetch_segment(start, end))
+    caller.charge_fetches(segment_len(start, end))?;
+    let build_orders = fetch_segment_async(&client, start, end, &LogObserver).await;
+    Ok(Json(project_all(&build_orders, query.fields.as_ref())))
 }
 
+#[utoipa::path(
+    description = "Highest build ID on Spawning Tool.",
+    responses((status = 200, body = u32), (status = 429, description = "Quota of upstream fetches exceeded"))
+)]
 #[get("/count")]
-async fn get_count() -> impl Responder {
-    Json(get_st_highest_index())
+async fn get_count(
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.charge_fetches(1)?;
+    Ok(Json(get_st_highest_index_async(&client).await))
+}
+
+#[utoipa::path(
+    description = "Compares the steps of two build orders.",
+    params(
+        ("a" = u32, Path, description = "ID of the first build order"),
+        ("b" = u32, Path, description = "ID of the second build order")
+    ),
+    responses(
+        (status = 200, body = ApiResult<BuildDiff>),
+        (status = 404, body = ApiResult<BuildDiff>, description = "The build order does not exist or has moved"),
+        (status = 403, body = ApiResult<BuildDiff>, description = "The build order is cloaked"),
+        (status = 502, body = ApiResult<BuildDiff>, description = "The build order could not be fetched"),
+        (status = 503, body = ApiResult<BuildDiff>, description = "Spawning Tool rate limited the fetch"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[get("/diff/{a}/{b}")]
+async fn get_diff(
+    ids: web::Path<(u32, u32)>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let (a, b) = ids.into_inner();
+    caller.charge_fetches(2)?;
+    let (left, right) = futures::join!(
+        fetch_build_order_async(&client, a),
+        fetch_build_order_async(&client, b)
+    );
+    Ok(result_response(left.and_then(|left| {
+        right.map(|right| diff_builds(&left, &right))
+    })))
+}
+
+#[utoipa::path(
+    description = "Stored build orders most similar to a build.",
+    params(("id" = u32, Path, description = "Build ID"), SimilarQuery),
+    responses(
+        (status = 200, body = ApiResult<Vec<SimilarBuild>>),
+        (status = 404, body = ApiResult<Vec<SimilarBuild>>, description = "The build order does not exist or has moved"),
+        (status = 403, body = ApiResult<Vec<SimilarBuild>>, description = "The build order is cloaked"),
+        (status = 502, body = ApiResult<Vec<SimilarBuild>>, description = "The build order could not be fetched"),
+        (status = 503, body = ApiResult<Vec<SimilarBuild>>, description = "Spawning Tool rate limited the fetch"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[get("/build/{id}/similar")]
+async fn get_similar(
+    id: web::Path<u32>,
+    query: web::Query<SimilarQuery>,
+    store: web::Data<BuildStore>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let id = id.into_inner();
+    let target = match store.load(id) {
+        Some(build_order) => Ok(build_order),
+        None => {
+            caller.charge_fetches(1)?;
+            fetch_build_order_async(&client, id).await
+        }
+    };
+    Ok(result_response(target.map(|target| {
+        most_similar(&target, &store.load_all(), query.limit.unwrap_or(10))
+    })))
+}
+
+#[utoipa::path(
+    description = "Groups of near-duplicate stored build orders, by ID.",
+    params(DuplicatesQuery),
+    responses((status = 200, body = Vec<Vec<u32>>))
+)]
+#[get("/duplicates")]
+async fn get_duplicates(
+    query: web::Query<DuplicatesQuery>,
+    store: web::Data<BuildStore>,
+) -> impl Responder {
+    Json(duplicate_clusters(
+        &store.load_all(),
+        query.threshold.unwrap_or(DUPLICATE_THRESHOLD),
+    ))
+}
+
+#[utoipa::path(
+    description = "Searches the names, creators, descriptions and step comments of the stored build orders, best matches first.",
+    params(SearchQuery),
+    responses((status = 200, body = Vec<SearchHit>), (status = 400, description = "Unknown race or build type"))
+)]
+#[get("/search")]
+async fn get_search(
+    query: web::Query<SearchQuery>,
+    index: web::Data<StoreIndex>,
+) -> Result<HttpResponse, Error> {
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e)),
+    };
+    let index = index.into_inner();
+    let index = web::block(move || index.current()).await?;
+    Ok(
+        HttpResponse::Ok().json(index.search_filtered(
+            &query.q,
+            &filter,
+            query.limit.unwrap_or(10),
+        )),
+    )
+}
+
+#[utoipa::path(
+    description = "Statistics over the stored build orders.",
+    params(StatsOptions),
+    responses((status = 200, body = CorpusStats))
+)]
+#[get("/stats")]
+async fn get_stats(
+    options: web::Query<StatsOptions>,
+    store: web::Data<BuildStore>,
+) -> impl Responder {
+    Json(compute_stats(&store.load_all(), &options))
+}
+
+#[utoipa::path(
+    description = "Opener labels of a build order.",
+    params(("id" = u32, Path, description = "Build ID")),
+    responses(
+        (status = 200, body = ApiResult<ClassifiedBuild>),
+        (status = 404, body = ApiResult<ClassifiedBuild>, description = "The build order does not exist or has moved"),
+        (status = 403, body = ApiResult<ClassifiedBuild>, description = "The build order is cloaked"),
+        (status = 502, body = ApiResult<ClassifiedBuild>, description = "The build order could not be fetched"),
+        (status = 503, body = ApiResult<ClassifiedBuild>, description = "Spawning Tool rate limited the fetch"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[get("/build/{id}/openers")]
+async fn get_build_openers(
+    id: web::Path<u32>,
+    store: web::Data<BuildStore>,
+    classifier: web::Data<OpenerClassifier>,
+    client: web::Data<AsyncHttpClient>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let id = id.into_inner();
+    let build_order = match store.load(id) {
+        Some(build_order) => Ok(build_order),
+        None => {
+            caller.charge_fetches(1)?;
+            fetch_build_order_async(&client, id).await
+        }
+    };
+    Ok(result_response(
+        build_order.map(|build_order| classifier.summarize(&build_order)),
+    ))
+}
+
+#[utoipa::path(
+    description = "Opener labels of the stored build orders.",
+    params(OpenersQuery),
+    responses((status = 200, body = Vec<ClassifiedBuild>))
+)]
+#[get("/openers")]
+async fn get_openers(
+    query: web::Query<OpenersQuery>,
+    store: web::Data<BuildStore>,
+    classifier: web::Data<OpenerClassifier>,
+) -> impl Responder {
+    Json(classifier.search(&store.load_all(), query.label.as_deref()))
+}
+
+#[utoipa::path(
+    description = "Imports a build order from text notation or a SALT string. Saving it to the local collection requires an API key.",
+    params(ImportQuery),
+    request_body(content = String, content_type = "text/plain"),
+    responses(
+        (status = 200, body = ApiResult<BuildOrder>),
+        (status = 400, body = ApiResult<BuildOrder>, description = "The build order could not be parsed"),
+        (status = 401, description = "An API key is required to save"),
+        (status = 500, body = ApiResult<BuildOrder>, description = "The build order could not be saved")
+    )
+)]
+#[post("/import")]
+async fn post_import(
+    body: String,
+    query: web::Query<ImportQuery>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    let save = query.save.unwrap_or(false);
+    if save {
+        caller.require_key()?;
+    }
+    let mut build_order = match import_build(&body, query.format.unwrap_or(ImportFormat::Text)) {
+        Ok(build_order) => build_order,
+        Err(e) => return Ok(error_response(StatusCode::BAD_REQUEST, e)),
+    };
+    if save && let Err(e) = store.save_local(&mut build_order) {
+        return Ok(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            BuildOrderError::InvalidData(e.to_string()),
+        ));
+    }
+    Ok(result_response(Ok(project(
+        &build_order,
+        query.fields.as_ref(),
+    ))))
+}
+
+/// The job `id` if the caller may see it.
+fn owned_job(jobs: &JobManager, id: u64, caller: &Caller) -> Option<Job> {
+    jobs.get(id).filter(|job| caller.owns(job.get_owner()))
+}
+
+#[utoipa::path(
+    description = "Queues a background fetch job, owned by the API key of the caller.",
+    request_body = JobKind,
+    responses(
+        (status = 202, body = Job),
+        (status = 401, description = "An API key is required"),
+        (status = 429, description = "Quota of upstream fetches exceeded")
+    )
+)]
+#[post("/jobs")]
+async fn post_job(
+    kind: Json<JobKind>,
+    jobs: web::Data<JobManager>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.require_key()?;
+    caller.charge_fetches(kind.fetches())?;
+    let owner = caller.get_key_name().map(str::to_string);
+    Ok(match jobs.submit(kind.into_inner(), owner) {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    })
+}
+
+#[utoipa::path(
+    description = "Lists the background jobs of the caller, oldest first.",
+    responses((status = 200, body = Vec<Job>))
+)]
+#[get("/jobs")]
+async fn get_jobs(jobs: web::Data<JobManager>, caller: Caller) -> impl Responder {
+    let jobs: Vec<Job> = jobs
+        .list()
+        .into_iter()
+        .filter(|job| caller.owns(job.get_owner()))
+        .collect();
+    Json(jobs)
+}
+
+#[utoipa::path(
+    description = "Status and progress of a background job of the caller.",
+    params(("id" = u64, Path, description = "Job ID")),
+    responses((status = 200, body = Job), (status = 404, description = "No such job"))
+)]
+#[get("/jobs/{id}")]
+async fn get_job(
+    id: web::Path<u64>,
+    jobs: web::Data<JobManager>,
+    caller: Caller,
+) -> impl Responder {
+    let id = id.into_inner();
+    match owned_job(&jobs, id, &caller) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().body(format!("No job {}", id)),
+    }
+}
+
+#[utoipa::path(
+    description = "Pages through the build orders fetched by a job of the caller.",
+    params(("id" = u64, Path, description = "Job ID"), ResultsQuery),
+    responses((status = 200, body = JobResults), (status = 404, description = "No such job"))
+)]
+#[get("/jobs/{id}/result")]
+async fn get_job_result(
+    id: web::Path<u64>,
+    query: web::Query<ResultsQuery>,
+    jobs: web::Data<JobManager>,
+    caller: Caller,
+) -> impl Responder {
+    let id = id.into_inner();
+    if owned_job(&jobs, id, &caller).is_none() {
+        return HttpResponse::NotFound().body(format!("No job {}", id));
+    }
+    let offset = query.offset.unwrap_or(0);
+    match jobs.results(id, offset, query.limit.unwrap_or(100)) {
+        Ok(Some(results)) => {
+            let mut value =
+                serde_json::to_value(&results).expect("Failed to serialize job results");
+            value["build_orders"] =
+                project_all(&results.build_orders, query.fields.as_ref()).into();
+            HttpResponse::Ok().json(value)
+        }
+        Ok(None) => HttpResponse::NotFound().body(format!("No job {}", id)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    description = "Cancels a background job of the caller.",
+    params(("id" = u64, Path, description = "Job ID")),
+    responses(
+        (status = 200, body = Job),
+        (status = 401, description = "An API key is required"),
+        (status = 404, description = "No such job")
+    )
+)]
+#[delete("/jobs/{id}")]
+async fn delete_job(
+    id: web::Path<u64>,
+    jobs: web::Data<JobManager>,
+    caller: Caller,
+) -> Result<impl Responder, AuthError> {
+    caller.require_key()?;
+    let id = id.into_inner();
+    if owned_job(&jobs, id, &caller).is_none() {
+        return Ok(HttpResponse::NotFound().body(format!("No job {}", id)));
+    }
+    Ok(match jobs.cancel(id) {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => HttpResponse::NotFound().body(format!("No job {}", id)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    })
 }
 
+/// Owner of the library of a caller: the name of its API key, or the local
+/// owner for requests not subject to authentication. Callers without a key
+/// have no library.
+fn owner(caller: &Caller) -> Result<&str, AuthError> {
+    match caller.get_id() {
+        ClientId::Key(name) => Ok(name),
+        ClientId::Unlimited => Ok(LOCAL_OWNER),
+        ClientId::Anonymous(_) => Err(AuthError::MissingKey),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewCollection {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub builds: Vec<u32>,
+}
+
+/// A note on a build, or on one of its steps when `step` is given.
+/// A blank note removes it.
+#[derive(Deserialize, ToSchema)]
+pub struct NoteRequest {
+    /// Number of the step, starting at 1
+    pub step: Option<usize>,
+    pub note: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BundleQuery {
+    /// Also bundle the notes on the builds, which are private otherwise
+    notes: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ImportBundleQuery {
+    /// Name of the imported collection, instead of the bundled one
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    description = "IDs of the builds starred by the caller.",
+    responses((status = 200, body = Vec<u32>), (status = 401, description = "An API key is required"))
+)]
+#[get("/favourites")]
+async fn get_favourites(
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    Ok(HttpResponse::Ok().json(library.get_favourites()))
+}
+
+#[utoipa::path(
+    description = "Stars a build.",
+    params(("id" = u32, Path, description = "Build ID")),
+    responses((status = 200, body = Vec<u32>), (status = 401, description = "An API key is required"))
+)]
+#[post("/favourites/{id}")]
+async fn post_favourite(
+    id: web::Path<u32>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let favourites = library.update(owner(&caller)?, |library| {
+        library.star(*id);
+        Ok(library.get_favourites().clone())
+    })?;
+    Ok(HttpResponse::Ok().json(favourites))
+}
+
+#[utoipa::path(
+    description = "Unstars a build.",
+    params(("id" = u32, Path, description = "Build ID")),
+    responses((status = 200, body = Vec<u32>), (status = 401, description = "An API key is required"))
+)]
+#[delete("/favourites/{id}")]
+async fn delete_favourite(
+    id: web::Path<u32>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let favourites = library.update(owner(&caller)?, |library| {
+        library.unstar(*id);
+        Ok(library.get_favourites().clone())
+    })?;
+    Ok(HttpResponse::Ok().json(favourites))
+}
+
+#[utoipa::path(
+    description = "Collections of the caller, by name.",
+    responses((status = 200, body = Vec<Collection>), (status = 401, description = "An API key is required"))
+)]
+#[get("/collections")]
+async fn get_collections(
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    Ok(HttpResponse::Ok().json(library.get_collections()))
+}
+
+#[utoipa::path(
+    description = "Creates a collection.",
+    request_body = NewCollection,
+    responses(
+        (status = 201, body = Collection),
+        (status = 400, description = "Invalid name"),
+        (status = 409, description = "A collection already has this name")
+    )
+)]
+#[post("/collections")]
+async fn post_collection(
+    request: Json<NewCollection>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let NewCollection {
+        name,
+        description,
+        builds,
+    } = request.into_inner();
+    let collection = library.update(owner(&caller)?, |library| {
+        library.create_collection(&name, description)?;
+        library.add_builds(&name, &builds).cloned()
+    })?;
+    Ok(HttpResponse::Created().json(collection))
+}
+
+#[utoipa::path(
+    description = "A collection of the caller.",
+    params(("name" = String, Path, description = "Collection name")),
+    responses((status = 200, body = Collection), (status = 404, description = "No such collection"))
+)]
+#[get("/collections/{name}")]
+async fn get_collection(
+    name: web::Path<String>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    let collection = library
+        .get_collection(&name)
+        .ok_or_else(|| CollectionError::NotFound(name.to_string()))?;
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+#[utoipa::path(
+    description = "Deletes a collection. Its builds stay in the local collection.",
+    params(("name" = String, Path, description = "Collection name")),
+    responses((status = 200, body = Collection), (status = 404, description = "No such collection"))
+)]
+#[delete("/collections/{name}")]
+async fn delete_collection(
+    name: web::Path<String>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let collection = library.update(owner(&caller)?, |library| library.delete_collection(&name))?;
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+#[utoipa::path(
+    description = "The builds of a collection found in the local collection, in the order of the collection.",
+    params(("name" = String, Path, description = "Collection name"), FieldsQuery),
+    responses((status = 200, body = Vec<BuildOrder>), (status = 404, description = "No such collection"))
+)]
+#[get("/collections/{name}/builds")]
+async fn get_collection_builds(
+    name: web::Path<String>,
+    query: web::Query<FieldsQuery>,
+    library: web::Data<LibraryStore>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    let collection = library
+        .get_collection(&name)
+        .ok_or_else(|| CollectionError::NotFound(name.to_string()))?;
+    let builds: Vec<BuildOrder> = collection
+        .get_builds()
+        .iter()
+        .filter_map(|id| store.load(*id))
+        .collect();
+    Ok(HttpResponse::Ok().json(project_all(&builds, query.fields.as_ref())))
+}
+
+#[utoipa::path(
+    description = "Adds builds to a collection, creating it if needed.",
+    params(("name" = String, Path, description = "Collection name")),
+    request_body = BatchRequest,
+    responses((status = 200, body = Collection), (status = 400, description = "Invalid name"))
+)]
+#[post("/collections/{name}/builds")]
+async fn post_collection_builds(
+    name: web::Path<String>,
+    request: Json<BatchRequest>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let collection = library.update(owner(&caller)?, |library| {
+        library.add_builds(&name, &request.ids).cloned()
+    })?;
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+#[utoipa::path(
+    description = "Removes a build from a collection.",
+    params(("name" = String, Path, description = "Collection name"), ("id" = u32, Path, description = "Build ID")),
+    responses((status = 200, body = Collection), (status = 404, description = "No such collection"))
+)]
+#[delete("/collections/{name}/builds/{id}")]
+async fn delete_collection_build(
+    path: web::Path<(String, u32)>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let (name, id) = path.into_inner();
+    let collection = library.update(owner(&caller)?, |library| {
+        library.remove_builds(&name, &[id]).cloned()
+    })?;
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+#[utoipa::path(
+    description = "Bundles a collection with its builds, to share it.",
+    params(("name" = String, Path, description = "Collection name"), BundleQuery),
+    responses((status = 200, body = CollectionBundle), (status = 404, description = "No such collection"))
+)]
+#[get("/collections/{name}/bundle")]
+async fn get_bundle(
+    name: web::Path<String>,
+    query: web::Query<BundleQuery>,
+    library: web::Data<LibraryStore>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    let bundle = library.bundle(&name, &store, query.notes.unwrap_or(false))?;
+    Ok(HttpResponse::Ok().json(bundle))
+}
+
+#[utoipa::path(
+    description = "Imports a bundled collection. Its builds from Spawning Tool are referenced by ID, and the others saved to the local collection under local IDs.",
+    params(ImportBundleQuery),
+    request_body = CollectionBundle,
+    responses(
+        (status = 201, body = Collection),
+        (status = 400, description = "Invalid name or unsupported bundle"),
+        (status = 409, description = "A collection already has this name")
+    )
+)]
+#[post("/collections/import")]
+async fn post_bundle(
+    bundle: Json<CollectionBundle>,
+    query: web::Query<ImportBundleQuery>,
+    library: web::Data<LibraryStore>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let collection = library.update(owner(&caller)?, |library| {
+        library
+            .import_bundle(bundle.into_inner(), query.name.as_deref(), &store)
+            .cloned()
+    })?;
+    Ok(HttpResponse::Created().json(collection))
+}
+
+#[utoipa::path(
+    description = "The notes of the caller on a build and its steps.",
+    params(("id" = u32, Path, description = "Build ID")),
+    responses((status = 200, body = BuildNotes), (status = 401, description = "An API key is required"))
+)]
+#[get("/build/{id}/notes")]
+async fn get_notes(
+    id: web::Path<u32>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let library = library.library(owner(&caller)?)?;
+    Ok(HttpResponse::Ok().json(library.get_notes(*id).cloned().unwrap_or_default()))
+}
+
+#[utoipa::path(
+    description = "Sets the note on a build, or on one of its steps. Steps can only be annotated on builds of the local collection.",
+    params(("id" = u32, Path, description = "Build ID")),
+    request_body = NoteRequest,
+    responses(
+        (status = 200, body = BuildNotes),
+        (status = 400, description = "No such step"),
+        (status = 404, description = "The build is not in the local collection")
+    )
+)]
+#[post("/build/{id}/notes")]
+async fn post_note(
+    id: web::Path<u32>,
+    request: Json<NoteRequest>,
+    library: web::Data<LibraryStore>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let owner = owner(&caller)?;
+    let build_order = match request.step {
+        Some(_) => match store.load(id) {
+            Some(build_order) => Some(build_order),
+            None => {
+                return Ok(HttpResponse::NotFound()
+                    .body(format!("Build {} is not in the local collection", id)));
+            }
+        },
+        None => None,
+    };
+    let notes = library.update(owner, |library| {
+        match (&build_order, request.step) {
+            (Some(build_order), Some(step)) => {
+                library.set_step_note(build_order, step, &request.note)?
+            }
+            _ => library.set_note(id, &request.note),
+        }
+        Ok(library.get_notes(id).cloned().unwrap_or_default())
+    })?;
+    Ok(HttpResponse::Ok().json(notes))
+}
+
+#[utoipa::path(
+    description = "Removes the notes of the caller on a build and its steps.",
+    params(("id" = u32, Path, description = "Build ID")),
+    responses((status = 204), (status = 401, description = "An API key is required"))
+)]
+#[delete("/build/{id}/notes")]
+async fn delete_notes(
+    id: web::Path<u32>,
+    library: web::Data<LibraryStore>,
+    caller: Caller,
+) -> Result<HttpResponse, Error> {
+    library.update(owner(&caller)?, |library| {
+        library.clear_notes(*id);
+        Ok(())
+    })?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    description = "Metrics in the Prometheus text format.",
+    responses((status = 200, body = String, content_type = "text/plain"))
+)]
+#[get("/metrics")]
+async fn get_metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(METRICS_CONTENT_TYPE)
+        .body(render())
+}
+
+#[utoipa::path(
+    description = "Liveness check, answering as long as the server runs.",
+    responses((status = 200, body = String, content_type = "text/plain"))
+)]
+#[get("/healthz")]
+async fn get_healthz() -> impl Responder {
+    HttpResponse::Ok().content_type("text/plain").body("ok")
+}
+
+/// Checks that a directory can be written to.
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".readyz");
+    std::fs::write(&probe, b"ok")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("{}: {}", dir.display(), e))
+}
+
+#[utoipa::path(
+    description = "Readiness check of the storage, and of Spawning Tool when asked.",
+    params(ReadyQuery),
+    responses((status = 200, body = Readiness), (status = 503, body = Readiness))
+)]
+#[get("/readyz")]
+async fn get_readyz(
+    query: web::Query<ReadyQuery>,
+    store: web::Data<BuildStore>,
+    jobs: web::Data<JobManager>,
+    client: web::Data<AsyncHttpClient>,
+) -> impl Responder {
+    let mut results = vec![
+        ("storage", check_writable(store.get_root())),
+        ("jobs", check_writable(jobs.get_root())),
+    ];
+    if query.upstream.unwrap_or(false) {
+        let upstream = match client.fetch_url(&current().spawning_tool.builds_url).await {
+            Ok(response) if response.status_code == 200 => Ok(()),
+            Ok(response) => Err(format!("Status {}", response.status_code)),
+            Err(e) => Err(e),
+        };
+        results.push(("upstream", upstream));
+    }
+    let readiness = Readiness {
+        ready: results.iter().all(|(_, result)| result.is_ok()),
+        checks: results
+            .into_iter()
+            .map(|(name, result)| (name.to_string(), result.err().unwrap_or("ok".to_string())))
+            .collect(),
+    };
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+#[utoipa::path(
+    description = "Runs a GraphQL query over the stored build orders, creators, matchups and statistics. The schema can be explored at `GET /graphql`.",
+    request_body(content = Object, description = "`{\"query\": ..., \"variables\": {...}}`"),
+    responses((status = 200, body = Object, description = "`{\"data\": {...}, \"errors\": [...]}`"))
+)]
+#[post("/graphql")]
+async fn post_graphql(
+    request: Json<async_graphql::Request>,
+    schema: web::Data<BuildWarrenSchema>,
+    store: web::Data<BuildStore>,
+    caller: Caller,
+) -> Result<impl Responder, Error> {
+    let store = store.get_ref().clone();
+    let builds = web::block(move || store.load_all()).await?;
+    let request = request.into_inner().data(Corpus::new(builds)).data(caller);
+    Ok(Json(schema.execute(request).await))
+}
+
+#[utoipa::path(
+    description = "GraphiQL page to write and run GraphQL queries.",
+    responses((status = 200, body = String, content_type = "text/html"))
+)]
+#[get("/graphql")]
+async fn get_graphiql() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+/// Records the count and latency of every request under the route it matched,
+/// and serves it within a span carrying its request ID.
+async fn track_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let request_id = request_id(&request);
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %method,
+        route = %route,
+        client = field::Empty,
+        status = field::Empty,
+    );
+    let mut response = next.call(request).instrument(span.clone()).await;
+    let status = match &response {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    span.record("status", status.as_u16());
+    record_request(&method, &route, status.as_u16(), start.elapsed());
+    if let Ok(response) = &mut response
+        && let Ok(value) = HeaderValue::from_str(&request_id)
+    {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    response
+}
+
+/// The request ID sent by the client, when it is a short printable token, or
+/// a new random one.
+fn request_id(request: &ServiceRequest) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AuthError::InvalidKey | AuthError::MissingKey => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            AuthError::QuotaExceeded { retry_after, .. } => {
+                response.insert_header((RETRY_AFTER, retry_after.to_string()))
+            }
+            AuthError::InvalidKey | AuthError::MissingKey => {
+                response.insert_header((WWW_AUTHENTICATE, "Bearer"))
+            }
+        };
+        response
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
+}
+
+impl ResponseError for CollectionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CollectionError::NotFound(_) => StatusCode::NOT_FOUND,
+            CollectionError::Duplicate(_) => StatusCode::CONFLICT,
+            CollectionError::InvalidName(_)
+            | CollectionError::NoSuchStep { .. }
+            | CollectionError::UnsupportedVersion(_) => StatusCode::BAD_REQUEST,
+            CollectionError::Io(_) | CollectionError::Parse(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// The caller resolved by [`authorize`], or an unlimited one when the server
+/// runs without authentication.
+impl FromRequest for Caller {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let caller = request.extensions().get::<Caller>().cloned();
+        ready(Ok(caller.unwrap_or_else(Caller::unlimited)))
+    }
+}
+
+/// The API key of a request, from `X-Api-Key` or `Authorization: Bearer`.
+fn api_key(request: &ServiceRequest) -> Option<&str> {
+    let header = |name| request.headers().get(name)?.to_str().ok();
+    header(API_KEY_HEADER)
+        .or_else(|| header(AUTHORIZATION.as_str())?.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Resolves the caller of every request from its API key, and turns away
+/// unknown keys and callers over their request quota.
+async fn authorize(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let open = OPEN_PATHS
+        .iter()
+        .any(|path| request.path() == *path || request.path().starts_with("/docs/"));
+    if !open && let Some(authenticator) = request.app_data::<web::Data<Authenticator>>() {
+        let address = request
+            .peer_addr()
+            .map(|address| address.ip().to_string())
+            .unwrap_or_default();
+        let caller = authenticator.authenticate(api_key(&request), &address)?;
+        Span::current().record("client", caller.get_id().to_string());
+        request.extensions_mut().insert(caller);
+    }
+    next.call(request).await
+}
+
+/// CORS policy letting browsers on `origins` call the API.
+fn cors(origins: &[String]) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(["GET", "POST", "DELETE"])
+        .allowed_headers([
+            AUTHORIZATION,
+            CONTENT_TYPE,
+            HeaderName::from_static(API_KEY_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            IF_NONE_MATCH,
+        ])
+        .expose_headers([
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            RETRY_AFTER,
+            ETAG,
+        ])
+        .max_age(3600);
+    if origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin))
+    }
+}
+
+/// Loads the certificate chain and private key the server is configured with.
+fn tls_config(cert: &str, key: &str) -> io::Result<rustls::ServerConfig> {
+    let invalid = |path: &str, e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e))
+    };
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert, &e))?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| invalid(key, &e))?;
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Serves the API on the configured address, over HTTPS when a certificate is configured.
 #[actix_web::main]
-pub async fn run(port: u16) -> std::io::Result<()> {
-    HttpServer::new(|| {
+pub async fn run(
+    config: ServerConfig,
+    store: BuildStore,
+    classifier: OpenerClassifier,
+    jobs: JobManager,
+    authenticator: Authenticator,
+    library: LibraryStore,
+) -> io::Result<()> {
+    let index = web::Data::new(StoreIndex::new(store.clone()));
+    let served_tags = web::Data::new(ServedTags::default());
+    let store = web::Data::new(store);
+    let library = web::Data::new(library);
+    let authenticator = web::Data::new(authenticator);
+    let cors_origins = config.cors_origins.clone();
+    let compress = config.compress;
+    let jobs = web::Data::new(jobs);
+    let classifier = web::Data::new(classifier);
+    // Shared by every worker so that connections to Spawning Tool are pooled
+    let client = web::Data::new(AsyncHttpClient::new());
+    let schema = web::Data::new(graphql::schema(
+        classifier.get_ref().clone(),
+        client.get_ref().clone(),
+    ));
+    let server = HttpServer::new(move || {
         App::new()
-            .service(get_by_id)
-            .service(get_latest)
-            .service(get_latest_default)
-            .service(get_segment)
-            .service(get_count)
-    })
-    .bind(("127.0.0.1", port))?
+            .wrap(from_fn(conditional_get))
+            .wrap(Condition::new(compress, Compress::default()))
+            .wrap(from_fn(authorize))
+            .wrap(from_fn(track_requests))
+            // Outermost, so that preflight requests are answered before authorization
+            .wrap(Condition::new(
+                !cors_origins.is_empty(),
+                cors(&cors_origins),
+            ))
+            .app_data(authenticator.clone())
+            .app_data(store.clone())
+            .app_data(index.clone())
+            .app_data(served_tags.clone())
+            .app_data(library.clone())
+            .app_data(classifier.clone())
+            .app_data(client.clone())
+            .app_data(jobs.clone())
+            .app_data(schema.clone())
+            .configure(routes)
+            .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", ApiDoc::openapi()))
+            .service(web::redirect("/docs", "/docs/"))
+    });
+    let address = (config.bind.as_str(), config.port);
+    match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => server.bind_rustls_0_23(address, tls_config(cert, key)?)?,
+        (None, None) => server.bind(address)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "tls_cert and tls_key must be set together",
+            ));
+        }
+    }
     .run()
     .await
 }
@@ -0,0 +1,516 @@
+use crate::config::{AuthConfig, QuotaConfig};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::error;
+
+/// File of the issued API keys, kept in the build store directory
+pub const KEYS_FILE: &str = "keys.json";
+/// Prefix of issued API keys, telling them apart from other secrets
+const KEY_PREFIX: &str = "bw_";
+/// Clients tracked before the least recently seen is forgotten
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyError {
+    Io(String),
+    Parse(String),
+    /// A key is already issued under this name
+    Duplicate(String),
+    /// No unrevoked key has this name
+    NotFound(String),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Io(msg) => write!(f, "Could not access API keys: {}", msg),
+            KeyError::Parse(msg) => write!(f, "Invalid API keys file: {}", msg),
+            KeyError::Duplicate(name) => write!(f, "A key is already issued to {}", name),
+            KeyError::NotFound(name) => write!(f, "No key is issued to {}", name),
+        }
+    }
+}
+
+/// Why a request is turned away.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The API key is unknown or revoked
+    InvalidKey,
+    /// No API key was given and anonymous access is off
+    MissingKey,
+    /// The named quota is used up for the next `retry_after` seconds
+    QuotaExceeded { quota: String, retry_after: u64 },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::InvalidKey => write!(f, "Invalid or revoked API key"),
+            AuthError::MissingKey => write!(f, "An API key is required"),
+            AuthError::QuotaExceeded { quota, retry_after } => write!(
+                f,
+                "Quota of {} exceeded, retry in {} seconds",
+                quota, retry_after
+            ),
+        }
+    }
+}
+
+/// SHA-256 of an API key in hex, stored instead of the key itself.
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// An API key issued to a client. Only the hash of the key is kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    name: String,
+    hash: String,
+    created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revoked: Option<DateTime<Utc>>,
+    /// Limits of this key, instead of those of the configured quota
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quota: Option<QuotaConfig>,
+}
+
+impl ApiKey {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn get_revoked(&self) -> Option<DateTime<Utc>> {
+        self.revoked
+    }
+
+    pub fn get_quota(&self) -> Option<QuotaConfig> {
+        self.quota
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.is_some()
+    }
+}
+
+/// Keys as last read from the keys file.
+#[derive(Default)]
+struct LoadedKeys {
+    modified: Option<SystemTime>,
+    keys: Vec<ApiKey>,
+}
+
+/// API keys issued to clients, kept in a JSON file. The file is read again
+/// whenever it changes, so keys issued or revoked from the CLI apply to a
+/// running server.
+pub struct KeyStore {
+    path: PathBuf,
+    loaded: Mutex<LoadedKeys>,
+}
+
+impl KeyStore {
+    /// Opens the keys file, which is created when the first key is issued.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, KeyError> {
+        let store = KeyStore {
+            path: path.as_ref().to_path_buf(),
+            loaded: Mutex::new(LoadedKeys::default()),
+        };
+        store.reload(&mut store.loaded.lock().unwrap())?;
+        Ok(store)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the keys file again if it changed since it was last read.
+    fn reload(&self, loaded: &mut LoadedKeys) -> Result<(), KeyError> {
+        let modified = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                *loaded = LoadedKeys::default();
+                return Ok(());
+            }
+            Err(e) => return Err(KeyError::Io(e.to_string())),
+        };
+        if modified.is_some() && modified == loaded.modified {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&self.path).map_err(|e| KeyError::Io(e.to_string()))?;
+        loaded.keys = serde_json::from_str(&content).map_err(|e| KeyError::Parse(e.to_string()))?;
+        loaded.modified = modified;
+        Ok(())
+    }
+
+    /// Replaces the keys file, through a temporary file so that readers
+    /// never see it half written.
+    fn write(&self, keys: &[ApiKey]) -> Result<(), KeyError> {
+        let json = serde_json::to_string_pretty(keys).map_err(|e| KeyError::Io(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| KeyError::Io(e.to_string()))?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, json)
+            .and_then(|_| fs::rename(&temp, &self.path))
+            .map_err(|e| KeyError::Io(e.to_string()))
+    }
+
+    /// Lists the keys, revoked ones included, in the order they were issued.
+    pub fn list(&self) -> Result<Vec<ApiKey>, KeyError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        Ok(loaded.keys.clone())
+    }
+
+    /// Issues a new key to `name`, limited by `quota` instead of the
+    /// configured one if given. Returns the key, which is only shown here.
+    pub fn issue(
+        &self,
+        name: &str,
+        quota: Option<QuotaConfig>,
+    ) -> Result<(ApiKey, String), KeyError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        if loaded
+            .keys
+            .iter()
+            .any(|key| key.name == name && !key.is_revoked())
+        {
+            return Err(KeyError::Duplicate(name.to_string()));
+        }
+        let secret = format!("{}{}", KEY_PREFIX, uuid::Uuid::new_v4().simple());
+        let key = ApiKey {
+            name: name.to_string(),
+            hash: hash_key(&secret),
+            created: Utc::now(),
+            revoked: None,
+            quota,
+        };
+        let mut keys = loaded.keys.clone();
+        keys.push(key.clone());
+        self.write(&keys)?;
+        loaded.keys = keys;
+        loaded.modified = None;
+        Ok((key, secret))
+    }
+
+    /// Revokes the key issued to `name`.
+    pub fn revoke(&self, name: &str) -> Result<ApiKey, KeyError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        let mut keys = loaded.keys.clone();
+        let key = keys
+            .iter_mut()
+            .find(|key| key.name == name && !key.is_revoked())
+            .ok_or_else(|| KeyError::NotFound(name.to_string()))?;
+        key.revoked = Some(Utc::now());
+        let key = key.clone();
+        self.write(&keys)?;
+        loaded.keys = keys;
+        loaded.modified = None;
+        Ok(key)
+    }
+
+    /// The unrevoked key matching `secret`, if any.
+    pub fn find(&self, secret: &str) -> Result<Option<ApiKey>, KeyError> {
+        let hash = hash_key(secret);
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        Ok(loaded
+            .keys
+            .iter()
+            .find(|key| key.hash == hash && !key.is_revoked())
+            .cloned())
+    }
+}
+
+/// Who a request is served for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientId {
+    /// A client with an API key, by the name it was issued to
+    Key(String),
+    /// A client without an API key, by its address
+    Anonymous(String),
+    /// Requests not subject to authentication
+    Unlimited,
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientId::Key(name) => write!(f, "key:{}", name),
+            ClientId::Anonymous(address) => write!(f, "anonymous:{}", address),
+            ClientId::Unlimited => write!(f, "unlimited"),
+        }
+    }
+}
+
+/// Uses of a resource in the current fixed window.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: Instant,
+    used: u64,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Window {
+            start: now,
+            used: 0,
+        }
+    }
+
+    /// Takes `count` uses out of `limit` per `period`, or returns the
+    /// seconds until the window resets.
+    fn take(&mut self, now: Instant, period: Duration, limit: u32, count: u64) -> Result<(), u64> {
+        if now.duration_since(self.start) >= period {
+            *self = Window::new(now);
+        }
+        if self.used + count > limit as u64 {
+            let reset = (self.start + period).saturating_duration_since(now);
+            return Err(reset.as_secs().max(1));
+        }
+        self.used += count;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    requests: Window,
+    fetches: Window,
+}
+
+/// Requests and upstream fetches of every client in the current windows.
+#[derive(Default)]
+pub struct QuotaTracker {
+    usage: Mutex<RecentUsage>,
+}
+
+/// Usage of the most recently seen clients, each with the sequence number of
+/// its last use.
+#[derive(Default)]
+struct RecentUsage {
+    clients: HashMap<ClientId, (Usage, u64)>,
+    /// Tracked clients by sequence number, least recently seen first
+    order: BTreeMap<u64, ClientId>,
+    next: u64,
+}
+
+impl RecentUsage {
+    /// Usage of `client`, now its most recently seen. Forgets the least
+    /// recently seen client to make room for a new one.
+    fn touch(&mut self, client: &ClientId, now: Instant) -> &mut Usage {
+        let sequence = self.next;
+        self.next += 1;
+        match self.clients.get_mut(client) {
+            Some((_, last)) => {
+                self.order.remove(last);
+                *last = sequence;
+            }
+            None => {
+                if self.clients.len() >= MAX_TRACKED_CLIENTS
+                    && let Some((_, oldest)) = self.order.pop_first()
+                {
+                    self.clients.remove(&oldest);
+                }
+                let usage = Usage {
+                    requests: Window::new(now),
+                    fetches: Window::new(now),
+                };
+                self.clients.insert(client.clone(), (usage, sequence));
+            }
+        }
+        self.order.insert(sequence, client.clone());
+        &mut self.clients.get_mut(client).unwrap().0
+    }
+}
+
+impl QuotaTracker {
+    fn take(
+        &self,
+        client: &ClientId,
+        limit: Option<u32>,
+        count: u64,
+        requests: bool,
+    ) -> Result<(), AuthError> {
+        let Some(limit) = limit else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.touch(client, now);
+        let (window, period, quota) = if requests {
+            (&mut usage.requests, MINUTE, "requests per minute")
+        } else {
+            (&mut usage.fetches, HOUR, "upstream fetches per hour")
+        };
+        window
+            .take(now, period, limit, count)
+            .map_err(|retry_after| AuthError::QuotaExceeded {
+                quota: format!("{} {}", limit, quota),
+                retry_after,
+            })
+    }
+
+    /// Counts a request of `client` against `quota`.
+    pub fn take_request(&self, client: &ClientId, quota: &QuotaConfig) -> Result<(), AuthError> {
+        self.take(client, quota.requests_per_minute, 1, true)
+    }
+
+    /// Counts `count` build pages fetched for `client` against `quota`.
+    pub fn take_fetches(
+        &self,
+        client: &ClientId,
+        quota: &QuotaConfig,
+        count: u64,
+    ) -> Result<(), AuthError> {
+        self.take(client, quota.fetches_per_hour, count, false)
+    }
+}
+
+/// The client a request is served for, with its quota.
+#[derive(Clone)]
+pub struct Caller {
+    id: ClientId,
+    quota: QuotaConfig,
+    usage: Arc<QuotaTracker>,
+}
+
+impl Caller {
+    /// A caller without limits, for requests not subject to authentication.
+    pub fn unlimited() -> Self {
+        Caller {
+            id: ClientId::Unlimited,
+            quota: QuotaConfig::default(),
+            usage: Arc::default(),
+        }
+    }
+
+    pub fn get_id(&self) -> &ClientId {
+        &self.id
+    }
+
+    pub fn get_quota(&self) -> &QuotaConfig {
+        &self.quota
+    }
+
+    /// Name of the API key of the caller, if it has one.
+    pub fn get_key_name(&self) -> Option<&str> {
+        match &self.id {
+            ClientId::Key(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Turns away callers without an API key, which may only read. Callers
+    /// not subject to authentication pass.
+    pub fn require_key(&self) -> Result<(), AuthError> {
+        match self.id {
+            ClientId::Anonymous(_) => Err(AuthError::MissingKey),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the caller may see and change what was made under the API key
+    /// `owner`: only its own, or everything when not subject to authentication.
+    pub fn owns(&self, owner: Option<&str>) -> bool {
+        match &self.id {
+            ClientId::Unlimited => true,
+            ClientId::Key(name) => owner == Some(name.as_str()),
+            ClientId::Anonymous(_) => false,
+        }
+    }
+
+    /// Counts `count` build pages about to be fetched from Spawning Tool,
+    /// failing without counting them when they exceed the quota.
+    pub fn charge_fetches(&self, count: u64) -> Result<(), AuthError> {
+        self.usage.take_fetches(&self.id, &self.quota, count)
+    }
+}
+
+/// Tells the clients of the server apart by their API keys, and enforces
+/// their quotas.
+pub struct Authenticator {
+    config: AuthConfig,
+    /// Names of the keys of the config file, by hash
+    config_keys: HashMap<String, String>,
+    keys: KeyStore,
+    usage: Arc<QuotaTracker>,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthConfig, keys: KeyStore) -> Self {
+        let config_keys = config
+            .keys
+            .iter()
+            .map(|(name, key)| (hash_key(key), name.clone()))
+            .collect();
+        Authenticator {
+            config,
+            config_keys,
+            keys,
+            usage: Arc::default(),
+        }
+    }
+
+    /// Resolves the caller of a request from its API key, or from its
+    /// `address` when it has none, and counts the request against its quota.
+    pub fn authenticate(&self, key: Option<&str>, address: &str) -> Result<Caller, AuthError> {
+        let (id, quota) = match key {
+            Some(key) => match self.config_keys.get(&hash_key(key)) {
+                Some(name) => (ClientId::Key(name.clone()), self.config.quota),
+                None => match self.keys.find(key) {
+                    Ok(Some(api_key)) => {
+                        let quota = match api_key.quota {
+                            // Limits the key leaves unset are those of the config
+                            Some(quota) => QuotaConfig {
+                                requests_per_minute: quota
+                                    .requests_per_minute
+                                    .or(self.config.quota.requests_per_minute),
+                                fetches_per_hour: quota
+                                    .fetches_per_hour
+                                    .or(self.config.quota.fetches_per_hour),
+                            },
+                            None => self.config.quota,
+                        };
+                        (ClientId::Key(api_key.name), quota)
+                    }
+                    Ok(None) => return Err(AuthError::InvalidKey),
+                    Err(e) => {
+                        error!(error = %e, "Could not read API keys");
+                        return Err(AuthError::InvalidKey);
+                    }
+                },
+            },
+            None if self.config.allow_anonymous => (
+                ClientId::Anonymous(address.to_string()),
+                self.config.anonymous_quota,
+            ),
+            None => return Err(AuthError::MissingKey),
+        };
+        self.usage.take_request(&id, &quota)?;
+        Ok(Caller {
+            id,
+            quota,
+            usage: self.usage.clone(),
+        })
+    }
+}
@@ -0,0 +1,637 @@
+use crate::build_order::{ActionType, BuildOrder, BuildType, Difficulty};
+use crate::build_parser::{PageCache, fetch_build_order_with};
+use crate::build_store::{BuildStore, LOCAL_ID_BASE};
+use crate::output::{OutputFormat, format_timestamp, render_build};
+use crate::query::Query;
+use crate::stats::matchup;
+use chrono::{NaiveTime, Timelike};
+use console::strip_ansi_codes;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::subscriber::NoSubscriber;
+
+/// Vote scores the list can be narrowed down to, cycled through with `v`
+const MIN_VOTES: [u32; 6] = [0, 50, 60, 70, 80, 90];
+/// Rows moved by Page Up and Page Down
+const PAGE: usize = 10;
+/// Steps kept in view above the next one while the timer runs
+const TIMER_CONTEXT: usize = 3;
+/// How often the screen is redrawn while waiting for keys, for the timer
+const TICK: Duration = Duration::from_millis(250);
+
+const BUILD_TYPES: [BuildType; 6] = [
+    BuildType::Cheese,
+    BuildType::AllIn,
+    BuildType::Timing,
+    BuildType::Economic,
+    BuildType::CoOp,
+    BuildType::None,
+];
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+/// Side effects a key asks the browser's caller to carry out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    None,
+    Quit,
+    /// Fetch a fresh copy of the build from Spawning Tool
+    Refresh(u32),
+    /// Write the build to a file
+    Export(u32),
+}
+
+/// Which part of the screen the arrow keys move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    List,
+    Detail,
+}
+
+/// The next value of `options` after `current`, wrapping around to `None`.
+fn cycle<T: Clone + PartialEq>(options: &[T], current: &Option<T>) -> Option<T> {
+    match current {
+        None => options.first().cloned(),
+        Some(value) => options
+            .iter()
+            .position(|option| option == value)
+            .and_then(|position| options.get(position + 1))
+            .cloned(),
+    }
+}
+
+fn action_style(action_type: &ActionType) -> Style {
+    match action_type {
+        ActionType::Worker => Style::new().add_modifier(Modifier::DIM),
+        ActionType::Unit => Style::new().fg(Color::Green),
+        ActionType::Building => Style::new().fg(Color::Yellow),
+        ActionType::Upgrade => Style::new().fg(Color::Magenta),
+        ActionType::Action => Style::new().fg(Color::Cyan),
+    }
+}
+
+/// State of the collection browser: the list of builds, its filters and
+/// the build shown in detail.
+pub struct Browser {
+    builds: Vec<BuildOrder>,
+    /// Positions in `builds` of the builds matching the filters
+    visible: Vec<usize>,
+    list: ListState,
+    focus: Focus,
+    matchups: Vec<String>,
+    matchup: Option<String>,
+    build_type: Option<BuildType>,
+    difficulty: Option<Difficulty>,
+    min_votes: u32,
+    query: Option<Query>,
+    /// Query being typed after `/`
+    query_input: Option<String>,
+    detail_scroll: u16,
+    /// When the timer of the selected build was started
+    timer: Option<Instant>,
+    status: Option<String>,
+}
+
+impl Browser {
+    pub fn new(builds: Vec<BuildOrder>) -> Self {
+        let mut matchups: Vec<String> = builds.iter().map(matchup).collect();
+        matchups.sort_unstable();
+        matchups.dedup();
+        let mut browser = Browser {
+            builds,
+            visible: Vec::new(),
+            list: ListState::default(),
+            focus: Focus::List,
+            matchups,
+            matchup: None,
+            build_type: None,
+            difficulty: None,
+            min_votes: 0,
+            query: None,
+            query_input: None,
+            detail_scroll: 0,
+            timer: None,
+            status: None,
+        };
+        browser.apply_filters();
+        browser
+    }
+
+    /// The builds matching the filters, in list order.
+    pub fn visible(&self) -> Vec<&BuildOrder> {
+        self.visible
+            .iter()
+            .map(|position| &self.builds[*position])
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&BuildOrder> {
+        let position = self.visible.get(self.list.selected()?)?;
+        Some(&self.builds[*position])
+    }
+
+    pub fn get_focus(&self) -> Focus {
+        self.focus
+    }
+
+    pub fn get_status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status(&mut self, status: impl Into<String>) {
+        self.status = Some(status.into());
+    }
+
+    pub fn is_timing(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    /// Replaces the stored copy of a build, as after fetching a fresh one.
+    pub fn replace(&mut self, build_order: BuildOrder) {
+        if let Some(build) = self
+            .builds
+            .iter_mut()
+            .find(|build| build.get_id() == build_order.get_id())
+        {
+            *build = build_order;
+        }
+        self.apply_filters();
+    }
+
+    fn matches(&self, build_order: &BuildOrder) -> bool {
+        self.matchup
+            .as_ref()
+            .is_none_or(|name| &matchup(build_order) == name)
+            && self
+                .build_type
+                .as_ref()
+                .is_none_or(|build_type| build_order.get_build_type() == build_type)
+            && self
+                .difficulty
+                .as_ref()
+                .is_none_or(|difficulty| build_order.get_difficulty() == Some(difficulty))
+            && (self.min_votes == 0
+                || build_order
+                    .get_votes()
+                    .is_some_and(|votes| votes.get_score() >= self.min_votes))
+            && self
+                .query
+                .as_ref()
+                .is_none_or(|query| query.matches(build_order))
+    }
+
+    /// Recomputes the visible builds, keeping the selected one when it still
+    /// matches.
+    fn apply_filters(&mut self) {
+        let selected = self.selected().map(BuildOrder::get_id);
+        self.visible = (0..self.builds.len())
+            .filter(|position| self.matches(&self.builds[*position]))
+            .collect();
+        let position = selected
+            .and_then(|id| {
+                self.visible
+                    .iter()
+                    .position(|position| self.builds[*position].get_id() == id)
+            })
+            .or((!self.visible.is_empty()).then_some(0));
+        self.list.select(position);
+        if self.selected().map(BuildOrder::get_id) != selected {
+            self.select(position);
+        }
+    }
+
+    fn select(&mut self, position: Option<usize>) {
+        self.list.select(position);
+        self.detail_scroll = 0;
+        self.timer = None;
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0);
+        let last = self.visible.len() - 1;
+        let position = current.saturating_add_signed(offset).min(last);
+        if Some(position) != self.list.selected() {
+            self.select(Some(position));
+        }
+    }
+
+    fn scroll_detail(&mut self, offset: i16) {
+        self.detail_scroll = self.detail_scroll.saturating_add_signed(offset);
+    }
+
+    /// Starts the timer of the selected build, or stops it.
+    pub fn toggle_timer(&mut self, now: Instant) {
+        self.timer = match self.timer {
+            Some(_) => None,
+            None => self.selected().map(|_| now),
+        };
+    }
+
+    /// Game time since the timer was started.
+    pub fn elapsed(&self, now: Instant) -> Option<Duration> {
+        self.timer.map(|start| now.saturating_duration_since(start))
+    }
+
+    /// Handles a key press, returning what the caller has to do.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Command {
+        if let Some(input) = &mut self.query_input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.query_input = None,
+                KeyCode::Enter => {
+                    let input = self.query_input.take().unwrap_or_default();
+                    match Query::parse(&input) {
+                        Ok(query) => {
+                            self.query = (!input.trim().is_empty()).then_some(query);
+                            self.status = None;
+                            self.apply_filters();
+                        }
+                        Err(e) => self.status = Some(format!("Invalid query: {}", e)),
+                    }
+                }
+                _ => {}
+            }
+            return Command::None;
+        }
+
+        self.status = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Command::Quit,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::List => Focus::Detail,
+                    Focus::Detail => Focus::List,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => match self.focus {
+                Focus::List => self.move_selection(-1),
+                Focus::Detail => self.scroll_detail(-1),
+            },
+            KeyCode::Down | KeyCode::Char('j') => match self.focus {
+                Focus::List => self.move_selection(1),
+                Focus::Detail => self.scroll_detail(1),
+            },
+            KeyCode::PageUp => match self.focus {
+                Focus::List => self.move_selection(-(PAGE as isize)),
+                Focus::Detail => self.scroll_detail(-(PAGE as i16)),
+            },
+            KeyCode::PageDown => match self.focus {
+                Focus::List => self.move_selection(PAGE as isize),
+                Focus::Detail => self.scroll_detail(PAGE as i16),
+            },
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX),
+            KeyCode::Char('m') => {
+                self.matchup = cycle(&self.matchups, &self.matchup);
+                self.apply_filters();
+            }
+            KeyCode::Char('t') => {
+                self.build_type = cycle(&BUILD_TYPES, &self.build_type);
+                self.apply_filters();
+            }
+            KeyCode::Char('d') => {
+                self.difficulty = cycle(&DIFFICULTIES, &self.difficulty);
+                self.apply_filters();
+            }
+            KeyCode::Char('v') => {
+                self.min_votes = cycle(&MIN_VOTES, &Some(self.min_votes)).unwrap_or(0);
+                self.apply_filters();
+            }
+            KeyCode::Char('c') => {
+                self.matchup = None;
+                self.build_type = None;
+                self.difficulty = None;
+                self.min_votes = 0;
+                self.query = None;
+                self.apply_filters();
+            }
+            KeyCode::Char('/') => {
+                self.query_input = Some(
+                    self.query
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                );
+            }
+            KeyCode::Char('s') => self.toggle_timer(Instant::now()),
+            KeyCode::Char('r') => {
+                if let Some(build_order) = self.selected() {
+                    return Command::Refresh(build_order.get_id());
+                }
+            }
+            KeyCode::Char('e') => {
+                if let Some(build_order) = self.selected() {
+                    return Command::Export(build_order.get_id());
+                }
+            }
+            _ => {}
+        }
+        Command::None
+    }
+
+    fn filter_line(&self) -> Line<'static> {
+        if let Some(input) = &self.query_input {
+            return Line::from(vec![
+                "Query: ".bold(),
+                Span::raw(input.clone()),
+                "_".slow_blink(),
+            ]);
+        }
+        let value = |value: Option<String>| value.unwrap_or_else(|| "All".to_string());
+        let mut spans = vec![
+            "Matchup: ".bold(),
+            Span::raw(value(self.matchup.clone())),
+            "  Type: ".bold(),
+            Span::raw(value(self.build_type.as_ref().map(ToString::to_string))),
+            "  Difficulty: ".bold(),
+            Span::raw(value(self.difficulty.as_ref().map(ToString::to_string))),
+            "  Votes: ".bold(),
+            Span::raw(match self.min_votes {
+                0 => "All".to_string(),
+                score => format!(">= {}%", score),
+            }),
+        ];
+        if let Some(query) = &self.query {
+            spans.extend(["  Query: ".bold(), Span::raw(query.to_string())]);
+        }
+        spans.push(Span::raw(format!(
+            "  ({}/{})",
+            self.visible.len(),
+            self.builds.len()
+        )));
+        Line::from(spans)
+    }
+
+    fn help_line(&self) -> Line<'static> {
+        if let Some(status) = &self.status {
+            return Line::from(status.clone().yellow());
+        }
+        let help = if self.query_input.is_some() {
+            "Enter apply  Esc cancel  e.g. race:Z vs:P votes>=60 has:\"Roach Warren\"<3:00"
+        } else {
+            "↑↓ move  Tab scroll details  m t d v filter  / query  c clear  r refresh  s timer  e export  q quit"
+        };
+        Line::from(help.dim())
+    }
+
+    fn detail_lines(build_order: &BuildOrder, elapsed: Option<Duration>) -> (Vec<Line<'_>>, usize) {
+        let mut lines = Vec::new();
+        let mut header = |key: &'static str, value: String| {
+            if !value.is_empty() {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{}: ", key), Style::new().bold()),
+                    Span::raw(value),
+                ]));
+            }
+        };
+        header("Matchup", matchup(build_order));
+        header("Type", build_order.get_build_type().to_string());
+        header("Author", build_order.get_creator().to_string());
+        header("Patch", build_order.get_patch().to_string());
+        header(
+            "Difficulty",
+            build_order
+                .get_difficulty()
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+        );
+        header(
+            "Published",
+            build_order
+                .get_published()
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+        );
+        header(
+            "Votes",
+            build_order
+                .get_votes()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        );
+        header("VOD", build_order.get_vod().unwrap_or("").to_string());
+        if let Some(description) = build_order.get_description() {
+            lines.push(Line::raw(""));
+            lines.extend(description.lines().map(|line| Line::raw(line.to_string())));
+        }
+        lines.push(Line::raw(""));
+
+        let first_step = lines.len();
+        let next_step = elapsed.and_then(|elapsed| {
+            build_order.get_entries().iter().position(|entry| {
+                entry
+                    .get_timestamp()
+                    .is_some_and(|time| time.num_seconds_from_midnight() as u64 > elapsed.as_secs())
+            })
+        });
+        for (index, entry) in build_order.get_entries().iter().enumerate() {
+            let time = entry
+                .get_timestamp()
+                .map(format_timestamp)
+                .unwrap_or_default();
+            let mut spans = vec![
+                Span::raw(format!("{:>3} ", entry.get_supply())),
+                Span::styled(format!("{:>5} ", time), Style::new().dim()),
+            ];
+            for (i, action) in entry.get_actions().iter().enumerate() {
+                if i > 0 {
+                    spans.push(Span::raw(", "));
+                }
+                spans.push(Span::styled(
+                    action.get_name().to_string(),
+                    action_style(action.get_action_type()),
+                ));
+            }
+            if let Some(comment) = entry.get_comment() {
+                spans.push(Span::styled(
+                    format!(" # {}", comment),
+                    Style::new().italic(),
+                ));
+            }
+            let line = Line::from(spans);
+            lines.push(match next_step {
+                Some(next) if index == next => line.reversed(),
+                Some(next) if index < next => line.dim(),
+                _ => line,
+            });
+        }
+        let scroll = next_step.map_or(0, |next| (first_step + next).saturating_sub(TIMER_CONTEXT));
+        (lines, scroll)
+    }
+
+    /// Draws the filter bar, the list, the selected build and the key help.
+    pub fn render(&mut self, frame: &mut Frame, now: Instant) {
+        let [filters, main, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+        frame.render_widget(Paragraph::new(self.filter_line()), filters);
+        frame.render_widget(Paragraph::new(self.help_line()), help);
+
+        let focused = |focus: Focus| {
+            if self.focus == focus {
+                Style::new().fg(Color::Cyan)
+            } else {
+                Style::new()
+            }
+        };
+        let items: Vec<ListItem> = self
+            .visible()
+            .into_iter()
+            .map(|build_order| {
+                let votes = build_order
+                    .get_votes()
+                    .map(|votes| format!("{:>3}%", votes.get_score()))
+                    .unwrap_or_else(|| "    ".to_string());
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", matchup(build_order)), Style::new().bold()),
+                    Span::styled(format!("{} ", votes), Style::new().dim()),
+                    Span::raw(build_order.get_name().to_string()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(" Builds ")
+                    .border_style(focused(Focus::List)),
+            )
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        self.render_detail(frame, detail_area, focused(Focus::Detail), now);
+    }
+
+    fn render_detail(&self, frame: &mut Frame, area: Rect, border: Style, now: Instant) {
+        let block = Block::bordered().border_style(border);
+        let Some(build_order) = self.selected() else {
+            frame.render_widget(
+                Paragraph::new("No build order matches the filters").block(block),
+                area,
+            );
+            return;
+        };
+        let (lines, timer_scroll) = Browser::detail_lines(build_order, self.elapsed(now));
+        let scroll = if self.timer.is_some() {
+            timer_scroll as u16
+        } else {
+            self.detail_scroll
+        };
+        let mut block = block.title(format!(
+            " {} ({}) ",
+            build_order.get_name(),
+            build_order.get_id()
+        ));
+        if let Some(elapsed) = self.elapsed(now) {
+            let time = NaiveTime::MIN + chrono::Duration::seconds(elapsed.as_secs() as i64);
+            block = block.title(
+                Line::from(format!(" Timer {} ", format_timestamp(&time)))
+                    .right_aligned()
+                    .red()
+                    .bold(),
+            );
+        }
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0)),
+            area,
+        );
+    }
+}
+
+/// Fetches a fresh copy of a build into the store and the browser.
+fn refresh(browser: &mut Browser, store: &BuildStore, id: u32) {
+    if id >= LOCAL_ID_BASE {
+        browser.set_status(format!("Build {} was imported, not from Spawning Tool", id));
+        return;
+    }
+    match fetch_build_order_with(id, PageCache::Refresh) {
+        Ok(build_order) => match store.save(&build_order) {
+            Ok(()) => {
+                browser.replace(build_order);
+                browser.set_status(format!("Build {} refreshed", id));
+            }
+            Err(e) => browser.set_status(format!("Error saving build {}: {}", id, e)),
+        },
+        Err(e) => browser.set_status(format!("Error fetching build {}: {}", id, e)),
+    }
+}
+
+/// Writes a build to `<id>.<extension>` in the current directory.
+fn export(browser: &mut Browser, id: u32, format: OutputFormat) {
+    let Some(build_order) = browser.selected().filter(|build| build.get_id() == id) else {
+        return;
+    };
+    let path = PathBuf::from(format!("{}.{}", id, format.extension()));
+    let output = render_build(build_order, format);
+    match std::fs::write(&path, strip_ansi_codes(&output).as_ref()) {
+        Ok(()) => browser.set_status(format!("Build {} written to {}", id, path.display())),
+        Err(e) => browser.set_status(format!("Error writing {}: {}", path.display(), e)),
+    }
+}
+
+fn run_loop(
+    terminal: &mut DefaultTerminal,
+    browser: &mut Browser,
+    store: &BuildStore,
+    format: OutputFormat,
+) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| browser.render(frame, Instant::now()))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match browser.handle_key(key) {
+            Command::None => {}
+            Command::Quit => return Ok(()),
+            Command::Refresh(id) => {
+                browser.set_status(format!("Fetching build {}...", id));
+                terminal.draw(|frame| browser.render(frame, Instant::now()))?;
+                refresh(browser, store, id);
+            }
+            Command::Export(id) => export(browser, id, format),
+        }
+    }
+}
+
+/// Opens the browser over the local collection, exporting builds in `format`.
+/// Fails when there is no terminal to draw on.
+pub fn run(store: &BuildStore, format: OutputFormat) -> io::Result<()> {
+    let mut browser = Browser::new(store.load_all());
+    // Restoring would write escape sequences to an output that may not be a
+    // terminal, only raw mode is undone in case it was turned on
+    let mut terminal = ratatui::try_init().inspect_err(|_| {
+        let _ = ratatui::crossterm::terminal::disable_raw_mode();
+    })?;
+    // Log lines would be drawn over the screen, errors are shown in the status line instead
+    let _silenced = tracing::subscriber::set_default(NoSubscriber::default());
+    let result = run_loop(&mut terminal, &mut browser, store, format);
+    ratatui::restore();
+    result
+}
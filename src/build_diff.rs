@@ -0,0 +1,332 @@
+use crate::build_order::{BuildOrder, OrderEntry};
+use chrono::NaiveTime;
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum DiffKind {
+    Matched,
+    Inserted,
+    Removed,
+    Reordered,
+}
+
+impl fmt::Display for DiffKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            DiffKind::Matched => "=",
+            DiffKind::Inserted => "+",
+            DiffKind::Removed => "-",
+            DiffKind::Reordered => "~",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// A step of one of the two compared build orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StepRef {
+    index: usize,
+    supply: u8,
+    timestamp: Option<NaiveTime>,
+    actions: String,
+}
+
+impl StepRef {
+    fn new(index: usize, entry: &OrderEntry) -> Self {
+        StepRef {
+            index,
+            supply: entry.get_supply(),
+            timestamp: entry.get_timestamp().copied(),
+            actions: action_key(entry),
+        }
+    }
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+    pub fn get_supply(&self) -> u8 {
+        self.supply
+    }
+    pub fn get_timestamp(&self) -> Option<&NaiveTime> {
+        self.timestamp.as_ref()
+    }
+    pub fn get_actions(&self) -> &str {
+        &self.actions
+    }
+}
+
+/// One line of the alignment between two build orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiffStep {
+    kind: DiffKind,
+    left: Option<StepRef>,
+    right: Option<StepRef>,
+    /// Timing difference in seconds (right minus left), when both steps are timed
+    time_delta: Option<i64>,
+    /// Supply difference (right minus left)
+    supply_delta: Option<i32>,
+}
+
+impl DiffStep {
+    fn new(kind: DiffKind, left: Option<StepRef>, right: Option<StepRef>) -> Self {
+        let (time_delta, supply_delta) = match (&left, &right) {
+            (Some(l), Some(r)) => (
+                match (l.timestamp, r.timestamp) {
+                    (Some(lt), Some(rt)) => Some((rt - lt).num_seconds()),
+                    _ => None,
+                },
+                Some(r.supply as i32 - l.supply as i32),
+            ),
+            _ => (None, None),
+        };
+        DiffStep {
+            kind,
+            left,
+            right,
+            time_delta,
+            supply_delta,
+        }
+    }
+    pub fn get_kind(&self) -> DiffKind {
+        self.kind
+    }
+    pub fn get_left(&self) -> Option<&StepRef> {
+        self.left.as_ref()
+    }
+    pub fn get_right(&self) -> Option<&StepRef> {
+        self.right.as_ref()
+    }
+    pub fn get_time_delta(&self) -> Option<i64> {
+        self.time_delta
+    }
+    pub fn get_supply_delta(&self) -> Option<i32> {
+        self.supply_delta
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiffSummary {
+    pub matched: usize,
+    pub inserted: usize,
+    pub removed: usize,
+    pub reordered: usize,
+}
+
+/// Alignment of the entries of two build orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BuildDiff {
+    left_id: u32,
+    left_name: String,
+    right_id: u32,
+    right_name: String,
+    summary: DiffSummary,
+    steps: Vec<DiffStep>,
+}
+
+impl BuildDiff {
+    pub fn get_left_id(&self) -> u32 {
+        self.left_id
+    }
+    pub fn get_right_id(&self) -> u32 {
+        self.right_id
+    }
+    pub fn get_summary(&self) -> &DiffSummary {
+        &self.summary
+    }
+    pub fn get_steps(&self) -> &[DiffStep] {
+        &self.steps
+    }
+}
+
+/// Normalised representation of the actions of a step, used to align entries.
+fn action_key(entry: &OrderEntry) -> String {
+    entry
+        .get_actions()
+        .iter()
+        .map(|action| action.get_name().trim().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Aligns the entries of two build orders by action sequence.
+///
+/// Entries are matched along the longest common subsequence of their actions.
+/// A removed step whose actions reappear as an inserted step elsewhere is
+/// reported once, as reordered, at its position in the right build order.
+pub fn diff_builds(left: &BuildOrder, right: &BuildOrder) -> BuildDiff {
+    let left_entries = left.get_entries();
+    let right_entries = right.get_entries();
+    let left_keys: Vec<String> = left_entries.iter().map(action_key).collect();
+    let right_keys: Vec<String> = right_entries.iter().map(action_key).collect();
+
+    // Longest common subsequence table, filled from the end
+    let (n, m) = (left_keys.len(), right_keys.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if left_keys[i] == right_keys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && left_keys[i] == right_keys[j] {
+            steps.push(DiffStep::new(
+                DiffKind::Matched,
+                Some(StepRef::new(i, &left_entries[i])),
+                Some(StepRef::new(j, &right_entries[j])),
+            ));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            steps.push(DiffStep::new(
+                DiffKind::Inserted,
+                None,
+                Some(StepRef::new(j, &right_entries[j])),
+            ));
+            j += 1;
+        } else {
+            steps.push(DiffStep::new(
+                DiffKind::Removed,
+                Some(StepRef::new(i, &left_entries[i])),
+                None,
+            ));
+            i += 1;
+        }
+    }
+
+    // Pair removed steps with inserted steps carrying the same actions
+    let mut removed: HashMap<String, Vec<usize>> = HashMap::new();
+    for (position, step) in steps.iter().enumerate() {
+        if let (DiffKind::Removed, Some(left)) = (step.kind, &step.left) {
+            removed
+                .entry(left.actions.clone())
+                .or_default()
+                .push(position);
+        }
+    }
+    for positions in removed.values_mut() {
+        positions.reverse();
+    }
+    let mut dropped = vec![false; steps.len()];
+    for position in 0..steps.len() {
+        if steps[position].kind != DiffKind::Inserted {
+            continue;
+        }
+        let key = steps[position].right.as_ref().unwrap().actions.clone();
+        if let Some(removed_position) = removed.get_mut(&key).and_then(|p| p.pop()) {
+            let left = steps[removed_position].left.clone();
+            let right = steps[position].right.clone();
+            steps[position] = DiffStep::new(DiffKind::Reordered, left, right);
+            dropped[removed_position] = true;
+        }
+    }
+    let steps: Vec<DiffStep> = steps
+        .into_iter()
+        .zip(dropped)
+        .filter_map(|(step, dropped)| (!dropped).then_some(step))
+        .collect();
+
+    let mut summary = DiffSummary::default();
+    for step in &steps {
+        match step.kind {
+            DiffKind::Matched => summary.matched += 1,
+            DiffKind::Inserted => summary.inserted += 1,
+            DiffKind::Removed => summary.removed += 1,
+            DiffKind::Reordered => summary.reordered += 1,
+        }
+    }
+
+    BuildDiff {
+        left_id: left.get_id(),
+        left_name: left.get_name().to_string(),
+        right_id: right.get_id(),
+        right_name: right.get_name().to_string(),
+        summary,
+        steps,
+    }
+}
+
+fn format_step(step: Option<&StepRef>) -> String {
+    match step {
+        Some(step) => format!(
+            "{:>3} {:>7}  {}",
+            step.supply,
+            step.timestamp
+                .map(|t| t.format("%-M:%S").to_string())
+                .unwrap_or_default(),
+            step.actions
+        ),
+        None => String::new(),
+    }
+}
+
+fn format_delta(delta: Option<i64>) -> String {
+    match delta {
+        Some(0) | None => String::new(),
+        Some(d) if d > 0 => format!("+{}s", d),
+        Some(d) => format!("{}s", d),
+    }
+}
+
+/// Coloured terminal view of the diff.
+impl fmt::Display for BuildDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {} ({})",
+            style("---").red(),
+            self.left_name,
+            self.left_id
+        )?;
+        writeln!(
+            f,
+            "{} {} ({})",
+            style("+++").green(),
+            self.right_name,
+            self.right_id
+        )?;
+        for step in &self.steps {
+            let shown = match step.kind {
+                DiffKind::Removed => step.left.as_ref(),
+                _ => step.right.as_ref(),
+            };
+            let line = format!("{} {}", step.kind, format_step(shown));
+            let delta = format_delta(step.time_delta);
+            match step.kind {
+                DiffKind::Matched => {
+                    writeln!(f, "{}\t{}", style(line).dim(), style(delta).yellow())?
+                }
+                DiffKind::Inserted => writeln!(f, "{}", style(line).green())?,
+                DiffKind::Removed => writeln!(f, "{}", style(line).red())?,
+                DiffKind::Reordered => writeln!(
+                    f,
+                    "{}\t{} {}",
+                    style(line).cyan(),
+                    style(format!(
+                        "(was step {})",
+                        step.left.as_ref().unwrap().index + 1
+                    ))
+                    .dim(),
+                    style(delta).yellow()
+                )?,
+            }
+        }
+        write!(
+            f,
+            "{} matched, {} inserted, {} removed, {} reordered",
+            self.summary.matched,
+            self.summary.inserted,
+            self.summary.removed,
+            self.summary.reordered
+        )
+    }
+}
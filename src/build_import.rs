@@ -0,0 +1,45 @@
+use crate::build_order::{BuildOrder, BuildOrderError};
+use crate::salt::decode_salt;
+use crate::text_notation::parse_text_build;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Formats build orders can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Text,
+    Salt,
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            ImportFormat::Text => "text",
+            ImportFormat::Salt => "salt",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<ImportFormat, Self::Err> {
+        match input {
+            "text" => Ok(ImportFormat::Text),
+            "salt" => Ok(ImportFormat::Salt),
+            _ => Err(format!("Unknown import format: {}", input)),
+        }
+    }
+}
+
+/// Parses a build order from content in the given format.
+pub fn import_build(content: &str, format: ImportFormat) -> Result<BuildOrder, BuildOrderError> {
+    match format {
+        ImportFormat::Text => parse_text_build(content),
+        ImportFormat::Salt => decode_salt(content),
+    }
+}
@@ -3,8 +3,9 @@ use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum Race {
     Terran,
     Protoss,
@@ -38,7 +39,7 @@ impl FromStr for Race {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum BuildType {
     Cheese,
     AllIn,
@@ -78,7 +79,7 @@ impl FromStr for BuildType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum Difficulty {
     Easy,
     Medium,
@@ -109,7 +110,7 @@ impl FromStr for Difficulty {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Votes {
     score: u32,
     count: u32,
@@ -135,7 +136,7 @@ impl Votes {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum ActionType {
     Worker,
     Unit,
@@ -172,7 +173,7 @@ impl FromStr for ActionType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Action {
     action_type: ActionType,
     name: String,
@@ -182,6 +183,12 @@ impl Action {
     pub fn new(action_type: ActionType, name: String) -> Self {
         Action { action_type, name }
     }
+    pub fn get_action_type(&self) -> &ActionType {
+        &self.action_type
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
 }
 
 impl fmt::Display for Action {
@@ -190,7 +197,7 @@ impl fmt::Display for Action {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct OrderEntry {
     supply: u8,
     timestamp: Option<NaiveTime>,
@@ -201,7 +208,7 @@ pub struct OrderEntry {
 impl OrderEntry {
     pub fn new(supply: u8, time: String, actions: Vec<Action>, comment: String) -> Self {
         let timevec: Vec<u32> = time.split(':').map(|s| s.parse().unwrap_or(0)).collect();
-        let timestamp = if time.len() == 0 {
+        let timestamp = if time.is_empty() {
             None
         } else if timevec.len() == 2 {
             Some(NaiveTime::from_hms_opt(0, timevec[0], timevec[1]).unwrap())
@@ -219,9 +226,21 @@ impl OrderEntry {
             },
         }
     }
+    pub fn get_supply(&self) -> u8 {
+        self.supply
+    }
+    pub fn get_timestamp(&self) -> Option<&NaiveTime> {
+        self.timestamp.as_ref()
+    }
+    pub fn get_actions(&self) -> &[Action] {
+        &self.actions
+    }
+    pub fn get_comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BuildOrder {
     id: u32,
     name: String,
@@ -238,6 +257,12 @@ pub struct BuildOrder {
     entries: Vec<OrderEntry>,
 }
 
+impl Default for BuildOrder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BuildOrder {
     pub fn new() -> Self {
         BuildOrder {
@@ -295,6 +320,9 @@ impl BuildOrder {
     pub fn add_step(&mut self, entry: OrderEntry) {
         self.entries.push(entry);
     }
+    pub fn clear_steps(&mut self) {
+        self.entries.clear();
+    }
     pub fn get_id(&self) -> u32 {
         self.id
     }
@@ -336,12 +364,15 @@ impl BuildOrder {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum BuildOrderError {
     ParseError(String),
     InvalidData(String),
     HttpError(String),
     Cloaked,
+    NotFound,
+    RateLimited { retry_after: Option<u64> },
+    Redirected { location: String },
 }
 
 impl fmt::Display for BuildOrderError {
@@ -351,6 +382,14 @@ impl fmt::Display for BuildOrderError {
             BuildOrderError::InvalidData(msg) => write!(f, "Invalid Data: {}", msg),
             BuildOrderError::HttpError(msg) => write!(f, "HTTP Error: {}", msg),
             BuildOrderError::Cloaked => write!(f, "Build order is cloaked."),
+            BuildOrderError::NotFound => write!(f, "Build order does not exist."),
+            BuildOrderError::RateLimited {
+                retry_after: Some(seconds),
+            } => write!(f, "Rate limited, retry after {} seconds.", seconds),
+            BuildOrderError::RateLimited { retry_after: None } => write!(f, "Rate limited."),
+            BuildOrderError::Redirected { location } => {
+                write!(f, "Build order moved to {}", location)
+            }
         }
     }
 }
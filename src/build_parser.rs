@@ -2,12 +2,35 @@ use crate::build_order::{
     Action, ActionType, BuildOrder, BuildOrderError, BuildType, Difficulty, OrderEntry, Race,
 };
 use crate::build_regex::*;
-use crate::http_client::HttpClient;
+use crate::config::current;
+use crate::http_client::{AsyncHttpClient, HttpClient, Response};
+use crate::metrics::{record_cache_lookup, record_fetch, record_parse_failure};
+use actix_web::rt::task::spawn_blocking;
 use chrono::NaiveDate;
 use onig::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{Span, instrument};
 
+/// Default base URL of the build pages
 pub const BUILD_URL: &str = "https://lotv.spawningtool.com/build/";
+/// Path Spawning Tool redirects to when a build is only visible to its author
+const LOGIN_PATH: &str = "/accounts/login/";
+/// Default number of seconds a cached build page is used before fetching it again
+pub const CACHE_TTL: u64 = 7 * 24 * 3600;
+
+/// Whether a fetch may be answered from the page cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageCache {
+    /// Uses a cached page younger than `cache_ttl`
+    Use,
+    /// Fetches the page again, then caches it
+    Refresh,
+}
 
 struct HeaderContent {
     name: String,
@@ -23,8 +46,15 @@ struct DetailsContent {
     difficulty: Option<String>,
 }
 
+/// Counts a field that could not be parsed and returns the matching error.
+fn parse_failure(field: &str, message: String) -> BuildOrderError {
+    record_parse_failure(field);
+    BuildOrderError::ParseError(message)
+}
+
 /// Extracts the name of the build order from the HTML content.
-fn extract_header(html_content: &str) -> HeaderContent {
+#[instrument(level = "trace", skip_all)]
+fn extract_header(html_content: &str) -> Result<HeaderContent, BuildOrderError> {
     let mut content = HeaderContent {
         name: String::new(),
         player_race: String::new(),
@@ -32,7 +62,12 @@ fn extract_header(html_content: &str) -> HeaderContent {
         build_type: String::new(),
     };
     let re = Regex::new(HEADER_REGEX).unwrap();
-    let captures = re.captures(html_content).unwrap();
+    let captures = re.captures(html_content).ok_or_else(|| {
+        parse_failure(
+            "header",
+            "Failed to find build header in HTML content".to_string(),
+        )
+    })?;
     if let Some(name) = captures.at(1) {
         content.name = name.to_string();
     }
@@ -45,10 +80,11 @@ fn extract_header(html_content: &str) -> HeaderContent {
     if let Some(build_type) = captures.at(4) {
         content.build_type = build_type.to_string();
     }
-    content
+    Ok(content)
 }
 
 /// Extracts the details of the build order from the HTML content.
+#[instrument(level = "trace", skip_all)]
 fn extract_details(html_content: &str) -> DetailsContent {
     let mut content = DetailsContent {
         author: String::new(),
@@ -61,10 +97,10 @@ fn extract_details(html_content: &str) -> DetailsContent {
         if let Some(author) = captures.at(1) {
             content.author = author.to_string();
         }
-        if let Some(published) = captures.at(2) {
-            if !published.is_empty() {
-                content.published = Some(published.to_string());
-            }
+        if let Some(published) = captures.at(2)
+            && !published.is_empty()
+        {
+            content.published = Some(published.to_string());
         }
         if let Some(patch) = captures.at(3) {
             content.patch = patch.to_string();
@@ -72,11 +108,14 @@ fn extract_details(html_content: &str) -> DetailsContent {
         if let Some(difficulty) = captures.at(4) {
             content.difficulty = Some(difficulty.to_string());
         }
+    } else {
+        record_parse_failure("details");
     }
     content
 }
 
 /// Extracts the description of the build order from the HTML content.
+#[instrument(level = "trace", skip_all)]
 fn extract_description(html_content: &str) -> Option<String> {
     let re = Regex::new(DESCRIPTION_REGEX).unwrap();
     re.captures(html_content)
@@ -84,6 +123,7 @@ fn extract_description(html_content: &str) -> Option<String> {
 }
 
 /// Extracts the VOD from the HTML content.
+#[instrument(level = "trace", skip_all)]
 fn extract_vod(html_content: &str) -> Option<String> {
     let re = Regex::new(VOD_REGEX).unwrap();
     re.captures(html_content)
@@ -91,6 +131,7 @@ fn extract_vod(html_content: &str) -> Option<String> {
 }
 
 /// Extracts the votes from the HTML content.
+#[instrument(level = "trace", skip_all)]
 fn extract_votes(html_content: &str) -> Option<(u32, u32)> {
     let re = Regex::new(VOTES_REGEX).unwrap();
     re.captures(html_content).and_then(|captures| {
@@ -105,6 +146,7 @@ fn extract_votes(html_content: &str) -> Option<(u32, u32)> {
 }
 
 /// Extracts the steps of the build order from the HTML content.
+#[instrument(level = "trace", skip_all)]
 fn extract_steps(html_content: &str) -> Result<Vec<OrderEntry>, BuildOrderError> {
     let mut steps = Vec::new();
     let re = Regex::new(BUILD_TABLE_REGEX).unwrap();
@@ -124,7 +166,7 @@ fn extract_steps(html_content: &str) -> Result<Vec<OrderEntry>, BuildOrderError>
                 if action.len() < 3 {
                     continue; // Skip invalid actions
                 }
-                let action_type = ActionType::from_str(&action.at(1).unwrap()).unwrap();
+                let action_type = ActionType::from_str(action.at(1).unwrap()).unwrap();
                 let name = action.at(2).unwrap().to_string();
                 actions.push(Action::new(action_type, name));
             }
@@ -132,7 +174,8 @@ fn extract_steps(html_content: &str) -> Result<Vec<OrderEntry>, BuildOrderError>
             steps.push(OrderEntry::new(supply, time, actions, comment));
         }
     } else {
-        return Err(BuildOrderError::ParseError(
+        return Err(parse_failure(
+            "steps",
             "Failed to find build table in HTML content".to_string(),
         ));
     }
@@ -140,33 +183,49 @@ fn extract_steps(html_content: &str) -> Result<Vec<OrderEntry>, BuildOrderError>
 }
 
 impl BuildOrder {
-    fn set_header(&mut self, header: HeaderContent) {
+    fn set_header(&mut self, header: HeaderContent) -> Result<(), BuildOrderError> {
+        let race = |race: &str| {
+            Race::from_str(race)
+                .map_err(|_| parse_failure("race", format!("Unknown race: {}", race)))
+        };
         self.set_name(header.name);
-        self.set_player_race(Race::from_str(&header.player_race).unwrap());
-        self.set_opponent_race(Race::from_str(&header.opponent_race).unwrap());
-        self.set_build_type(BuildType::from_str(&header.build_type).unwrap());
+        self.set_player_race(race(&header.player_race)?);
+        self.set_opponent_race(race(&header.opponent_race)?);
+        self.set_build_type(BuildType::from_str(&header.build_type).map_err(|_| {
+            parse_failure(
+                "build_type",
+                format!("Unknown build type: {}", header.build_type),
+            )
+        })?);
+        Ok(())
     }
 
+    /// Sets the details of the build. Unreadable optional details are counted and left out.
     fn set_details(&mut self, details: DetailsContent) {
         self.set_creator(details.author);
         if let Some(published) = details.published {
-            if let Ok(date) = NaiveDate::parse_from_str(&published, "%b %d, %Y") {
-                self.set_published(date);
+            match NaiveDate::parse_from_str(&published, "%b %d, %Y") {
+                Ok(date) => self.set_published(date),
+                Err(_) => record_parse_failure("published"),
             }
         }
         self.set_patch(details.patch);
         if let Some(difficulty) = details.difficulty {
-            self.set_difficulty(Difficulty::from_str(&difficulty).unwrap());
+            match Difficulty::from_str(&difficulty) {
+                Ok(difficulty) => self.set_difficulty(difficulty),
+                Err(_) => record_parse_failure("difficulty"),
+            }
         }
     }
 }
 
 /// Parses a build order from the given HTML content and returns a `BuildOrder`.
+#[instrument(level = "debug", skip(html_content))]
 pub fn parse_build_order(html_content: &str, id: u32) -> Result<BuildOrder, BuildOrderError> {
     let mut build_order = BuildOrder::new();
     build_order.set_id(id);
-    let header = extract_header(html_content);
-    build_order.set_header(header);
+    let header = extract_header(html_content)?;
+    build_order.set_header(header)?;
 
     // Parse the build order description
     if let Some(description) = extract_description(html_content) {
@@ -196,21 +255,179 @@ pub fn parse_build_order(html_content: &str, id: u32) -> Result<BuildOrder, Buil
     Ok(build_order)
 }
 
+/// Path of a build page in the configured cache directory, if there is one.
+fn cached_page_path(build_id: u32) -> Option<PathBuf> {
+    let cache = current().storage.cache.as_ref()?;
+    Some(Path::new(cache).join(format!("{}.html", build_id)))
+}
+
+/// The build order of a cached page younger than `cache_ttl`. Pages that no
+/// longer parse are fetched again.
+fn read_cached_page(path: &Path, build_id: u32) -> Option<BuildOrder> {
+    let ttl = Duration::from_secs(current().storage.cache_ttl);
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    if modified.elapsed().unwrap_or_default() >= ttl {
+        return None;
+    }
+    parse_build_order(&fs::read_to_string(path).ok()?, build_id).ok()
+}
+
+/// Keeps a build page that parsed in the cache, through a temporary file so
+/// that readers never see it half written. Failing to do so only means
+/// fetching it again next time.
+fn cache_page(path: &Path, body: &str) {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // Unique, since several workers may fetch the same build at once
+    let temp = path.with_extension(format!(
+        "html.{}.{}.tmp",
+        process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    if fs::write(&temp, body)
+        .and_then(|_| fs::rename(&temp, path))
+        .is_err()
+    {
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+/// Fetches a build order from Spawning Tool, or from the page cache when configured.
 pub fn fetch_build_order(build_id: u32) -> Result<BuildOrder, BuildOrderError> {
-    let url = format!("{}{}/", BUILD_URL, build_id);
-    match HttpClient::fetch_url(&url) {
-        Ok(response) => {
-            if response.status_code == 302 {
-                return Err(BuildOrderError::Cloaked);
-            }
-            if response.status_code != 200 {
-                return Err(BuildOrderError::HttpError(format!(
-                    "Failed to fetch build order (URL: {} ) (Status: {})",
-                    url, response.status_code,
-                )));
-            }
-            parse_build_order(&response.body, build_id)
+    fetch_build_order_with(build_id, PageCache::Use)
+}
+
+/// Fetches a build order from Spawning Tool, or from the page cache when
+/// configured and `cache` allows it.
+#[instrument(level = "debug", err(Display, level = "debug"))]
+pub fn fetch_build_order_with(
+    build_id: u32,
+    cache: PageCache,
+) -> Result<BuildOrder, BuildOrderError> {
+    let base_url = &current().spawning_tool.build_url;
+    let Some(path) = cached_page_path(build_id) else {
+        return fetch_build_order_from(base_url, build_id);
+    };
+    if cache == PageCache::Use {
+        let cached = read_cached_page(&path, build_id);
+        record_cache_lookup(cached.is_some());
+        if let Some(build_order) = cached {
+            return Ok(build_order);
         }
-        Err(e) => Err(BuildOrderError::HttpError(e)),
+    }
+    let body = fetch_page(&format!("{}{}/", base_url, build_id))?;
+    let build_order = parse_build_order(&body, build_id)?;
+    cache_page(&path, &body);
+    Ok(build_order)
+}
+
+/// Tells a redirect to the login page, which hides cloaked builds, from a moved build.
+fn redirect_error(location: Option<&str>) -> BuildOrderError {
+    match location {
+        Some(location) if !location.contains(LOGIN_PATH) => BuildOrderError::Redirected {
+            location: location.to_string(),
+        },
+        _ => BuildOrderError::Cloaked,
+    }
+}
+
+/// Fetches a build order from the build pages under `base_url`.
+pub fn fetch_build_order_from(
+    base_url: &str,
+    build_id: u32,
+) -> Result<BuildOrder, BuildOrderError> {
+    let body = fetch_page(&format!("{}{}/", base_url, build_id))?;
+    parse_build_order(&body, build_id)
+}
+
+/// Fetches the body of a build page.
+#[instrument(level = "debug", fields(status))]
+fn fetch_page(url: &str) -> Result<String, BuildOrderError> {
+    let result = HttpClient::fetch_url(url)
+        .map_err(BuildOrderError::HttpError)
+        .and_then(|response| check_response(url, &response).map(|_| response.body));
+    record_fetch(&result);
+    result
+}
+
+/// Fetches a build order from Spawning Tool without blocking the current
+/// thread, or from the page cache when configured.
+#[instrument(level = "debug", skip(client), err(Display, level = "debug"))]
+pub async fn fetch_build_order_async(
+    client: &AsyncHttpClient,
+    build_id: u32,
+) -> Result<BuildOrder, BuildOrderError> {
+    let base_url = &current().spawning_tool.build_url;
+    let Some(path) = cached_page_path(build_id) else {
+        return fetch_build_order_from_async(client, base_url, build_id).await;
+    };
+    let cached = {
+        let path = path.clone();
+        let span = Span::current();
+        spawn_blocking(move || span.in_scope(|| read_cached_page(&path, build_id)))
+            .await
+            .ok()
+            .flatten()
+    };
+    record_cache_lookup(cached.is_some());
+    if let Some(build_order) = cached {
+        return Ok(build_order);
+    }
+    let body = fetch_page_async(client, &format!("{}{}/", base_url, build_id)).await?;
+    let span = Span::current();
+    spawn_blocking(move || {
+        let build_order = span.in_scope(|| parse_build_order(&body, build_id))?;
+        cache_page(&path, &body);
+        Ok(build_order)
+    })
+    .await
+    .map_err(|e| BuildOrderError::ParseError(e.to_string()))?
+}
+
+/// Fetches a build order from the build pages under `base_url`, parsing it on
+/// the blocking thread pool so that the async runtime stays responsive.
+pub async fn fetch_build_order_from_async(
+    client: &AsyncHttpClient,
+    base_url: &str,
+    build_id: u32,
+) -> Result<BuildOrder, BuildOrderError> {
+    let body = fetch_page_async(client, &format!("{}{}/", base_url, build_id)).await?;
+    let span = Span::current();
+    spawn_blocking(move || span.in_scope(|| parse_build_order(&body, build_id)))
+        .await
+        .map_err(|e| BuildOrderError::ParseError(e.to_string()))?
+}
+
+/// Fetches the body of a build page without blocking the current thread.
+#[instrument(level = "debug", skip(client), fields(status))]
+async fn fetch_page_async(client: &AsyncHttpClient, url: &str) -> Result<String, BuildOrderError> {
+    let result = client
+        .fetch_url(url)
+        .await
+        .map_err(BuildOrderError::HttpError)
+        .and_then(|response| check_response(url, &response).map(|_| response.body));
+    record_fetch(&result);
+    result
+}
+
+/// Maps the status of a build page response to the matching error.
+fn check_response(url: &str, response: &Response) -> Result<(), BuildOrderError> {
+    Span::current().record("status", response.status_code);
+    match response.status_code {
+        200 => Ok(()),
+        301 | 302 | 303 | 307 | 308 => Err(redirect_error(response.header("Location"))),
+        404 | 410 => Err(BuildOrderError::NotFound),
+        429 => Err(BuildOrderError::RateLimited {
+            // Only the delay-seconds form of Retry-After is understood
+            retry_after: response
+                .header("Retry-After")
+                .and_then(|value| value.parse().ok()),
+        }),
+        status => Err(BuildOrderError::HttpError(format!(
+            "Failed to fetch build order (URL: {} ) (Status: {})",
+            url, status,
+        ))),
     }
 }
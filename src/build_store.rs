@@ -0,0 +1,153 @@
+use crate::build_order::BuildOrder;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Default directory holding the local build collection
+pub const DEFAULT_STORE_PATH: &str = "builds";
+/// First ID given to imported builds, far above Spawning Tool IDs
+pub const LOCAL_ID_BASE: u32 = 1_000_000_000;
+
+/// Local collection of build orders, stored as one JSON file per build.
+#[derive(Debug, Clone)]
+pub struct BuildStore {
+    root: PathBuf,
+    /// Saves made through this store and its clones
+    saves: Arc<AtomicU64>,
+}
+
+/// State of a store's contents, changing whenever a build is saved to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoreVersion {
+    modified: Option<SystemTime>,
+    saves: u64,
+}
+
+impl BuildStore {
+    /// Opens the store at the given directory, creating it if needed.
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        fs::create_dir_all(root.as_ref())?;
+        Ok(BuildStore {
+            root: root.as_ref().to_path_buf(),
+            saves: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    fn build_path(&self, id: u32) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+
+    /// Saves a build order, replacing any previously stored copy. Written
+    /// through a temporary file, so that readers and crashes never see it half
+    /// written.
+    pub fn save(&self, build_order: &BuildOrder) -> io::Result<()> {
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+        let json = serde_json::to_string(build_order)?;
+        let path = self.build_path(build_order.get_id());
+        // Unique, since jobs and requests may save the same build at once
+        let temp = path.with_extension(format!(
+            "json.{}.{}.tmp",
+            process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, json)
+            .and_then(|_| fs::rename(&temp, &path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&temp);
+            })?;
+        self.saves.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Current version of the contents. Saves from this process are counted
+    /// and those from other processes show in the directory's modification
+    /// time.
+    pub fn version(&self) -> StoreVersion {
+        StoreVersion {
+            modified: fs::metadata(&self.root)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            saves: self.saves.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Loads a stored build order, if present.
+    pub fn load(&self, id: u32) -> Option<BuildOrder> {
+        let content = fs::read_to_string(self.build_path(id)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.build_path(id).exists()
+    }
+
+    /// Lists the IDs of the stored build orders, in ascending order.
+    pub fn ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = match fs::read_dir(&self.root) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    if path.extension()? != "json" {
+                        return None;
+                    }
+                    path.file_stem()?.to_str()?.parse::<u32>().ok()
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Next free ID for a build that does not come from Spawning Tool.
+    pub fn next_local_id(&self) -> u32 {
+        self.ids()
+            .last()
+            .filter(|id| **id >= LOCAL_ID_BASE)
+            .map_or(LOCAL_ID_BASE, |id| id + 1)
+    }
+
+    /// Saves a build that does not come from Spawning Tool under the next free
+    /// local ID, and returns it. The ID is claimed by creating its file, so
+    /// concurrent saves, even from other processes, never share one.
+    pub fn save_local(&self, build_order: &mut BuildOrder) -> io::Result<u32> {
+        loop {
+            let id = self.next_local_id();
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.build_path(id))
+            {
+                Ok(_) => {
+                    build_order.set_id(id);
+                    return self
+                        .save(build_order)
+                        .inspect_err(|_| {
+                            let _ = fs::remove_file(self.build_path(id));
+                        })
+                        .map(|_| id);
+                }
+                // Taken since it was read, try the one after
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Loads every stored build order, skipping unreadable files.
+    pub fn load_all(&self) -> Vec<BuildOrder> {
+        self.ids()
+            .into_iter()
+            .filter_map(|id| self.load(id))
+            .collect()
+    }
+}
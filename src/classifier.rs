@@ -0,0 +1,196 @@
+use crate::build_order::{BuildOrder, OrderEntry, Race};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use utoipa::ToSchema;
+
+/// Rules shipped with Build Warren, used when no rules file is given
+pub const DEFAULT_RULES: &str = include_str!("../data/openers.toml");
+
+fn default_max_supply() -> u8 {
+    30
+}
+
+/// A named opener and the conditions a build must meet to carry its label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenerRule {
+    pub name: String,
+    pub race: Option<Race>,
+    pub vs: Option<Race>,
+    #[serde(default = "default_max_supply")]
+    pub max_supply: u8,
+    #[serde(default)]
+    pub sequence: Vec<String>,
+    #[serde(default)]
+    pub min_counts: HashMap<String, usize>,
+    #[serde(default)]
+    pub max_counts: HashMap<String, usize>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClassifierError {
+    Io(String),
+    Parse(String),
+}
+
+impl fmt::Display for ClassifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassifierError::Io(msg) => write!(f, "Could not read opener rules: {}", msg),
+            ClassifierError::Parse(msg) => write!(f, "Invalid opener rules: {}", msg),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    opener: Vec<OpenerRule>,
+}
+
+/// A build order summarised by its opener labels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ClassifiedBuild {
+    pub id: u32,
+    pub name: String,
+    pub openers: Vec<String>,
+}
+
+/// Rule-based classifier labelling builds with opener names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenerClassifier {
+    rules: Vec<OpenerRule>,
+}
+
+impl Default for OpenerClassifier {
+    fn default() -> Self {
+        OpenerClassifier::from_toml(DEFAULT_RULES).expect("Default opener rules are invalid")
+    }
+}
+
+impl OpenerClassifier {
+    /// Parses rules from the content of a TOML rules file.
+    pub fn from_toml(content: &str) -> Result<Self, ClassifierError> {
+        let file: RulesFile =
+            toml::from_str(content).map_err(|e| ClassifierError::Parse(e.to_string()))?;
+        Ok(OpenerClassifier { rules: file.opener })
+    }
+
+    /// Loads rules from a TOML file, or the default rules when no path is given.
+    pub fn load(path: Option<&str>) -> Result<Self, ClassifierError> {
+        match path {
+            Some(path) => {
+                let content =
+                    fs::read_to_string(path).map_err(|e| ClassifierError::Io(e.to_string()))?;
+                OpenerClassifier::from_toml(&content)
+            }
+            None => Ok(OpenerClassifier::default()),
+        }
+    }
+
+    pub fn get_rules(&self) -> &[OpenerRule] {
+        &self.rules
+    }
+
+    /// Labels of every rule matched by the build order, in rules file order.
+    pub fn classify(&self, build_order: &BuildOrder) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|rule| rule_matches(rule, build_order))
+            .map(|rule| rule.name.clone())
+            .collect()
+    }
+
+    /// Whether the build order carries the given label (case-insensitive).
+    pub fn has_label(&self, build_order: &BuildOrder, label: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| rule.name.eq_ignore_ascii_case(label))
+            .any(|rule| rule_matches(rule, build_order))
+    }
+
+    pub fn summarize(&self, build_order: &BuildOrder) -> ClassifiedBuild {
+        ClassifiedBuild {
+            id: build_order.get_id(),
+            name: build_order.get_name().to_string(),
+            openers: self.classify(build_order),
+        }
+    }
+
+    /// Classifies a collection, keeping the builds carrying `label` if one is given
+    /// and every labelled build otherwise.
+    pub fn search(&self, corpus: &[BuildOrder], label: Option<&str>) -> Vec<ClassifiedBuild> {
+        corpus
+            .iter()
+            .filter(|build_order| label.is_none_or(|label| self.has_label(build_order, label)))
+            .map(|build_order| self.summarize(build_order))
+            .filter(|classified| !classified.openers.is_empty())
+            .collect()
+    }
+}
+
+fn race_matches(expected: &Option<Race>, actual: &Race) -> bool {
+    match expected {
+        Some(race) => race == actual,
+        None => true,
+    }
+}
+
+fn rule_matches(rule: &OpenerRule, build_order: &BuildOrder) -> bool {
+    if !race_matches(&rule.race, build_order.get_player_race())
+        || !race_matches(&rule.vs, build_order.get_opponent_race())
+    {
+        return false;
+    }
+
+    let window: Vec<&OrderEntry> = build_order
+        .get_entries()
+        .iter()
+        .filter(|entry| entry.get_supply() <= rule.max_supply)
+        .collect();
+    let actions: Vec<&str> = window
+        .iter()
+        .flat_map(|entry| entry.get_actions())
+        .map(|action| action.get_name().trim())
+        .collect();
+
+    // First occurrences of the sequence must exist and be in order
+    let mut previous = None;
+    for name in &rule.sequence {
+        match actions.iter().position(|action| action == name) {
+            Some(position) if previous.is_none_or(|p| position > p) => previous = Some(position),
+            _ => return false,
+        }
+    }
+
+    let count = |name: &str| actions.iter().filter(|action| **action == name).count();
+    if rule.min_counts.iter().any(|(name, min)| count(name) < *min)
+        || rule.max_counts.iter().any(|(name, max)| count(name) > *max)
+    {
+        return false;
+    }
+
+    if !rule.mentions.is_empty() {
+        let mut texts = vec![build_order.get_name().to_lowercase()];
+        if let Some(description) = build_order.get_description() {
+            texts.push(description.to_lowercase());
+        }
+        texts.extend(
+            window
+                .iter()
+                .filter_map(|entry| entry.get_comment())
+                .map(|comment| comment.to_lowercase()),
+        );
+        let mentioned = rule.mentions.iter().any(|word| {
+            let word = word.to_lowercase();
+            texts.iter().any(|text| text.contains(&word))
+        });
+        if !mentioned {
+            return false;
+        }
+    }
+    true
+}
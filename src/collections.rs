@@ -0,0 +1,508 @@
+use crate::build_order::BuildOrder;
+use crate::build_store::{BuildStore, LOCAL_ID_BASE};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use utoipa::ToSchema;
+
+/// File of the favourites, collections and notes of every owner, kept in the
+/// build store directory apart from the builds so that syncing leaves it alone
+pub const LIBRARY_FILE: &str = "library.json";
+/// Owner of the library used from the CLI, shared with the server under an
+/// API key of the same name
+pub const LOCAL_OWNER: &str = "local";
+/// Version of the bundles written by this release
+pub const BUNDLE_VERSION: u32 = 1;
+/// Longest name accepted for a collection
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollectionError {
+    Io(String),
+    Parse(String),
+    /// A collection already has this name
+    Duplicate(String),
+    /// No collection has this name
+    NotFound(String),
+    /// The name is empty, too long or contains a `/`
+    InvalidName(String),
+    /// The build has no step with this number
+    NoSuchStep {
+        id: u32,
+        step: usize,
+    },
+    /// The bundle was written by a newer release
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for CollectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectionError::Io(msg) => write!(f, "Could not access the library: {}", msg),
+            CollectionError::Parse(msg) => write!(f, "Invalid library or bundle: {}", msg),
+            CollectionError::Duplicate(name) => {
+                write!(f, "A collection is already named {}", name)
+            }
+            CollectionError::NotFound(name) => write!(f, "No collection is named {}", name),
+            CollectionError::InvalidName(name) => write!(
+                f,
+                "Invalid collection name {:?}, names are 1 to {} characters without '/'",
+                name, MAX_NAME_LENGTH
+            ),
+            CollectionError::NoSuchStep { id, step } => {
+                write!(f, "Build {} has no step {}", id, step)
+            }
+            CollectionError::UnsupportedVersion(version) => write!(
+                f,
+                "Bundle version {} is newer than the supported version {}",
+                version, BUNDLE_VERSION
+            ),
+        }
+    }
+}
+
+fn check_name(name: &str) -> Result<(), CollectionError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH || name.contains('/') {
+        return Err(CollectionError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// A named list of build IDs, such as "ZvP ladder" or "tournament prep".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Collection {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// IDs of the builds, in the order they were added
+    builds: Vec<u32>,
+    created: DateTime<Utc>,
+}
+
+impl Collection {
+    pub fn new(name: &str, description: Option<String>) -> Result<Self, CollectionError> {
+        check_name(name)?;
+        Ok(Collection {
+            name: name.to_string(),
+            description: description.filter(|description| !description.trim().is_empty()),
+            builds: Vec::new(),
+            created: Utc::now(),
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn get_builds(&self) -> &[u32] {
+        &self.builds
+    }
+
+    pub fn get_created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.builds.contains(&id)
+    }
+}
+
+/// Private notes on a build: one about the whole build, and annotations on
+/// its steps by step number, starting at 1.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BuildNotes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    steps: BTreeMap<usize, String>,
+}
+
+impl BuildNotes {
+    pub fn get_note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn get_steps(&self) -> &BTreeMap<usize, String> {
+        &self.steps
+    }
+
+    /// The annotation on step `step`, starting at 1.
+    pub fn get_step(&self, step: usize) -> Option<&str> {
+        self.steps.get(&step).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.note.is_none() && self.steps.is_empty()
+    }
+}
+
+/// The favourites, collections and notes of one owner.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(default)]
+    favourites: BTreeSet<u32>,
+    #[serde(default)]
+    collections: BTreeMap<String, Collection>,
+    #[serde(default)]
+    notes: BTreeMap<u32, BuildNotes>,
+}
+
+impl Library {
+    pub fn get_favourites(&self) -> &BTreeSet<u32> {
+        &self.favourites
+    }
+
+    pub fn is_favourite(&self, id: u32) -> bool {
+        self.favourites.contains(&id)
+    }
+
+    /// Stars a build, returning whether it was not starred yet.
+    pub fn star(&mut self, id: u32) -> bool {
+        self.favourites.insert(id)
+    }
+
+    /// Unstars a build, returning whether it was starred.
+    pub fn unstar(&mut self, id: u32) -> bool {
+        self.favourites.remove(&id)
+    }
+
+    /// The collections, by name.
+    pub fn get_collections(&self) -> Vec<&Collection> {
+        self.collections.values().collect()
+    }
+
+    pub fn get_collection(&self, name: &str) -> Option<&Collection> {
+        self.collections.get(name)
+    }
+
+    pub fn create_collection(
+        &mut self,
+        name: &str,
+        description: Option<String>,
+    ) -> Result<&Collection, CollectionError> {
+        if self.collections.contains_key(name) {
+            return Err(CollectionError::Duplicate(name.to_string()));
+        }
+        let collection = Collection::new(name, description)?;
+        Ok(self
+            .collections
+            .entry(name.to_string())
+            .or_insert(collection))
+    }
+
+    pub fn delete_collection(&mut self, name: &str) -> Result<Collection, CollectionError> {
+        self.collections
+            .remove(name)
+            .ok_or_else(|| CollectionError::NotFound(name.to_string()))
+    }
+
+    /// Adds builds to the end of a collection, creating it if needed. Builds
+    /// already in the collection keep their place.
+    pub fn add_builds(&mut self, name: &str, ids: &[u32]) -> Result<&Collection, CollectionError> {
+        if !self.collections.contains_key(name) {
+            self.create_collection(name, None)?;
+        }
+        let collection = self.collections.get_mut(name).unwrap();
+        for id in ids {
+            if !collection.contains(*id) {
+                collection.builds.push(*id);
+            }
+        }
+        Ok(collection)
+    }
+
+    pub fn remove_builds(
+        &mut self,
+        name: &str,
+        ids: &[u32],
+    ) -> Result<&Collection, CollectionError> {
+        let collection = self
+            .collections
+            .get_mut(name)
+            .ok_or_else(|| CollectionError::NotFound(name.to_string()))?;
+        collection.builds.retain(|id| !ids.contains(id));
+        Ok(collection)
+    }
+
+    pub fn get_notes(&self, id: u32) -> Option<&BuildNotes> {
+        self.notes.get(&id)
+    }
+
+    /// Replaces the note on a build, or removes it when `note` is blank.
+    pub fn set_note(&mut self, id: u32, note: &str) {
+        let notes = self.notes.entry(id).or_default();
+        notes.note = Some(note.trim().to_string()).filter(|note| !note.is_empty());
+        self.prune_notes(id);
+    }
+
+    /// Replaces the annotation on step `step` of a build, starting at 1, or
+    /// removes it when `note` is blank.
+    pub fn set_step_note(
+        &mut self,
+        build_order: &BuildOrder,
+        step: usize,
+        note: &str,
+    ) -> Result<(), CollectionError> {
+        let id = build_order.get_id();
+        if step == 0 || step > build_order.get_entries().len() {
+            return Err(CollectionError::NoSuchStep { id, step });
+        }
+        let notes = self.notes.entry(id).or_default();
+        match note.trim() {
+            "" => notes.steps.remove(&step),
+            note => notes.steps.insert(step, note.to_string()),
+        };
+        self.prune_notes(id);
+        Ok(())
+    }
+
+    /// Removes the note and step annotations of a build.
+    pub fn clear_notes(&mut self, id: u32) -> Option<BuildNotes> {
+        self.notes.remove(&id)
+    }
+
+    fn prune_notes(&mut self, id: u32) {
+        if self.notes.get(&id).is_some_and(BuildNotes::is_empty) {
+            self.notes.remove(&id);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.favourites.is_empty() && self.collections.is_empty() && self.notes.is_empty()
+    }
+
+    /// Bundles a collection to share it, with the builds of `store` that are
+    /// not on Spawning Tool. Notes are private and only included when
+    /// `with_notes` is set.
+    pub fn bundle(
+        &self,
+        name: &str,
+        store: &BuildStore,
+        with_notes: bool,
+    ) -> Result<CollectionBundle, CollectionError> {
+        let collection = self
+            .get_collection(name)
+            .ok_or_else(|| CollectionError::NotFound(name.to_string()))?;
+        let notes = match with_notes {
+            true => collection
+                .builds
+                .iter()
+                .filter_map(|id| Some((*id, self.notes.get(id)?.clone())))
+                .collect(),
+            false => BTreeMap::new(),
+        };
+        Ok(CollectionBundle {
+            version: BUNDLE_VERSION,
+            collection: collection.clone(),
+            builds: collection
+                .builds
+                .iter()
+                .filter(|id| **id >= LOCAL_ID_BASE)
+                .filter_map(|id| store.load(*id))
+                .collect(),
+            notes,
+        })
+    }
+
+    /// Adds a bundled collection, under `name` if given. Builds from Spawning
+    /// Tool are only referenced by ID, so that a bundle cannot pass off its
+    /// own copy as the scraped one. The bundled builds of the sender are
+    /// saved to `store` under new local IDs, unless it already holds the
+    /// same build. Bundled notes are kept for builds without notes.
+    pub fn import_bundle(
+        &mut self,
+        bundle: CollectionBundle,
+        name: Option<&str>,
+        store: &BuildStore,
+    ) -> Result<&Collection, CollectionError> {
+        if bundle.version > BUNDLE_VERSION {
+            return Err(CollectionError::UnsupportedVersion(bundle.version));
+        }
+        let name = name.unwrap_or(&bundle.collection.name);
+        if self.collections.contains_key(name) {
+            return Err(CollectionError::Duplicate(name.to_string()));
+        }
+        let mut collection = Collection::new(name, bundle.collection.description)?;
+        let mut ids = BTreeMap::new();
+        for mut build_order in bundle.builds {
+            let id = build_order.get_id();
+            if id < LOCAL_ID_BASE || !bundle.collection.builds.contains(&id) {
+                continue;
+            }
+            match stored_copy(store, &build_order) {
+                Some(copy) => build_order.set_id(copy),
+                None => {
+                    store
+                        .save_local(&mut build_order)
+                        .map_err(|e| CollectionError::Io(e.to_string()))?;
+                }
+            }
+            ids.insert(id, build_order.get_id());
+        }
+        // Local IDs of builds missing from the bundle would point at
+        // unrelated builds of `store`, so they are dropped
+        let new_id = |id: &u32| match *id >= LOCAL_ID_BASE {
+            true => ids.get(id).copied(),
+            false => Some(*id),
+        };
+        collection.builds = bundle.collection.builds.iter().filter_map(new_id).collect();
+        for (id, notes) in &bundle.notes {
+            if let Some(id) = new_id(id) {
+                self.notes.entry(id).or_insert_with(|| notes.clone());
+            }
+        }
+        Ok(self
+            .collections
+            .entry(name.to_string())
+            .or_insert(collection))
+    }
+}
+
+/// ID of a local build of `store` identical to `build_order` but for its ID.
+fn stored_copy(store: &BuildStore, build_order: &BuildOrder) -> Option<u32> {
+    let mut build_order = build_order.clone();
+    store
+        .ids()
+        .into_iter()
+        .filter(|id| *id >= LOCAL_ID_BASE)
+        .find(|id| {
+            build_order.set_id(*id);
+            store.load(*id).is_some_and(|stored| {
+                serde_json::to_value(stored).ok() == serde_json::to_value(&build_order).ok()
+            })
+        })
+}
+
+/// A collection with its builds, to be shared with other players.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CollectionBundle {
+    version: u32,
+    collection: Collection,
+    /// The builds of the collection imported by the sender, which are not on
+    /// Spawning Tool
+    builds: Vec<BuildOrder>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    notes: BTreeMap<u32, BuildNotes>,
+}
+
+impl CollectionBundle {
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get_collection(&self) -> &Collection {
+        &self.collection
+    }
+
+    pub fn get_builds(&self) -> &[BuildOrder] {
+        &self.builds
+    }
+
+    pub fn get_notes(&self) -> &BTreeMap<u32, BuildNotes> {
+        &self.notes
+    }
+}
+
+/// Libraries as last read from the library file.
+#[derive(Default)]
+struct LoadedLibraries {
+    modified: Option<SystemTime>,
+    libraries: BTreeMap<String, Library>,
+}
+
+/// The libraries of every owner, kept in a JSON file. The file is read again
+/// whenever it changes, so changes made from the CLI apply to a running
+/// server.
+pub struct LibraryStore {
+    path: PathBuf,
+    loaded: Mutex<LoadedLibraries>,
+}
+
+impl LibraryStore {
+    /// Opens the library file, which is created on the first change.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CollectionError> {
+        let store = LibraryStore {
+            path: path.as_ref().to_path_buf(),
+            loaded: Mutex::new(LoadedLibraries::default()),
+        };
+        store.reload(&mut store.loaded.lock().unwrap())?;
+        Ok(store)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the library file again if it changed since it was last read.
+    fn reload(&self, loaded: &mut LoadedLibraries) -> Result<(), CollectionError> {
+        let modified = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                *loaded = LoadedLibraries::default();
+                return Ok(());
+            }
+            Err(e) => return Err(CollectionError::Io(e.to_string())),
+        };
+        if modified.is_some() && modified == loaded.modified {
+            return Ok(());
+        }
+        let content =
+            fs::read_to_string(&self.path).map_err(|e| CollectionError::Io(e.to_string()))?;
+        loaded.libraries =
+            serde_json::from_str(&content).map_err(|e| CollectionError::Parse(e.to_string()))?;
+        loaded.modified = modified;
+        Ok(())
+    }
+
+    /// Replaces the library file, through a temporary file so that readers
+    /// never see it half written.
+    fn write(&self, libraries: &BTreeMap<String, Library>) -> Result<(), CollectionError> {
+        let json = serde_json::to_string_pretty(libraries)
+            .map_err(|e| CollectionError::Io(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| CollectionError::Io(e.to_string()))?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, json)
+            .and_then(|_| fs::rename(&temp, &self.path))
+            .map_err(|e| CollectionError::Io(e.to_string()))
+    }
+
+    /// The library of `owner`, empty if they have none yet.
+    pub fn library(&self, owner: &str) -> Result<Library, CollectionError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        Ok(loaded.libraries.get(owner).cloned().unwrap_or_default())
+    }
+
+    /// Changes the library of `owner` with `change`, and saves it unless
+    /// `change` fails.
+    pub fn update<T>(
+        &self,
+        owner: &str,
+        change: impl FnOnce(&mut Library) -> Result<T, CollectionError>,
+    ) -> Result<T, CollectionError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        let mut libraries = loaded.libraries.clone();
+        let library = libraries.entry(owner.to_string()).or_default();
+        let result = change(library)?;
+        if library.is_empty() {
+            libraries.remove(owner);
+        }
+        self.write(&libraries)?;
+        loaded.libraries = libraries;
+        loaded.modified = None;
+        Ok(result)
+    }
+}
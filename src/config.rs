@@ -0,0 +1,390 @@
+use crate::build_parser::{BUILD_URL, CACHE_TTL};
+use crate::build_store::DEFAULT_STORE_PATH;
+use crate::handlers::FETCH_WORKERS;
+use crate::index_manager::{BUILDS_URL, LOWEST_INDEX};
+use crate::jobs::{DEFAULT_JOBS_PATH, JOB_WORKERS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+
+/// Config file read from the working directory when no other is given
+pub const DEFAULT_CONFIG_PATH: &str = "build_warren.toml";
+/// Prefix of the environment variables overriding config values, such as
+/// `BUILD_WARREN_SERVER_PORT` for `port` in `[server]`
+pub const ENV_PREFIX: &str = "BUILD_WARREN_";
+/// Environment variable naming the config file
+pub const CONFIG_PATH_ENV: &str = "BUILD_WARREN_CONFIG";
+
+/// Sections of the config file, matched against environment variable names
+const SECTIONS: [&str; 5] = ["spawning_tool", "server", "storage", "log", "auth"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    Io(String),
+    Parse(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(msg) => write!(f, "Could not read config: {}", msg),
+            ConfigError::Parse(msg) => write!(f, "Invalid config: {}", msg),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+impl LogLevel {
+    /// The level `steps` levels more verbose, or less verbose when negative.
+    pub fn shifted(self, steps: i16) -> LogLevel {
+        const LEVELS: [LogLevel; 6] = [
+            LogLevel::Off,
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Trace,
+        ];
+        let index = (self as i16 + steps).clamp(0, LEVELS.len() as i16 - 1);
+        LEVELS[index as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Where and how fast build orders are fetched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawningToolConfig {
+    /// Base URL of the build pages, followed by the build ID
+    pub build_url: String,
+    /// Page listing the newest builds, read for the highest build ID
+    pub builds_url: String,
+    pub lowest_index: u32,
+    /// Build orders fetched concurrently
+    pub workers: usize,
+    /// Most requests started per second, unlimited when unset
+    pub requests_per_second: Option<f64>,
+}
+
+impl Default for SpawningToolConfig {
+    fn default() -> Self {
+        SpawningToolConfig {
+            build_url: BUILD_URL.to_string(),
+            builds_url: BUILDS_URL.to_string(),
+            lowest_index: LOWEST_INDEX,
+            workers: FETCH_WORKERS,
+            requests_per_second: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub port: u16,
+    /// PEM certificate chain, served over HTTPS together with `tls_key`
+    pub tls_cert: Option<String>,
+    /// PEM private key of `tls_cert`
+    pub tls_key: Option<String>,
+    /// Background jobs run at the same time
+    pub job_workers: usize,
+    /// Origins allowed to call the API from a browser, `*` for any
+    pub cors_origins: Vec<String>,
+    /// Compresses responses with gzip, brotli or zstd when the client accepts it
+    pub compress: bool,
+    /// Seconds responses about a single build may be reused for
+    pub build_max_age: u32,
+    /// Seconds responses about the newest builds may be reused for
+    pub listing_max_age: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "127.0.0.1".to_string(),
+            port: 8080,
+            tls_cert: None,
+            tls_key: None,
+            job_workers: JOB_WORKERS,
+            cors_origins: Vec::new(),
+            compress: true,
+            build_max_age: 86400,
+            listing_max_age: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory of the local build collection
+    pub store: String,
+    /// Directory of the background jobs
+    pub jobs: String,
+    /// Directory keeping fetched build pages, so that they are only fetched once
+    pub cache: Option<String>,
+    /// Seconds a cached build page is used before it is fetched again
+    pub cache_ttl: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            store: DEFAULT_STORE_PATH.to_string(),
+            jobs: DEFAULT_JOBS_PATH.to_string(),
+            cache: None,
+            cache_ttl: CACHE_TTL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    /// Human-readable lines or one JSON object per line
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: LogLevel::Info,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+/// Limits of a client of the server, unlimited when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub requests_per_minute: Option<u32>,
+    /// Build pages fetched from Spawning Tool on behalf of the client
+    pub fetches_per_hour: Option<u32>,
+}
+
+/// Who may call the server, and how much.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Serves requests without an API key under `anonymous_quota`, by client address
+    pub allow_anonymous: bool,
+    /// API keys by client name, on top of those issued with `keys issue`
+    pub keys: BTreeMap<String, String>,
+    /// Limits of each API key without a quota of its own
+    pub quota: QuotaConfig,
+    pub anonymous_quota: QuotaConfig,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            allow_anonymous: true,
+            keys: BTreeMap::new(),
+            quota: QuotaConfig {
+                requests_per_minute: Some(120),
+                fetches_per_hour: Some(2000),
+            },
+            anonymous_quota: QuotaConfig {
+                requests_per_minute: Some(30),
+                fetches_per_hour: Some(100),
+            },
+        }
+    }
+}
+
+/// Settings of the CLI and server, read from a TOML file and environment
+/// variables over the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub spawning_tool: SpawningToolConfig,
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub log: LogConfig,
+    pub auth: AuthConfig,
+}
+
+/// Reads an environment value as a TOML value, or as a string when it isn't
+/// one. `layered` falls back to the string when the config rejects the value.
+fn env_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+impl Config {
+    /// Parses the content of a TOML config file.
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        Config::layered(Some(content), Vec::new())
+    }
+
+    /// Reads the config file, then applies the `BUILD_WARREN_*` environment
+    /// variables. Without a `path`, the file is taken from
+    /// `BUILD_WARREN_CONFIG` or `build_warren.toml` when it exists.
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        let path = path
+            .map(str::to_string)
+            .or_else(|| env::var(CONFIG_PATH_ENV).ok())
+            .or_else(|| {
+                Path::new(DEFAULT_CONFIG_PATH)
+                    .exists()
+                    .then(|| DEFAULT_CONFIG_PATH.to_string())
+            });
+        let content = match path {
+            Some(path) => Some(
+                fs::read_to_string(&path)
+                    .map_err(|e| ConfigError::Io(format!("{}: {}", path, e)))?,
+            ),
+            None => None,
+        };
+        Config::layered(content.as_deref(), env::vars())
+    }
+
+    /// Applies the config file content, then the `BUILD_WARREN_*` variables
+    /// among `vars`, over the defaults.
+    pub fn layered(
+        content: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = match content {
+            Some(content) => {
+                toml::from_str(content).map_err(|e| ConfigError::Parse(e.to_string()))?
+            }
+            None => toml::Table::new(),
+        };
+        for (name, raw) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = key.to_lowercase();
+            let Some((name, field)) = SECTIONS.iter().find_map(|section| {
+                let field = key.strip_prefix(section)?.strip_prefix('_')?;
+                Some((*section, field))
+            }) else {
+                continue;
+            };
+            let section = table
+                .entry(name)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            match section.as_table_mut() {
+                Some(section) => {
+                    section.insert(field.to_string(), env_value(&raw));
+                }
+                None => return Err(ConfigError::Parse(format!("{} is not a section", name))),
+            }
+            // Values such as `2024` for a directory are meant as strings
+            let typed = toml::Value::Table(table.clone()).try_into::<Config>();
+            if typed.is_err() {
+                let mut as_string = table.clone();
+                if let Some(section) = as_string.get_mut(name).and_then(toml::Value::as_table_mut) {
+                    section.insert(field.to_string(), toml::Value::String(raw));
+                }
+                if toml::Value::Table(as_string.clone())
+                    .try_into::<Config>()
+                    .is_ok()
+                {
+                    table = as_string;
+                }
+            }
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))
+    }
+
+    /// The config as a TOML file. Unset optional values are left out.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Failed to serialize config")
+    }
+}
+
+static CURRENT: OnceLock<Config> = OnceLock::new();
+
+/// Makes `config` the config of the process. Only the first call has an
+/// effect, it returns whether it was this one.
+pub fn install(config: Config) -> bool {
+    CURRENT.set(config).is_ok()
+}
+
+/// The config of the process, the defaults until one is installed.
+pub fn current() -> &'static Config {
+    CURRENT.get_or_init(Config::default)
+}
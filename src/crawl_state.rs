@@ -0,0 +1,227 @@
+use crate::build_order::{BuildOrder, BuildOrderError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Result of fetching one build order during a crawl.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum CrawlOutcome {
+    Ok,
+    Cloaked,
+    NotFound,
+    Redirected { location: String },
+    Failed { reason: String },
+}
+
+impl CrawlOutcome {
+    /// Whether the build needs no further attempt.
+    pub fn is_complete(&self) -> bool {
+        !matches!(self, CrawlOutcome::Failed { .. })
+    }
+}
+
+impl From<&Result<BuildOrder, BuildOrderError>> for CrawlOutcome {
+    fn from(result: &Result<BuildOrder, BuildOrderError>) -> Self {
+        match result {
+            Ok(_) => CrawlOutcome::Ok,
+            Err(BuildOrderError::Cloaked) => CrawlOutcome::Cloaked,
+            Err(BuildOrderError::NotFound) => CrawlOutcome::NotFound,
+            Err(BuildOrderError::Redirected { location }) => CrawlOutcome::Redirected {
+                location: location.clone(),
+            },
+            Err(e) => CrawlOutcome::Failed {
+                reason: e.to_string(),
+            },
+        }
+    }
+}
+
+/// First line of a crawl-state file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct CrawlRange {
+    start: u32,
+    end: u32,
+}
+
+/// One line per attempted build after the range line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CrawlRecord {
+    id: u32,
+    #[serde(flatten)]
+    outcome: CrawlOutcome,
+}
+
+/// Progress of a crawl, as reported by `crawl status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrawlSummary {
+    pub start: u32,
+    pub end: u32,
+    pub total: usize,
+    pub ok: usize,
+    pub cloaked: usize,
+    pub not_found: usize,
+    pub redirected: usize,
+    pub failed: usize,
+    pub not_attempted: usize,
+    /// Share of the range needing no further attempt, in percent
+    pub progress: f64,
+    /// Inclusive ranges of IDs still to fetch, failed or never attempted
+    pub gaps: Vec<(u32, u32)>,
+    /// Reasons of the failed builds, by ID
+    pub failures: BTreeMap<u32, String>,
+}
+
+/// Per-ID outcomes of a crawl over a range of build IDs, kept in an
+/// append-only JSON lines file so that an interrupted crawl can be resumed.
+#[derive(Debug)]
+pub struct CrawlState {
+    path: PathBuf,
+    start: u32,
+    end: u32,
+    outcomes: BTreeMap<u32, CrawlOutcome>,
+    file: File,
+}
+
+fn invalid_data(path: &Path, line: usize, e: serde_json::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} line {}: {}", path.display(), line, e),
+    )
+}
+
+impl CrawlState {
+    /// Starts a new crawl over `start..=end`, replacing any existing file.
+    pub fn create<P: AsRef<Path>>(path: P, start: u32, end: u32) -> io::Result<Self> {
+        let mut file = File::create(path.as_ref())?;
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&CrawlRange { start, end })?
+        )?;
+        file.flush()?;
+        Ok(CrawlState {
+            path: path.as_ref().to_path_buf(),
+            start,
+            end,
+            outcomes: BTreeMap::new(),
+            file,
+        })
+    }
+
+    /// Opens the state of an earlier crawl. Later records of an ID replace earlier ones.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines();
+        let range: CrawlRange = match lines.next() {
+            Some(line) => serde_json::from_str(line).map_err(|e| invalid_data(path, 1, e))?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is empty", path.display()),
+                ));
+            }
+        };
+        let mut outcomes = BTreeMap::new();
+        for (number, line) in lines.enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<CrawlRecord>(line) {
+                Ok(record) => {
+                    outcomes.insert(record.id, record.outcome);
+                }
+                // A crash can leave a line half-written, its build is simply retried
+                Err(e) if e.is_eof() => continue,
+                Err(e) => return Err(invalid_data(path, number + 2, e)),
+            }
+        }
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        if !content.ends_with('\n') {
+            writeln!(file)?;
+        }
+        Ok(CrawlState {
+            path: path.to_path_buf(),
+            start: range.start,
+            end: range.end,
+            outcomes,
+            file,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn get_end(&self) -> u32 {
+        self.end
+    }
+
+    pub fn get_outcome(&self, id: u32) -> Option<&CrawlOutcome> {
+        self.outcomes.get(&id)
+    }
+
+    /// Records the outcome of a build and flushes it to disk.
+    pub fn record(&mut self, id: u32, outcome: CrawlOutcome) -> io::Result<()> {
+        let record = CrawlRecord { id, outcome };
+        writeln!(self.file, "{}", serde_json::to_string(&record)?)?;
+        self.file.flush()?;
+        self.outcomes.insert(id, record.outcome);
+        Ok(())
+    }
+
+    /// IDs of the range that failed or were never attempted, in ascending order.
+    pub fn pending_ids(&self) -> Vec<u32> {
+        (self.start..=self.end)
+            .filter(|id| !self.outcomes.get(id).is_some_and(|o| o.is_complete()))
+            .collect()
+    }
+
+    pub fn summary(&self) -> CrawlSummary {
+        let mut summary = CrawlSummary {
+            start: self.start,
+            end: self.end,
+            total: (self.start..=self.end).count(),
+            ok: 0,
+            cloaked: 0,
+            not_found: 0,
+            redirected: 0,
+            failed: 0,
+            not_attempted: 0,
+            progress: 0.0,
+            gaps: Vec::new(),
+            failures: BTreeMap::new(),
+        };
+        for id in self.start..=self.end {
+            match self.outcomes.get(&id) {
+                Some(CrawlOutcome::Ok) => summary.ok += 1,
+                Some(CrawlOutcome::Cloaked) => summary.cloaked += 1,
+                Some(CrawlOutcome::NotFound) => summary.not_found += 1,
+                Some(CrawlOutcome::Redirected { .. }) => summary.redirected += 1,
+                Some(CrawlOutcome::Failed { reason }) => {
+                    summary.failed += 1;
+                    summary.failures.insert(id, reason.clone());
+                }
+                None => summary.not_attempted += 1,
+            }
+        }
+        for id in self.pending_ids() {
+            match summary.gaps.last_mut() {
+                Some((_, end)) if *end + 1 == id => *end = id,
+                _ => summary.gaps.push((id, id)),
+            }
+        }
+        let complete = summary.ok + summary.cloaked + summary.not_found + summary.redirected;
+        if summary.total > 0 {
+            summary.progress = complete as f64 * 100.0 / summary.total as f64;
+        }
+        summary
+    }
+}
@@ -0,0 +1,172 @@
+use crate::build_order::{BuildOrder, BuildOrderError};
+use crate::handlers::FetchSummary;
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+use tracing::warn;
+
+/// Receives the progress of a fetch run. Every method does nothing by
+/// default, so implementations only handle the events they need.
+pub trait FetchObserver {
+    /// A run starts, over `total` builds when the number is known up front.
+    fn started(&self, _total: Option<u64>) {}
+    fn fetched(&self, _build_order: &BuildOrder) {}
+    fn skipped_cloaked(&self, _id: u32) {}
+    /// Any other failure, including builds that don't exist or moved.
+    fn failed(&self, _id: u32, _error: &BuildOrderError) {}
+    fn finished(&self, _summary: &FetchSummary) {}
+    /// Polled between builds, a run stops early once it returns `true`.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Reports the result of fetching one build to an observer.
+pub fn notify(observer: &dyn FetchObserver, id: u32, result: &Result<BuildOrder, BuildOrderError>) {
+    match result {
+        Ok(build_order) => observer.fetched(build_order),
+        Err(BuildOrderError::Cloaked) => observer.skipped_cloaked(id),
+        Err(e) => observer.failed(id, e),
+    }
+}
+
+/// Observer ignoring every event.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl FetchObserver for NoopObserver {}
+
+/// Events sent by a [`ChannelObserver`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchEvent {
+    Started { total: Option<u64> },
+    Fetched { id: u32 },
+    SkippedCloaked { id: u32 },
+    Failed { id: u32, error: BuildOrderError },
+    Finished { summary: FetchSummary },
+}
+
+/// Observer sending every event to a channel, for consumers on another thread.
+#[derive(Debug, Clone)]
+pub struct ChannelObserver {
+    sender: Sender<FetchEvent>,
+}
+
+impl ChannelObserver {
+    /// Creates an observer and the receiving end of its channel.
+    pub fn new() -> (Self, Receiver<FetchEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (ChannelObserver { sender }, receiver)
+    }
+
+    fn send(&self, event: FetchEvent) {
+        // A dropped receiver only means nobody is listening anymore
+        let _ = self.sender.send(event);
+    }
+}
+
+impl FetchObserver for ChannelObserver {
+    fn started(&self, total: Option<u64>) {
+        self.send(FetchEvent::Started { total });
+    }
+
+    fn fetched(&self, build_order: &BuildOrder) {
+        self.send(FetchEvent::Fetched {
+            id: build_order.get_id(),
+        });
+    }
+
+    fn skipped_cloaked(&self, id: u32) {
+        self.send(FetchEvent::SkippedCloaked { id });
+    }
+
+    fn failed(&self, id: u32, error: &BuildOrderError) {
+        self.send(FetchEvent::Failed {
+            id,
+            error: error.clone(),
+        });
+    }
+
+    fn finished(&self, summary: &FetchSummary) {
+        self.send(FetchEvent::Finished { summary: *summary });
+    }
+}
+
+/// Observer logging failed builds as warnings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogObserver;
+
+impl FetchObserver for LogObserver {
+    fn failed(&self, id: u32, error: &BuildOrderError) {
+        warn!(build_id = id, error = %error, "Error fetching build order");
+    }
+}
+
+/// Observer drawing a progress bar on the terminal.
+#[derive(Debug, Clone)]
+pub struct ProgressObserver {
+    pb: ProgressBar,
+}
+
+impl Default for ProgressObserver {
+    fn default() -> Self {
+        ProgressObserver::new()
+    }
+}
+
+impl ProgressObserver {
+    pub fn new() -> Self {
+        ProgressObserver {
+            pb: ProgressBar::no_length(),
+        }
+    }
+
+    fn tick(&self, message: String) {
+        self.pb.inc(1);
+        self.pb.set_message(message);
+    }
+}
+
+impl FetchObserver for ProgressObserver {
+    fn started(&self, total: Option<u64>) {
+        let template = match total {
+            Some(_) => "[{percent:.bold.dim}%] {elapsed:.dim} {spinner} {bar}\t{wide_msg}",
+            None => "{elapsed:.dim} {spinner} {pos} builds checked\t{wide_msg}",
+        };
+        let spinner_style = ProgressStyle::with_template(template)
+            .unwrap()
+            .tick_chars("⡇⣆⣤⣰⢸⠹⠛⠏ ");
+        if let Some(total) = total {
+            self.pb.set_length(total);
+        }
+        self.pb.set_style(spinner_style);
+        self.pb.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    fn fetched(&self, build_order: &BuildOrder) {
+        self.tick(format!("Fetched build order {}", build_order.get_id()));
+    }
+
+    fn skipped_cloaked(&self, id: u32) {
+        self.tick(format!("Build order {} is cloaked, skipping.", id));
+    }
+
+    fn failed(&self, id: u32, error: &BuildOrderError) {
+        match error {
+            BuildOrderError::NotFound | BuildOrderError::Redirected { .. } => {
+                self.tick(format!("Skipping build order {}: {}", id, error))
+            }
+            _ => {
+                self.pb
+                    .suspend(|| warn!(build_id = id, error = %error, "Error fetching build order"));
+                self.pb.inc(1);
+            }
+        }
+    }
+
+    fn finished(&self, summary: &FetchSummary) {
+        self.pb
+            .finish_with_message(format!("{} {}", style("✔").green(), summary));
+    }
+}
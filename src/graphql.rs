@@ -0,0 +1,576 @@
+use crate::auth::Caller;
+use crate::build_order::{self, BuildOrder as Build};
+use crate::build_parser::fetch_build_order_async;
+use crate::classifier::OpenerClassifier;
+use crate::http_client::AsyncHttpClient;
+use crate::stats::{CorpusStats, StatsOptions, compute_stats, matchup};
+use async_graphql::connection::{Connection, Edge, query};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, InputObject, Object, Result, Schema,
+    SimpleObject,
+};
+use chrono::{NaiveDate, Timelike};
+use std::collections::BTreeMap;
+
+/// Deepest nesting of fields a query may select
+pub const MAX_DEPTH: usize = 10;
+/// Highest complexity of a query, each field counting for one and each list
+/// for as many items as it may return
+pub const MAX_COMPLEXITY: usize = 5000;
+/// Items of a page when neither `first` nor `last` is given
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+/// Most items of a page
+pub const MAX_PAGE_SIZE: usize = 100;
+
+pub type BuildWarrenSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema. The opener classifier and HTTP client are expected as
+/// data of the schema, the [`Corpus`] and [`Caller`] as data of each request.
+pub fn schema(classifier: OpenerClassifier, client: AsyncHttpClient) -> BuildWarrenSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(classifier)
+        .data(client)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// The local collection, loaded once per query before it runs, so that
+/// resolvers never read the store on the async runtime.
+pub struct Corpus {
+    builds: Vec<Build>,
+}
+
+impl Corpus {
+    pub fn new(builds: Vec<Build>) -> Self {
+        Corpus { builds }
+    }
+
+    pub fn builds(&self) -> &[Build] {
+        &self.builds
+    }
+}
+
+/// Complexity of a paginated field, as many times its items as it may return.
+fn page_complexity(first: Option<i32>, last: Option<i32>, child_complexity: usize) -> usize {
+    let size = first.or(last).map_or(DEFAULT_PAGE_SIZE, |size| {
+        size.clamp(0, MAX_PAGE_SIZE as i32) as usize
+    });
+    size.max(1) * child_complexity
+}
+
+/// Extra fields of every connection.
+#[derive(SimpleObject)]
+pub struct PageTotal {
+    /// Items over all pages
+    total_count: usize,
+}
+
+pub type Page<T> = Connection<usize, T, PageTotal>;
+
+/// Pages through `items` with `first`/`after` or `last`/`before`. Cursors are
+/// positions in `items`.
+async fn paginate<T: async_graphql::OutputType>(
+    items: Vec<T>,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> Result<Page<T>> {
+    query(
+        after,
+        before,
+        first,
+        last,
+        |after: Option<usize>, before: Option<usize>, first, last| async move {
+            if first.or(last).is_some_and(|size| size > MAX_PAGE_SIZE) {
+                return Err(async_graphql::Error::new(format!(
+                    "Pages hold at most {} items",
+                    MAX_PAGE_SIZE
+                )));
+            }
+            let total = items.len();
+            let mut end = before.unwrap_or(total).min(total);
+            let mut start = after.map_or(0, |after| after + 1).min(end);
+            match (first, last) {
+                (Some(first), _) => end = end.min(start + first),
+                (None, Some(last)) => start = start.max(end.saturating_sub(last)),
+                (None, None) => end = end.min(start + DEFAULT_PAGE_SIZE),
+            }
+            let mut connection = Connection::with_additional_fields(
+                start > 0,
+                end < total,
+                PageTotal { total_count: total },
+            );
+            connection.edges.extend(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .skip(start)
+                    .take(end - start)
+                    .map(|(index, item)| Edge::new(index, item)),
+            );
+            Ok(connection)
+        },
+    )
+    .await
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "Race", remote = "crate::build_order::Race")]
+pub enum GqlRace {
+    Terran,
+    Protoss,
+    Zerg,
+    Any,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "BuildType", remote = "crate::build_order::BuildType")]
+pub enum GqlBuildType {
+    Cheese,
+    AllIn,
+    Timing,
+    Economic,
+    CoOp,
+    None,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "Difficulty", remote = "crate::build_order::Difficulty")]
+pub enum GqlDifficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "ActionType", remote = "crate::build_order::ActionType")]
+pub enum GqlActionType {
+    Worker,
+    Unit,
+    Building,
+    Upgrade,
+    Action,
+}
+
+/// Build orders to keep, every given field having to match.
+#[derive(InputObject, Default)]
+pub struct BuildFilter {
+    ids: Option<Vec<u32>>,
+    race: Option<GqlRace>,
+    opponent_race: Option<GqlRace>,
+    /// Matchup such as `ZvP`
+    matchup: Option<String>,
+    build_type: Option<GqlBuildType>,
+    difficulty: Option<GqlDifficulty>,
+    /// Creator name, ignoring case
+    creator: Option<String>,
+    /// Patch, or the start of one such as `5.0`
+    patch: Option<String>,
+    /// Text found in the name, ignoring case
+    name_contains: Option<String>,
+    published_since: Option<NaiveDate>,
+    published_until: Option<NaiveDate>,
+    min_score: Option<u32>,
+    min_votes: Option<u32>,
+    /// Opener label given by the classifier
+    opener: Option<String>,
+}
+
+impl BuildFilter {
+    pub fn matches(&self, build: &Build, classifier: &OpenerClassifier) -> bool {
+        let votes = build.get_votes();
+        self.ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&build.get_id()))
+            && self
+                .race
+                .is_none_or(|race| build.get_player_race() == &race.into())
+            && self
+                .opponent_race
+                .is_none_or(|race| build.get_opponent_race() == &race.into())
+            && self
+                .matchup
+                .as_ref()
+                .is_none_or(|name| matchup(build).eq_ignore_ascii_case(name))
+            && self
+                .build_type
+                .is_none_or(|build_type| build.get_build_type() == &build_type.into())
+            && self.difficulty.is_none_or(|difficulty| {
+                build.get_difficulty() == Some(&build_order::Difficulty::from(difficulty))
+            })
+            && self
+                .creator
+                .as_ref()
+                .is_none_or(|creator| build.get_creator().eq_ignore_ascii_case(creator))
+            && self
+                .patch
+                .as_ref()
+                .is_none_or(|patch| build.get_patch().starts_with(patch.as_str()))
+            && self.name_contains.as_ref().is_none_or(|text| {
+                build
+                    .get_name()
+                    .to_lowercase()
+                    .contains(&text.to_lowercase())
+            })
+            && self
+                .published_since
+                .is_none_or(|since| build.get_published().is_some_and(|date| *date >= since))
+            && self
+                .published_until
+                .is_none_or(|until| build.get_published().is_some_and(|date| *date <= until))
+            && self
+                .min_score
+                .is_none_or(|score| votes.is_some_and(|votes| votes.get_score() >= score))
+            && self
+                .min_votes
+                .is_none_or(|count| votes.is_some_and(|votes| votes.get_count() >= count))
+            && self
+                .opener
+                .as_ref()
+                .is_none_or(|label| classifier.has_label(build, label))
+    }
+}
+
+/// Stored build orders matching `filter`, by ID.
+fn filtered(ctx: &Context<'_>, filter: Option<&BuildFilter>) -> Result<Vec<BuildOrder>> {
+    let corpus = ctx.data::<Corpus>()?;
+    let classifier = ctx.data::<OpenerClassifier>()?;
+    Ok(corpus
+        .builds()
+        .iter()
+        .filter(|build| filter.is_none_or(|filter| filter.matches(build, classifier)))
+        .cloned()
+        .map(BuildOrder)
+        .collect())
+}
+
+pub struct BuildOrder(Build);
+
+#[Object]
+impl BuildOrder {
+    async fn id(&self) -> u32 {
+        self.0.get_id()
+    }
+
+    async fn name(&self) -> &str {
+        self.0.get_name()
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.get_description()
+    }
+
+    async fn vod(&self) -> Option<&str> {
+        self.0.get_vod()
+    }
+
+    async fn player_race(&self) -> GqlRace {
+        self.0.get_player_race().clone().into()
+    }
+
+    async fn opponent_race(&self) -> GqlRace {
+        self.0.get_opponent_race().clone().into()
+    }
+
+    /// Matchup such as `ZvP`
+    async fn matchup(&self) -> String {
+        matchup(&self.0)
+    }
+
+    async fn build_type(&self) -> GqlBuildType {
+        self.0.get_build_type().clone().into()
+    }
+
+    async fn creator(&self) -> Creator {
+        Creator {
+            name: self.0.get_creator().to_string(),
+        }
+    }
+
+    async fn votes(&self) -> Option<Votes> {
+        self.0.get_votes().map(|votes| Votes {
+            score: votes.get_score(),
+            count: votes.get_count(),
+        })
+    }
+
+    async fn published(&self) -> Option<NaiveDate> {
+        self.0.get_published().copied()
+    }
+
+    async fn patch(&self) -> &str {
+        self.0.get_patch()
+    }
+
+    async fn difficulty(&self) -> Option<GqlDifficulty> {
+        self.0.get_difficulty().cloned().map(Into::into)
+    }
+
+    /// Opener labels given by the classifier
+    async fn openers(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        Ok(ctx.data::<OpenerClassifier>()?.classify(&self.0))
+    }
+
+    /// Steps of the build, the first `limit` ones when given
+    #[graphql(complexity = "limit.map_or(50, |limit| limit.min(500)) * child_complexity")]
+    async fn entries(&self, limit: Option<usize>) -> Vec<OrderEntry> {
+        let entries = self.0.get_entries();
+        entries[..limit.unwrap_or(entries.len()).min(entries.len())]
+            .iter()
+            .cloned()
+            .map(OrderEntry)
+            .collect()
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Votes {
+    /// Share of upvotes, in percent
+    score: u32,
+    count: u32,
+}
+
+pub struct OrderEntry(build_order::OrderEntry);
+
+#[Object]
+impl OrderEntry {
+    async fn supply(&self) -> u8 {
+        self.0.get_supply()
+    }
+
+    /// Game time such as `1:10`
+    async fn time(&self) -> Option<String> {
+        self.0
+            .get_timestamp()
+            .map(|time| format!("{}:{:02}", time.hour() * 60 + time.minute(), time.second()))
+    }
+
+    /// Game time in seconds
+    async fn seconds(&self) -> Option<u32> {
+        self.0
+            .get_timestamp()
+            .map(|time| time.num_seconds_from_midnight())
+    }
+
+    async fn actions(&self) -> Vec<Action> {
+        self.0
+            .get_actions()
+            .iter()
+            .map(|action| Action {
+                action_type: action.get_action_type().clone().into(),
+                name: action.get_name().to_string(),
+            })
+            .collect()
+    }
+
+    async fn comment(&self) -> Option<&str> {
+        self.0.get_comment()
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Action {
+    action_type: GqlActionType,
+    name: String,
+}
+
+/// Author of build orders in the local collection.
+pub struct Creator {
+    name: String,
+}
+
+#[Object]
+impl Creator {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stored build orders of the creator
+    async fn build_count(&self, ctx: &Context<'_>) -> Result<usize> {
+        let corpus = ctx.data::<Corpus>()?;
+        Ok(corpus
+            .builds()
+            .iter()
+            .filter(|build| build.get_creator() == self.name)
+            .count())
+    }
+
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn builds(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BuildFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<BuildOrder>> {
+        let filter = BuildFilter {
+            creator: Some(self.name.clone()),
+            ..filter.unwrap_or_default()
+        };
+        let builds = filtered(ctx, Some(&filter))?;
+        paginate(builds, after, before, first, last).await
+    }
+}
+
+/// Races of a matchup and the stored build orders played in it.
+pub struct Matchup {
+    name: String,
+    player_race: GqlRace,
+    opponent_race: GqlRace,
+    build_count: usize,
+}
+
+#[Object]
+impl Matchup {
+    /// Matchup such as `ZvP`
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn player_race(&self) -> GqlRace {
+        self.player_race
+    }
+
+    async fn opponent_race(&self) -> GqlRace {
+        self.opponent_race
+    }
+
+    async fn build_count(&self) -> usize {
+        self.build_count
+    }
+
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn builds(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BuildFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<BuildOrder>> {
+        let filter = BuildFilter {
+            race: Some(self.player_race),
+            opponent_race: Some(self.opponent_race),
+            ..filter.unwrap_or_default()
+        };
+        let builds = filtered(ctx, Some(&filter))?;
+        paginate(builds, after, before, first, last).await
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A build order from the local collection, or else from Spawning Tool
+    async fn build(&self, ctx: &Context<'_>, id: u32) -> Result<BuildOrder> {
+        let corpus = ctx.data::<Corpus>()?;
+        if let Some(build) = corpus.builds().iter().find(|build| build.get_id() == id) {
+            return Ok(BuildOrder(build.clone()));
+        }
+        if let Some(caller) = ctx.data_opt::<Caller>() {
+            caller.charge_fetches(1)?;
+        }
+        let client = ctx.data::<AsyncHttpClient>()?;
+        Ok(BuildOrder(fetch_build_order_async(client, id).await?))
+    }
+
+    /// Stored build orders matching `filter`, by ID
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn builds(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BuildFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<BuildOrder>> {
+        let builds = filtered(ctx, filter.as_ref())?;
+        paginate(builds, after, before, first, last).await
+    }
+
+    /// A creator of stored build orders, by name
+    async fn creator(&self, ctx: &Context<'_>, name: String) -> Result<Option<Creator>> {
+        let corpus = ctx.data::<Corpus>()?;
+        Ok(corpus
+            .builds()
+            .iter()
+            .find(|build| build.get_creator().eq_ignore_ascii_case(&name))
+            .map(|build| Creator {
+                name: build.get_creator().to_string(),
+            }))
+    }
+
+    /// Creators of stored build orders, by name
+    #[graphql(complexity = "page_complexity(first, last, child_complexity)")]
+    async fn creators(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<Creator>> {
+        let corpus = ctx.data::<Corpus>()?;
+        let mut names: Vec<&str> = corpus
+            .builds()
+            .iter()
+            .map(|build| build.get_creator())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        let creators = names
+            .into_iter()
+            .map(|name| Creator {
+                name: name.to_string(),
+            })
+            .collect();
+        paginate(creators, after, before, first, last).await
+    }
+
+    /// Matchups of the stored build orders
+    async fn matchups(&self, ctx: &Context<'_>) -> Result<Vec<Matchup>> {
+        let corpus = ctx.data::<Corpus>()?;
+        let mut matchups: BTreeMap<String, Matchup> = BTreeMap::new();
+        for build in corpus.builds() {
+            let name = matchup(build);
+            matchups
+                .entry(name.clone())
+                .or_insert_with(|| Matchup {
+                    name,
+                    player_race: build.get_player_race().clone().into(),
+                    opponent_race: build.get_opponent_race().clone().into(),
+                    build_count: 0,
+                })
+                .build_count += 1;
+        }
+        Ok(matchups.into_values().collect())
+    }
+
+    /// Statistics over the stored build orders
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        opener_length: Option<usize>,
+        top: Option<usize>,
+        min_votes: Option<u32>,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    ) -> Result<CorpusStats> {
+        let defaults = StatsOptions::default();
+        let options = StatsOptions {
+            opener_length: opener_length.unwrap_or(defaults.opener_length),
+            top: top.unwrap_or(defaults.top),
+            min_votes: min_votes.unwrap_or(defaults.min_votes),
+            since,
+            until,
+        };
+        Ok(compute_stats(ctx.data::<Corpus>()?.builds(), &options))
+    }
+}
@@ -1,137 +1,341 @@
 use crate::build_order::{BuildOrder, BuildOrderError};
-use crate::build_parser::fetch_build_order;
-use crate::index_manager::{LOWEST_INDEX, get_st_highest_index};
-use console::style;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use crate::build_parser::{
+    PageCache, fetch_build_order, fetch_build_order_async, fetch_build_order_with,
+};
+use crate::config::current;
+use crate::crawl_state::{CrawlOutcome, CrawlState};
+use crate::fetch_observer::{FetchObserver, NoopObserver, notify};
+use crate::http_client::AsyncHttpClient;
+use crate::index_manager::{get_st_highest_index, get_st_highest_index_async, lowest_index};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::ready;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use tracing::Span;
+use utoipa::ToSchema;
 
-pub fn fetch_latest(count: u32) -> Vec<BuildOrder> {
-    let spinner_style = ProgressStyle::with_template(
-        "[{percent:.bold.dim}%] {elapsed:.dim} {spinner} {bar}\t{wide_msg}",
+/// Default number of build orders fetched concurrently
+pub const FETCH_WORKERS: usize = 8;
+
+/// Number of build orders fetched concurrently, as configured
+pub fn fetch_workers() -> usize {
+    current().spawning_tool.workers.max(1)
+}
+
+/// Number of builds of a fetch run, by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FetchSummary {
+    pub fetched: usize,
+    pub cloaked: usize,
+    pub not_found: usize,
+    pub redirected: usize,
+    pub rate_limited: usize,
+    pub failed: usize,
+}
+
+impl FetchSummary {
+    pub fn record<T>(&mut self, result: &Result<T, BuildOrderError>) {
+        match result {
+            Ok(_) => self.fetched += 1,
+            Err(BuildOrderError::Cloaked) => self.cloaked += 1,
+            Err(BuildOrderError::NotFound) => self.not_found += 1,
+            Err(BuildOrderError::Redirected { .. }) => self.redirected += 1,
+            Err(BuildOrderError::RateLimited { .. }) => self.rate_limited += 1,
+            Err(_) => self.failed += 1,
+        }
+    }
+}
+
+impl fmt::Display for FetchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} build orders fetched, {} cloaked, {} not found, {} moved, {} rate limited, {} failed.",
+            self.fetched,
+            self.cloaked,
+            self.not_found,
+            self.redirected,
+            self.rate_limited,
+            self.failed
+        )
+    }
+}
+
+/// Whether a build will never be available, as opposed to a failure worth retrying.
+fn is_unavailable(error: &BuildOrderError) -> bool {
+    matches!(
+        error,
+        BuildOrderError::Cloaked | BuildOrderError::NotFound | BuildOrderError::Redirected { .. }
     )
-    .unwrap()
-    .tick_chars("⡇⣆⣤⣰⢸⠹⠛⠏ ");
-    let pb = ProgressBar::new(count as u64);
-    pb.set_style(spinner_style);
-    pb.enable_steady_tick(Duration::from_millis(100));
+}
 
-    let highest_index = get_st_highest_index();
-    let mut end_index = if count > highest_index - LOWEST_INDEX {
-        LOWEST_INDEX
+/// Lowest build ID checked when fetching the latest `count` builds, before
+/// moving it down for each unavailable build.
+fn latest_end_index(count: u32, highest_index: u32) -> u32 {
+    if count > highest_index.saturating_sub(lowest_index()) {
+        lowest_index()
     } else {
         highest_index - count + 1
-    };
+    }
+}
+
+pub fn fetch_latest(count: u32) -> Vec<BuildOrder> {
+    let mut build_orders = Vec::new();
+    fetch_latest_with(count, &NoopObserver, |build_order| {
+        build_orders.push(build_order)
+    });
+    build_orders
+}
+
+/// Fetches the latest `count` build orders, handing each one to `on_build` as
+/// soon as it is fetched.
+pub fn fetch_latest_with(
+    count: u32,
+    observer: &dyn FetchObserver,
+    mut on_build: impl FnMut(BuildOrder),
+) -> FetchSummary {
+    // The number of builds to check depends on how many are unavailable
+    observer.started(None);
+    let highest_index = get_st_highest_index();
+    let mut end_index = latest_end_index(count, highest_index);
     let mut increment = 0;
     let mut current_id = highest_index;
-    let mut build_orders = Vec::new();
-    while current_id >= end_index && build_orders.len() < count as usize {
+    let mut summary = FetchSummary::default();
+    while current_id >= end_index && summary.fetched < count as usize && !observer.is_cancelled() {
         current_id = highest_index - increment;
-        match fetch_build_order(current_id) {
-            Ok(build_order) => {
-                build_orders.push(build_order);
-                increment += 1;
-                pb.inc(1);
-            }
+        let result = fetch_build_order(current_id);
+        summary.record(&result);
+        notify(observer, current_id, &result);
+        match result {
+            Ok(build_order) => on_build(build_order),
             Err(e) => {
-                if e.eq(&BuildOrderError::Cloaked) {
-                    pb.set_message(format!("Build order {} is cloaked, skipping.", current_id));
-                    end_index = if end_index > LOWEST_INDEX {
+                if is_unavailable(&e) {
+                    end_index = if end_index > lowest_index() {
                         end_index - 1
                     } else {
-                        LOWEST_INDEX
+                        lowest_index()
                     } // Decrease end_index to compensate for the skipped build
-                } else {
-                    eprintln!("Error fetching build order {}: {}", current_id, e);
                 }
-                increment += 1; // Increment to avoid infinite loop
-                continue; // Some builds might not be available, continue fetching
             }
         }
+        increment += 1;
     }
-    pb.finish_with_message(format!(
-        "{} {} build orders fetched.",
-        style("✔").green(),
-        build_orders.len()
-    ));
-    build_orders
+    observer.finished(&summary);
+    summary
 }
 
 pub fn fetch_segment(start: u32, end: u32) -> Vec<BuildOrder> {
-    let spinner_style = ProgressStyle::with_template(
-        "[{percent:.bold.dim}%] {elapsed:.dim} {spinner} {bar}\t{wide_msg}",
-    )
-    .unwrap()
-    .tick_chars("⡇⣆⣤⣰⢸⠹⠛⠏ ");
-    let pb = ProgressBar::new((end - start + 1) as u64);
-    pb.set_style(spinner_style);
+    let mut build_orders = Vec::new();
+    fetch_segment_with(start, end, PageCache::Use, &NoopObserver, |build_order| {
+        build_orders.push(build_order)
+    });
+    build_orders.sort_by_key(|build_order| build_order.get_id());
+    build_orders
+}
 
-    let highest_index = get_st_highest_index();
-    let start = if start < LOWEST_INDEX {
-        LOWEST_INDEX
-    } else {
-        start
-    };
-    let end = if end > highest_index {
-        highest_index
-    } else {
-        end
-    };
+/// Clamps a segment to the available build IDs, or `None` if it is empty.
+pub fn segment_bounds(start: u32, end: u32) -> Option<(u32, u32)> {
+    clamp_segment(start, end, get_st_highest_index())
+}
 
+/// Number of build IDs in `start..=end`.
+pub fn segment_len(start: u32, end: u32) -> u64 {
     if start > end {
-        eprintln!(
-            "Start index {} is greater than end index {}. Returning empty segment.",
-            start, end
-        );
-        return Vec::new();
+        0
+    } else {
+        (end - start) as u64 + 1
     }
+}
 
-    let m = MultiProgress::new();
+fn clamp_segment(start: u32, end: u32, highest_index: u32) -> Option<(u32, u32)> {
+    let start = start.max(lowest_index());
+    let end = end.min(highest_index);
+    (start <= end).then_some((start, end))
+}
 
-    let mut build_orders = Vec::new();
-    let mut handles = Vec::new();
-    let count = (end - start + 1) as usize;
-
-    for id in start..=end {
-        let pb_clone = m.add(ProgressBar::new(1));
-        let handle = thread::spawn(move || {
-            let spinner_style =
-                ProgressStyle::with_template("[{prefix:.bold.dim}] {spinner} \t{wide_msg}")
-                    .unwrap()
-                    .tick_chars("⡇⣆⣤⣰⢸⠹⠛⠏ ");
-            pb_clone.set_message(format!("Fetching build order {}", id));
-            pb_clone.set_style(spinner_style.clone());
-            pb_clone.enable_steady_tick(Duration::from_millis(100));
-            pb_clone.set_prefix(format!("{}/{}", id - start + 1, count));
-            match fetch_build_order(id) {
-                Ok(build_order) => {
-                    pb_clone.inc(1);
-                    Some(build_order)
-                }
-                Err(e) => {
-                    if e.eq(&BuildOrderError::Cloaked) {
-                        pb_clone.set_message(format!("Build order {} is cloaked, skipping.", id));
-                    } else {
-                        eprintln!("Error fetching build order {}: {}", id, e);
+/// Fetches build orders `start..=end`, handing each one to `on_build` as soon
+/// as it is fetched, in completion order.
+pub fn fetch_segment_with(
+    start: u32,
+    end: u32,
+    cache: PageCache,
+    observer: &dyn FetchObserver,
+    mut on_build: impl FnMut(BuildOrder),
+) -> FetchSummary {
+    let ids: Vec<u32> = match segment_bounds(start, end) {
+        Some((start, end)) => (start..=end).collect(),
+        None => Vec::new(),
+    };
+    fetch_ids_with(&ids, cache, observer, |_, result| {
+        if let Ok(build_order) = result {
+            on_build(build_order);
+        }
+    })
+}
+
+/// Fetches the pending builds of a crawl, recording each outcome in its state
+/// and handing fetched build orders to `on_build`.
+pub fn crawl(
+    state: &mut CrawlState,
+    cache: PageCache,
+    observer: &dyn FetchObserver,
+    mut on_build: impl FnMut(BuildOrder),
+) -> FetchSummary {
+    let ids = state.pending_ids();
+    fetch_ids_with(&ids, cache, observer, |id, result| {
+        // Handed over before it is recorded, so an interrupted crawl fetches it again
+        let outcome = CrawlOutcome::from(&result);
+        if let Ok(build_order) = result {
+            on_build(build_order);
+        }
+        state
+            .record(id, outcome)
+            .expect("Failed to write crawl state");
+    })
+}
+
+/// Fetches the given build IDs with a pool of [`fetch_workers`] threads,
+/// handing each result to `on_result` in completion order.
+pub fn fetch_ids_with(
+    ids: &[u32],
+    cache: PageCache,
+    observer: &dyn FetchObserver,
+    mut on_result: impl FnMut(u32, Result<BuildOrder, BuildOrderError>),
+) -> FetchSummary {
+    observer.started(Some(ids.len() as u64));
+    let next_index = AtomicUsize::new(0);
+    let mut summary = FetchSummary::default();
+    thread::scope(|scope| {
+        // Bounded so that workers wait for slow consumers instead of piling up builds
+        let (sender, receiver) = mpsc::sync_channel(fetch_workers());
+        for _ in 0..fetch_workers() {
+            let sender = sender.clone();
+            let next_index = &next_index;
+            // Workers log under the caller's span, such as the job or request
+            let span = Span::current();
+            scope.spawn(move || {
+                let _entered = span.enter();
+                while let Some(id) = ids.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+                    if sender
+                        .send((*id, fetch_build_order_with(*id, cache)))
+                        .is_err()
+                    {
+                        break;
                     }
-                    None
                 }
+            });
+        }
+        drop(sender);
+
+        // Dropping the receiver on cancellation stops the workers at their next send
+        for (id, result) in receiver {
+            summary.record(&result);
+            notify(observer, id, &result);
+            on_result(id, result);
+            if observer.is_cancelled() {
+                break;
             }
-        });
-        handles.push(handle);
+        }
+    });
+    observer.finished(&summary);
+    summary
+}
+
+/// Fetches the latest `count` build orders without blocking the current
+/// thread, [`fetch_workers`] at a time, skipping unavailable builds and stopping
+/// at the first failed or rate limited fetch.
+pub async fn fetch_latest_async(
+    client: &AsyncHttpClient,
+    count: u32,
+    observer: &dyn FetchObserver,
+) -> Vec<BuildOrder> {
+    observer.started(None);
+    let highest_index = get_st_highest_index_async(client).await;
+    // As in `fetch_latest_with`, the range only grows for unavailable builds
+    let end_index = Cell::new(latest_end_index(count, highest_index));
+    let mut results = stream::iter((lowest_index()..=highest_index).rev())
+        .take_while(|id| ready(*id >= end_index.get()))
+        .map(|id| async move { (id, fetch_build_order_async(client, id).await) })
+        .buffered(fetch_workers());
+    let mut summary = FetchSummary::default();
+    let mut build_orders = Vec::new();
+    while build_orders.len() < count as usize
+        && !observer.is_cancelled()
+        && let Some((id, result)) = results.next().await
+    {
+        summary.record(&result);
+        notify(observer, id, &result);
+        match result {
+            Ok(build_order) => build_orders.push(build_order),
+            Err(e) if is_unavailable(&e) => {
+                end_index.set(end_index.get().saturating_sub(1).max(lowest_index()))
+            }
+            // Spawning Tool is failing or throttling us, so stop asking
+            Err(_) => break,
+        }
     }
+    observer.finished(&summary);
+    build_orders
+}
 
-    for handle in handles {
-        if let Ok(Some(build_order)) = handle.join() {
+/// Fetches build orders `start..=end` without blocking the current thread,
+/// [`fetch_workers`] at a time.
+pub async fn fetch_segment_async(
+    client: &AsyncHttpClient,
+    start: u32,
+    end: u32,
+    observer: &dyn FetchObserver,
+) -> Vec<BuildOrder> {
+    let highest_index = get_st_highest_index_async(client).await;
+    let ids: Vec<u32> = match clamp_segment(start, end, highest_index) {
+        Some((start, end)) => (start..=end).collect(),
+        None => Vec::new(),
+    };
+    observer.started(Some(ids.len() as u64));
+    let mut results = stream::iter(ids)
+        .map(|id| async move { (id, fetch_build_order_async(client, id).await) })
+        .buffer_unordered(fetch_workers());
+    let mut summary = FetchSummary::default();
+    let mut build_orders = Vec::new();
+    while !observer.is_cancelled()
+        && let Some((id, result)) = results.next().await
+    {
+        summary.record(&result);
+        notify(observer, id, &result);
+        if let Ok(build_order) = result {
             build_orders.push(build_order);
         }
     }
-
-    pb.finish_with_message(format!(
-        "{} {} build orders fetched.",
-        style("✔").green(),
-        build_orders.len()
-    ));
-
+    observer.finished(&summary);
+    build_orders.sort_by_key(|build_order| build_order.get_id());
     build_orders
 }
+
+/// Fetches the given build IDs without blocking the current thread,
+/// [`fetch_workers`] at a time, returning every result by ID.
+pub async fn fetch_ids_async(
+    client: &AsyncHttpClient,
+    ids: &[u32],
+    observer: &dyn FetchObserver,
+) -> BTreeMap<u32, Result<BuildOrder, BuildOrderError>> {
+    observer.started(Some(ids.len() as u64));
+    let mut results = stream::iter(ids.iter().copied())
+        .map(|id| async move { (id, fetch_build_order_async(client, id).await) })
+        .buffer_unordered(fetch_workers());
+    let mut summary = FetchSummary::default();
+    let mut fetched = BTreeMap::new();
+    while !observer.is_cancelled()
+        && let Some((id, result)) = results.next().await
+    {
+        summary.record(&result);
+        notify(observer, id, &result);
+        fetched.insert(id, result);
+    }
+    observer.finished(&summary);
+    fetched
+}
@@ -0,0 +1,193 @@
+use crate::config::{ServerConfig, current};
+use actix_web::Error;
+use actix_web::body::{BoxBody, MessageBody, to_bytes};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{CACHE_CONTROL, ETAG, HeaderValue, IF_NONE_MATCH, VARY};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, web};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Routes serving a single build order, which rarely changes once published
+const BUILD_ROUTES: [&str; 4] = [
+    "/build/{id}",
+    "/build/{id}.salt",
+    "/build/{id}/openers",
+    "/diff/{a}/{b}",
+];
+/// Routes following the newest builds on Spawning Tool
+const LISTING_ROUTES: [&str; 4] = [
+    "/latest",
+    "/latest/{count}",
+    "/count",
+    "/segment/{start}/{end}",
+];
+/// Routes serving the favourites, collections and notes of the caller
+const PERSONAL_ROUTES: [&str; 6] = [
+    "/favourites",
+    "/collections",
+    "/collections/{name}",
+    "/collections/{name}/builds",
+    "/collections/{name}/bundle",
+    "/build/{id}/notes",
+];
+/// Routes left to set their own caching headers
+const UNCACHED_ROUTES: [&str; 2] = ["/openapi.json", "/docs/{_:.*}"];
+/// Request headers that may change a response kept by shared caches, since
+/// quotas and access depend on the API key sent
+pub const VARY_BY_KEY: &str = "Authorization, X-Api-Key";
+/// Paths whose tags are remembered before all of them are forgotten
+const MAX_TAGGED_PATHS: usize = 10_000;
+
+/// Weak entity tag of a response body, from the SHA-256 hash of its bytes.
+/// Weak, since the same tag is served for every content encoding of the body.
+pub fn etag(body: &[u8]) -> String {
+    let hash = Sha256::digest(body);
+    let hex: String = hash[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("W/\"{}\"", hex)
+}
+
+/// Whether an `If-None-Match` header lists `etag`, comparing tags weakly.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .any(|tag| opaque(tag) == opaque(etag))
+}
+
+/// Seconds a response of a public route may be kept by any cache, or `None`
+/// when the route is not public.
+fn public_max_age(route: &str, config: &ServerConfig) -> Option<u32> {
+    if BUILD_ROUTES.contains(&route) {
+        Some(config.build_max_age)
+    } else if LISTING_ROUTES.contains(&route) {
+        Some(config.listing_max_age)
+    } else {
+        None
+    }
+}
+
+/// `Cache-Control` of the responses of a route, or `None` to leave it to the
+/// handler. Build pages are kept for `build_max_age` seconds, the newest
+/// builds for `listing_max_age`, personal data only by the client, and
+/// everything else is revalidated first.
+pub fn cache_control(route: &str, config: &ServerConfig) -> Option<String> {
+    if let Some(max_age) = public_max_age(route, config) {
+        Some(format!("public, max-age={}", max_age))
+    } else if PERSONAL_ROUTES.contains(&route) {
+        Some("private, no-cache".to_string())
+    } else if UNCACHED_ROUTES.contains(&route) {
+        None
+    } else {
+        Some("no-cache".to_string())
+    }
+}
+
+/// Entity tags last served for the paths of public routes, so that a client
+/// revalidating one within its max-age is answered without running the
+/// handler, which may fetch from Spawning Tool.
+#[derive(Default)]
+pub struct ServedTags {
+    tags: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl ServedTags {
+    /// Tag served for `path` less than `max_age` ago.
+    fn fresh(&self, path: &str, max_age: Duration) -> Option<String> {
+        let tags = self.tags.lock().unwrap();
+        let (etag, served) = tags.get(path)?;
+        (served.elapsed() < max_age).then(|| etag.clone())
+    }
+
+    fn insert(&self, path: String, etag: String) {
+        let mut tags = self.tags.lock().unwrap();
+        if tags.len() >= MAX_TAGGED_PATHS && !tags.contains_key(&path) {
+            tags.clear();
+        }
+        tags.insert(path, (etag, Instant::now()));
+    }
+}
+
+/// Tags successful `GET` responses with an ETag and their route's
+/// `Cache-Control`, varying public ones by API key, and answers `304 Not
+/// Modified` without a body when the client already holds the same content.
+/// With [`ServedTags`] in the app data, a public response still fresh is
+/// answered so without running the handler.
+pub async fn conditional_get(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let cacheable = matches!(*request.method(), Method::GET | Method::HEAD);
+    let route = request.match_pattern();
+    let if_none_match = request
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let config = &current().server;
+    let cache_control = route
+        .as_deref()
+        .and_then(|route| cache_control(route, config))
+        .filter(|_| cacheable);
+    let max_age = route
+        .as_deref()
+        .and_then(|route| public_max_age(route, config))
+        .filter(|_| cacheable);
+    let served = request.app_data::<web::Data<ServedTags>>().cloned();
+    let path = request.uri().to_string();
+
+    if let (Some(tags), Some(served), Some(max_age), Some(cache_control)) =
+        (&if_none_match, &served, max_age, &cache_control)
+        && let Some(etag) = served.fresh(&path, Duration::from_secs(max_age as u64))
+        && etag_matches(tags, &etag)
+    {
+        let response = HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, cache_control.as_str()))
+            .append_header((VARY, VARY_BY_KEY))
+            .finish();
+        return Ok(request.into_response(response));
+    }
+
+    let response = next.call(request).await?.map_into_boxed_body();
+    let Some(cache_control) = cache_control else {
+        return Ok(response);
+    };
+    if response.status() != StatusCode::OK {
+        return Ok(response);
+    }
+
+    let (request, response) = response.into_parts();
+    let (mut response, body) = response.into_parts();
+    let body = to_bytes(body).await.map_err(ErrorInternalServerError)?;
+    let etag = etag(&body);
+    let not_modified = if_none_match.is_some_and(|tags| etag_matches(&tags, &etag));
+    let public = max_age.is_some();
+    if public && let Some(served) = &served {
+        served.insert(path, etag.clone());
+    }
+    let headers = response.headers_mut();
+    for (name, value) in [(ETAG, etag), (CACHE_CONTROL, cache_control)] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+    if public {
+        headers.append(VARY, HeaderValue::from_static(VARY_BY_KEY));
+    }
+    let response = if not_modified {
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response.set_body(BoxBody::new(()))
+    } else {
+        response.set_body(BoxBody::new(body))
+    };
+    Ok(ServiceResponse::new(request, response))
+}
@@ -1,5 +1,29 @@
+use crate::config::current;
 use curl::easy::Easy;
 use std::str;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Start of the next request allowed by the rate limit, shared by every client
+static NEXT_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Reserves a start time for a request under the configured rate limit and
+/// returns how long to wait for it.
+fn reserve_request() -> Duration {
+    let Some(rate) = current()
+        .spawning_tool
+        .requests_per_second
+        .filter(|rate| *rate > 0.0)
+    else {
+        return Duration::ZERO;
+    };
+    let now = Instant::now();
+    let mut next_request = NEXT_REQUEST.lock().unwrap();
+    let start = next_request.map_or(now, |next| next.max(now));
+    *next_request = Some(start + Duration::from_secs_f64(1.0 / rate));
+    start - now
+}
 
 pub struct HttpClient;
 
@@ -9,9 +33,20 @@ pub struct Response {
     pub headers: Vec<String>,
 }
 
+impl Response {
+    /// Value of the first header with the given name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
 impl HttpClient {
     /// Fetches the content of the given URL and returns it as a `String`.
     pub fn fetch_url(url: &str) -> Result<Response, String> {
+        thread::sleep(reserve_request());
         let mut data = Vec::new();
         let mut easy = Easy::new();
         let mut headers = Vec::new();
@@ -44,3 +79,58 @@ impl HttpClient {
         })
     }
 }
+
+/// Idle connections kept open to each host for reuse
+const POOL_IDLE_PER_HOST: usize = 16;
+/// Longest a single request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Non-blocking HTTP client for the server, keeping connections alive between
+/// requests. Clones share the same connection pool.
+#[derive(Debug, Clone)]
+pub struct AsyncHttpClient {
+    client: reqwest::Client,
+}
+
+impl Default for AsyncHttpClient {
+    fn default() -> Self {
+        AsyncHttpClient::new()
+    }
+}
+
+impl AsyncHttpClient {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            // Redirects are reported as they are, cloaked builds are detected through them
+            .redirect(reqwest::redirect::Policy::none())
+            .pool_max_idle_per_host(POOL_IDLE_PER_HOST)
+            .tcp_keepalive(Duration::from_secs(60))
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        AsyncHttpClient { client }
+    }
+
+    /// Fetches the content of the given URL without blocking the current thread.
+    pub async fn fetch_url(&self, url: &str) -> Result<Response, String> {
+        actix_web::rt::time::sleep(reserve_request()).await;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status_code = response.status().as_u16() as u32;
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value.to_str().unwrap_or("")))
+            .collect();
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        Ok(Response {
+            status_code,
+            body,
+            headers,
+        })
+    }
+}
@@ -1,31 +1,56 @@
-use crate::http_client::HttpClient;
+use crate::config::current;
+use crate::http_client::{AsyncHttpClient, HttpClient};
+use crate::metrics::set_highest_index;
 use onig::Regex;
 
-/// Lowest index that points to an available build order
+/// Default lowest index that points to an available build order
 pub const LOWEST_INDEX: u32 = 5;
-/// URL to the newest builds page
+/// Default URL to the newest builds page
 pub static BUILDS_URL: &str = "https://lotv.spawningtool.com/build/?name=&contributor=&sort_by=r&build_type=&difficulty=&patch=&mine=&fav=&is_tl=";
 /// Regex to find the first build index in the HTML response
 const FIRST_BUILD_REGEX: &str = r#"<tbody><tr><td><a href="/build/(\d+)/">"#;
 
 /// Retrieves the highest build index from Spawning Tool
 pub fn get_st_highest_index() -> u32 {
-    get_highest_index(BUILDS_URL)
+    get_highest_index(&current().spawning_tool.builds_url)
+}
+
+/// Lowest index that points to an available build order, as configured
+pub fn lowest_index() -> u32 {
+    current().spawning_tool.lowest_index
 }
 
 /// Retrieves the highest build index from a given URL
 pub fn get_highest_index(url: &str) -> u32 {
     match HttpClient::fetch_url(url) {
-        Ok(response) => {
-            let re = Regex::new(FIRST_BUILD_REGEX).unwrap();
-            if let Some(captures) = re.captures(&response.body) {
-                captures
-                    .at(1)
-                    .map_or(LOWEST_INDEX, |m| m.parse::<u32>().unwrap_or(LOWEST_INDEX))
-            } else {
-                LOWEST_INDEX
-            }
+        Ok(response) => extract_highest_index(&response.body),
+        Err(_) => lowest_index(),
+    }
+}
+
+/// Retrieves the highest build index from Spawning Tool without blocking the current thread
+pub async fn get_st_highest_index_async(client: &AsyncHttpClient) -> u32 {
+    get_highest_index_async(client, &current().spawning_tool.builds_url).await
+}
+
+/// Retrieves the highest build index from a given URL without blocking the current thread
+pub async fn get_highest_index_async(client: &AsyncHttpClient, url: &str) -> u32 {
+    match client.fetch_url(url).await {
+        Ok(response) => extract_highest_index(&response.body),
+        Err(_) => lowest_index(),
+    }
+}
+
+fn extract_highest_index(html_content: &str) -> u32 {
+    let re = Regex::new(FIRST_BUILD_REGEX).unwrap();
+    let index = re
+        .captures(html_content)
+        .and_then(|captures| captures.at(1)?.parse::<u32>().ok());
+    match index {
+        Some(index) => {
+            set_highest_index(index);
+            index
         }
-        Err(_) => LOWEST_INDEX,
+        None => lowest_index(),
     }
 }
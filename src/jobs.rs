@@ -0,0 +1,618 @@
+use crate::build_order::{BuildOrder, BuildOrderError};
+use crate::build_parser::PageCache;
+use crate::build_store::BuildStore;
+use crate::crawl_state::CrawlState;
+use crate::fetch_observer::FetchObserver;
+use crate::handlers::{FetchSummary, crawl, fetch_latest_with, segment_bounds, segment_len};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+
+/// Default directory holding the state and results of background jobs
+pub const DEFAULT_JOBS_PATH: &str = "jobs";
+/// Number of jobs run at the same time by the server
+pub const JOB_WORKERS: usize = 2;
+/// Number of builds checked between two saves of the progress of a job
+pub const PROGRESS_SAVE_INTERVAL: u64 = 50;
+
+/// Work done by a background job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobKind {
+    FetchSegment {
+        start: u32,
+        end: u32,
+    },
+    FetchLatest {
+        count: u32,
+    },
+    /// Saves the fetched builds to the local collection instead of the job results
+    Sync {
+        start: u32,
+        end: u32,
+    },
+}
+
+impl JobKind {
+    /// Build pages the job fetches from Spawning Tool, not counting builds
+    /// skipped on the way.
+    pub fn fetches(&self) -> u64 {
+        match self {
+            JobKind::FetchSegment { start, end } | JobKind::Sync { start, end } => {
+                segment_len(*start, *end)
+            }
+            JobKind::FetchLatest { count } => *count as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job will not run any further.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Cancelled | JobStatus::Failed
+        )
+    }
+}
+
+/// A background job and its progress, as reported by `GET /jobs/{id}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    id: u64,
+    #[serde(flatten)]
+    kind: JobKind,
+    /// Name of the API key the job was submitted with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    status: JobStatus,
+    created: DateTime<Utc>,
+    started: Option<DateTime<Utc>>,
+    finished: Option<DateTime<Utc>>,
+    /// Number of builds to check, when known up front
+    total: Option<u64>,
+    checked: u64,
+    summary: FetchSummary,
+    /// Failure of the builds that could not be fetched, by ID
+    errors: BTreeMap<u32, String>,
+    /// Why the job itself failed
+    error: Option<String>,
+}
+
+impl Job {
+    fn new(id: u64, kind: JobKind, owner: Option<String>) -> Self {
+        Job {
+            id,
+            kind,
+            owner,
+            status: JobStatus::Queued,
+            created: Utc::now(),
+            started: None,
+            finished: None,
+            total: None,
+            checked: 0,
+            summary: FetchSummary::default(),
+            errors: BTreeMap::new(),
+            error: None,
+        }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_kind(&self) -> &JobKind {
+        &self.kind
+    }
+
+    pub fn get_owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    pub fn get_status(&self) -> JobStatus {
+        self.status
+    }
+
+    pub fn get_created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn get_started(&self) -> Option<DateTime<Utc>> {
+        self.started
+    }
+
+    pub fn get_finished(&self) -> Option<DateTime<Utc>> {
+        self.finished
+    }
+
+    pub fn get_total(&self) -> Option<u64> {
+        self.total
+    }
+
+    pub fn get_checked(&self) -> u64 {
+        self.checked
+    }
+
+    pub fn get_summary(&self) -> &FetchSummary {
+        &self.summary
+    }
+
+    pub fn get_errors(&self) -> &BTreeMap<u32, String> {
+        &self.errors
+    }
+
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// One page of the build orders fetched by a job.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobResults {
+    pub offset: usize,
+    /// Number of build orders fetched so far
+    pub total: usize,
+    pub build_orders: Vec<BuildOrder>,
+}
+
+#[derive(Debug)]
+struct JobsInner {
+    root: PathBuf,
+    store: BuildStore,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    cancelled: Mutex<HashSet<u64>>,
+    queue: Mutex<Sender<u64>>,
+}
+
+/// Appends the results of a job as NDJSON, and the byte offset of each to
+/// the job's index.
+struct ResultWriter {
+    results: File,
+    index: File,
+    length: u64,
+}
+
+impl ResultWriter {
+    fn write(&mut self, build_order: &BuildOrder) -> io::Result<()> {
+        let line = serde_json::to_string(build_order)? + "\n";
+        self.results.write_all(line.as_bytes())?;
+        self.index.write_all(&self.length.to_le_bytes())?;
+        self.length += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Runs fetch jobs in the background on a fixed number of worker threads.
+///
+/// Each job is kept in its own directory entry: `{id}.json` for its state,
+/// `{id}.ndjson` for its results, `{id}.index` for the byte offset of each
+/// result and `{id}.crawl` for the crawl state of segment and sync jobs. Jobs interrupted by a restart are queued again,
+/// segment and sync jobs resuming where they stopped.
+#[derive(Debug, Clone)]
+pub struct JobManager {
+    inner: Arc<JobsInner>,
+}
+
+impl JobManager {
+    /// Opens the jobs at the given directory, creating it if needed, and
+    /// starts `workers` threads to run them.
+    pub fn open<P: AsRef<Path>>(root: P, store: BuildStore, workers: usize) -> io::Result<Self> {
+        fs::create_dir_all(root.as_ref())?;
+        let mut jobs = BTreeMap::new();
+        for entry in fs::read_dir(root.as_ref())? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let job = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<Job>(&json).map_err(|e| e.to_string()));
+            match job {
+                Ok(job) => {
+                    jobs.insert(job.id, job);
+                }
+                // One damaged job should not keep the others from running
+                Err(e) => warn!(path = %path.display(), error = %e, "Skipping unreadable job file"),
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let manager = JobManager {
+            inner: Arc::new(JobsInner {
+                root: root.as_ref().to_path_buf(),
+                store,
+                jobs: Mutex::new(BTreeMap::new()),
+                cancelled: Mutex::new(HashSet::new()),
+                queue: Mutex::new(sender),
+            }),
+        };
+        for mut job in jobs.into_values() {
+            let id = job.id;
+            let interrupted = !job.status.is_finished();
+            if interrupted {
+                job.status = JobStatus::Queued;
+                manager.save(&job)?;
+            }
+            manager.inner.jobs.lock().unwrap().insert(id, job);
+            if interrupted {
+                manager.enqueue(id);
+            }
+        }
+
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers {
+            let manager = manager.clone();
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || manager.work(&receiver));
+        }
+        Ok(manager)
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    fn job_path(&self, id: u64, extension: &str) -> PathBuf {
+        self.inner.root.join(format!("{}.{}", id, extension))
+    }
+
+    /// Replaces the state of a job, through a temporary file so that a crash
+    /// never leaves it half written.
+    fn save(&self, job: &Job) -> io::Result<()> {
+        let path = self.job_path(job.id, "json");
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_string(job)?)?;
+        fs::rename(&temp, &path)
+    }
+
+    fn enqueue(&self, id: u64) {
+        // Workers only stop with the manager, so the queue is always open
+        let _ = self.inner.queue.lock().unwrap().send(id);
+    }
+
+    /// Queues a new job for the API key `owner`, and returns it.
+    pub fn submit(&self, kind: JobKind, owner: Option<String>) -> io::Result<Job> {
+        let job = {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+            let job = Job::new(id, kind, owner);
+            self.save(&job)?;
+            jobs.insert(id, job.clone());
+            job
+        };
+        self.enqueue(job.id);
+        Ok(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.inner.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Lists every job, oldest first.
+    pub fn list(&self) -> Vec<Job> {
+        self.inner.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Cancels a job. A queued job is cancelled at once, a running one after
+    /// the build it is fetching.
+    pub fn cancel(&self, id: u64) -> io::Result<Option<Job>> {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(&id) else {
+            return Ok(None);
+        };
+        match job.status {
+            JobStatus::Queued => {
+                job.status = JobStatus::Cancelled;
+                job.finished = Some(Utc::now());
+                self.save(job)?;
+            }
+            JobStatus::Running => {
+                self.inner.cancelled.lock().unwrap().insert(id);
+            }
+            _ => {}
+        }
+        Ok(Some(job.clone()))
+    }
+
+    /// Reads up to `limit` of the build orders fetched by a job, from `offset`.
+    /// Only the requested results are read, found through the job's index.
+    pub fn results(&self, id: u64, offset: usize, limit: usize) -> io::Result<Option<JobResults>> {
+        if self.get(id).is_none() {
+            return Ok(None);
+        }
+        let index_path = self.job_path(id, "index");
+        if !index_path.exists() {
+            self.index_results(id)?;
+        }
+        let mut index = match File::open(&index_path) {
+            Ok(index) => index,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Some(JobResults {
+                    offset,
+                    total: 0,
+                    build_orders: Vec::new(),
+                }));
+            }
+            Err(e) => return Err(e),
+        };
+        // A result is indexed once written, so each counted one is complete
+        let total = (index.metadata()?.len() / 8) as usize;
+        let count = limit.min(total.saturating_sub(offset));
+        let mut build_orders = Vec::with_capacity(count);
+        if count > 0 {
+            let mut offsets = vec![0; count * 8];
+            index.seek(SeekFrom::Start(offset as u64 * 8))?;
+            index.read_exact(&mut offsets)?;
+            let mut results = BufReader::new(File::open(self.job_path(id, "ndjson"))?);
+            let mut line = String::new();
+            for start in offsets.chunks_exact(8) {
+                results.seek(SeekFrom::Start(u64::from_le_bytes(
+                    start.try_into().unwrap(),
+                )))?;
+                line.clear();
+                results.read_line(&mut line)?;
+                build_orders.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Some(JobResults {
+            offset,
+            total,
+            build_orders,
+        }))
+    }
+
+    /// Indexes results written without an index, by earlier versions. Does
+    /// nothing for a job without results.
+    fn index_results(&self, id: u64) -> io::Result<()> {
+        let mut results = match File::open(self.job_path(id, "ndjson")) {
+            Ok(file) => BufReader::new(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut index = Vec::new();
+        let mut start = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let length = results.read_line(&mut line)?;
+            if length == 0 {
+                break;
+            }
+            if line.ends_with('\n') && !line.trim().is_empty() {
+                index.extend_from_slice(&(start as u64).to_le_bytes());
+            }
+            start += length;
+        }
+        let path = self.job_path(id, "index");
+        let temp = path.with_extension("index.tmp");
+        fs::write(&temp, index)?;
+        fs::rename(&temp, &path)
+    }
+
+    /// Starts the results of a job over.
+    fn create_results(&self, id: u64) -> io::Result<ResultWriter> {
+        Ok(ResultWriter {
+            results: File::create(self.job_path(id, "ndjson"))?,
+            index: File::create(self.job_path(id, "index"))?,
+            length: 0,
+        })
+    }
+
+    /// Continues the results of an interrupted job.
+    fn append_results(&self, id: u64) -> io::Result<ResultWriter> {
+        let index_path = self.job_path(id, "index");
+        if !index_path.exists() {
+            self.index_results(id)?;
+        }
+        let results = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.job_path(id, "ndjson"))?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(index_path)?;
+        // Past any line left half written by a crash, which stays unindexed
+        let length = results.metadata()?.len();
+        Ok(ResultWriter {
+            results,
+            index,
+            length,
+        })
+    }
+
+    fn work(&self, receiver: &Mutex<Receiver<u64>>) {
+        loop {
+            let id = match receiver.lock().unwrap().recv() {
+                Ok(id) => id,
+                Err(_) => return,
+            };
+            self.run(id);
+        }
+    }
+
+    /// Applies a change to a job and saves it.
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) {
+        self.change(id, true, change);
+    }
+
+    /// Applies the progress of one checked build to a job, saving it every
+    /// [`PROGRESS_SAVE_INTERVAL`] builds. Readers see every change at once,
+    /// and the job is saved again when it finishes.
+    fn progress(&self, id: u64, change: impl FnOnce(&mut Job)) {
+        self.change(id, false, |job| {
+            job.checked += 1;
+            change(job);
+        });
+    }
+
+    fn change(&self, id: u64, always_save: bool, change: impl FnOnce(&mut Job)) {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&id) {
+            change(job);
+            if (always_save || job.checked % PROGRESS_SAVE_INTERVAL == 0)
+                && let Err(e) = self.save(job)
+            {
+                // Saved again on the next change, a lost write only delays it
+                warn!(error = %e, "Could not save job");
+            }
+        }
+    }
+
+    #[instrument(name = "job", skip(self))]
+    fn run(&self, id: u64) {
+        let kind = {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(&id) else {
+                return;
+            };
+            if job.status != JobStatus::Queued {
+                return;
+            }
+            job.status = JobStatus::Running;
+            job.started.get_or_insert_with(Utc::now);
+            if let Err(e) = self.save(job) {
+                warn!(error = %e, "Could not save job");
+            }
+            job.kind.clone()
+        };
+
+        info!(kind = ?kind, "Job started");
+        let observer = JobObserver { manager: self, id };
+        let result = self.execute(id, &kind, &observer);
+        match &result {
+            Ok(()) => info!("Job finished"),
+            Err(e) => warn!(error = %e, "Job failed"),
+        }
+        let cancelled = self.inner.cancelled.lock().unwrap().remove(&id);
+        self.update(id, |job| {
+            job.finished = Some(Utc::now());
+            job.status = match result {
+                Ok(()) if cancelled => JobStatus::Cancelled,
+                Ok(()) => JobStatus::Completed,
+                Err(e) => {
+                    job.error = Some(e.to_string());
+                    JobStatus::Failed
+                }
+            };
+        });
+    }
+
+    fn execute(&self, id: u64, kind: &JobKind, observer: &JobObserver) -> io::Result<()> {
+        let mut error = None;
+        match kind {
+            JobKind::FetchLatest { count } => {
+                // The latest builds change between runs, so an interrupted job starts over
+                self.update(id, |job| {
+                    job.checked = 0;
+                    job.summary = FetchSummary::default();
+                    job.errors.clear();
+                });
+                let mut writer = self.create_results(id)?;
+                fetch_latest_with(*count, observer, |build_order| {
+                    if let Err(e) = writer.write(&build_order) {
+                        error.get_or_insert(e);
+                    }
+                });
+            }
+            JobKind::FetchSegment { start, end } => {
+                let mut state = self.crawl_state(id, *start, *end)?;
+                let mut writer = self.append_results(id)?;
+                crawl(&mut state, PageCache::Use, observer, |build_order| {
+                    if let Err(e) = writer.write(&build_order) {
+                        error.get_or_insert(e);
+                    }
+                });
+            }
+            JobKind::Sync { start, end } => {
+                let mut state = self.crawl_state(id, *start, *end)?;
+                // Syncing is meant to pick up changes, so cached pages are not used
+                crawl(&mut state, PageCache::Refresh, observer, |build_order| {
+                    if let Err(e) = self.inner.store.save(&build_order) {
+                        error.get_or_insert(e);
+                    }
+                });
+            }
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Opens the crawl state of an interrupted job, or starts a new one.
+    fn crawl_state(&self, id: u64, start: u32, end: u32) -> io::Result<CrawlState> {
+        let path = self.job_path(id, "crawl");
+        if path.exists() {
+            return CrawlState::open(path);
+        }
+        // An empty segment is kept as an empty range, without any build to fetch
+        let bounds = if start <= end {
+            segment_bounds(start, end)
+        } else {
+            None
+        };
+        let (start, end) = bounds.unwrap_or((1, 0));
+        CrawlState::create(path, start, end)
+    }
+}
+
+/// Records the progress of a running job.
+struct JobObserver<'a> {
+    manager: &'a JobManager,
+    id: u64,
+}
+
+impl FetchObserver for JobObserver<'_> {
+    fn started(&self, total: Option<u64>) {
+        // A resumed job adds the builds left to those it already checked
+        self.manager.update(self.id, |job| {
+            job.total = total.map(|total| total + job.checked)
+        });
+    }
+
+    fn fetched(&self, build_order: &BuildOrder) {
+        self.manager.progress(self.id, |job| {
+            job.errors.remove(&build_order.get_id());
+            job.summary.fetched += 1;
+        });
+    }
+
+    fn skipped_cloaked(&self, id: u32) {
+        self.manager.progress(self.id, |job| {
+            job.errors.remove(&id);
+            job.summary.cloaked += 1;
+        });
+    }
+
+    fn failed(&self, id: u32, error: &BuildOrderError) {
+        self.manager.progress(self.id, |job| {
+            job.summary.record(&Err::<(), _>(error.clone()));
+            job.errors.insert(id, error.to_string());
+        });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.manager
+            .inner
+            .cancelled
+            .lock()
+            .unwrap()
+            .contains(&self.id)
+    }
+}
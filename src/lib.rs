@@ -1,7 +1,31 @@
+pub mod action_catalog;
 pub mod api;
+pub mod auth;
+pub mod browser;
+pub mod build_diff;
+pub mod build_import;
 pub mod build_order;
 pub mod build_parser;
 pub mod build_regex;
+pub mod build_store;
+pub mod classifier;
+pub mod collections;
+pub mod config;
+pub mod crawl_state;
+pub mod fetch_observer;
+pub mod graphql;
 pub mod handlers;
+pub mod http_cache;
 pub mod http_client;
 pub mod index_manager;
+pub mod jobs;
+pub mod logging;
+pub mod metrics;
+pub mod output;
+pub mod projection;
+pub mod query;
+pub mod salt;
+pub mod search;
+pub mod similarity;
+pub mod stats;
+pub mod text_notation;
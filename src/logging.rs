@@ -0,0 +1,34 @@
+use crate::config::{LogConfig, LogFormat, LogLevel};
+use std::io::{self, IsTerminal};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Filter directive logging Build Warren at `level` and its dependencies
+/// only when they warn.
+pub fn filter_directive(level: LogLevel) -> String {
+    match level {
+        LogLevel::Off => "off".to_string(),
+        LogLevel::Error => "error".to_string(),
+        level => format!("warn,build_warren={}", level),
+    }
+}
+
+/// Sends log events to stderr, as configured. Spans are logged when they
+/// close, with the time spent in them. Returns `false` when a logger was
+/// already installed.
+pub fn init(config: &LogConfig) -> bool {
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_env_filter(EnvFilter::new(filter_directive(config.level)))
+        .with_span_events(FmtSpan::CLOSE);
+    match config.format {
+        LogFormat::Text => builder.try_init().is_ok(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init()
+            .is_ok(),
+    }
+}
@@ -1,11 +1,34 @@
 use build_warren::api::run;
-use build_warren::build_parser::fetch_build_order;
-use build_warren::handlers::{fetch_latest, fetch_segment};
+use build_warren::auth::{Authenticator, KEYS_FILE, KeyStore};
+use build_warren::browser;
+use build_warren::build_diff::diff_builds;
+use build_warren::build_import::{ImportFormat, import_build};
+use build_warren::build_order::{BuildOrder, BuildType, Race};
+use build_warren::build_parser::{PageCache, fetch_build_order};
+use build_warren::build_store::BuildStore;
+use build_warren::classifier::OpenerClassifier;
+use build_warren::collections::{CollectionBundle, LIBRARY_FILE, LOCAL_OWNER, LibraryStore};
+use build_warren::config::{self, Config, LogFormat, QuotaConfig};
+use build_warren::crawl_state::CrawlState;
+use build_warren::fetch_observer::ProgressObserver;
+use build_warren::handlers::{
+    FetchSummary, crawl, fetch_latest_with, fetch_segment_with, segment_bounds,
+};
 use build_warren::index_manager::get_st_highest_index;
-use clap::{Parser, Subcommand};
-use console::{Emoji, style};
-use serde_json;
+use build_warren::jobs::JobManager;
+use build_warren::logging;
+use build_warren::output::{BuildWriter, OutputFormat, PartFile, render_build, render_builds};
+use build_warren::query::Query;
+use build_warren::search::{SearchFilter, parse_build_type, parse_race, search};
+use build_warren::similarity::{DUPLICATE_THRESHOLD, duplicate_clusters, most_similar};
+use build_warren::stats::{StatsOptions, compute_stats};
+use chrono::NaiveDate;
+use clap::{ArgAction, Parser, Subcommand};
+use console::{Emoji, strip_ansi_codes, style};
 use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::{error, warn};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -15,6 +38,35 @@ struct Cli {
 
     #[arg(short, long)]
     output: Option<String>,
+
+    /// The format of fetched build orders (json, ndjson, yaml, csv, markdown, text, salt),
+    /// or of the file read by `import` (text, salt)
+    #[arg(short, long, global = true)]
+    format: Option<OutputFormat>,
+
+    /// Directory of the local build collection (default: builds)
+    #[arg(long, global = true)]
+    store: Option<String>,
+
+    /// TOML config file (default: $BUILD_WARREN_CONFIG, or build_warren.toml if present)
+    #[arg(long, global = true)]
+    config: Option<String>,
+
+    /// Log more detail, repeat for even more (-vv)
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Log less, repeat for even less (-qq)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+
+    /// The format of log lines on stderr (text, json)
+    #[arg(long, global = true)]
+    log_format: Option<LogFormat>,
+
+    /// TOML file of opener classification rules (defaults to the bundled rules)
+    #[arg(long)]
+    rules: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -37,17 +89,271 @@ enum Commands {
 
     /// Fetch a segment of build orders
     FetchSegment {
+        /// The starting index of the segment
+        #[arg(required_unless_present = "resume")]
+        start: Option<u32>,
+        /// The ending index of the segment
+        #[arg(required_unless_present = "resume")]
+        end: Option<u32>,
+        /// Record the outcome of every build in this crawl-state file
+        #[arg(long, conflicts_with = "resume")]
+        state: Option<String>,
+        /// Resume the crawl recorded in this crawl-state file, retrying failed builds
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        resume: Option<String>,
+    },
+
+    /// Inspect crawl-state files
+    Crawl {
+        #[command(subcommand)]
+        command: CrawlCommands,
+    },
+
+    /// Compare the steps of two build orders
+    Diff {
+        /// The ID of the first build order, or a path to a JSON build order
+        a: String,
+        /// The ID of the second build order, or a path to a JSON build order
+        b: String,
+        /// Print the diff as JSON instead of the coloured view
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Fetch a segment of build orders into the local collection
+    Sync {
         /// The starting index of the segment
         start: u32,
         /// The ending index of the segment
         end: u32,
     },
 
+    /// Find the stored build orders most similar to a build
+    Similar {
+        /// The ID of the reference build order
+        id: u32,
+        /// The maximum number of similar builds to show
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Group near-duplicate build orders of the local collection
+    Duplicates {
+        /// The similarity above which two builds are near-duplicates
+        #[arg(short, long, default_value_t = DUPLICATE_THRESHOLD)]
+        threshold: f64,
+    },
+
+    /// Search the names, creators, descriptions and comments of the local collection
+    Search {
+        /// The words to look for, such as "roach ravager timing"
+        query: String,
+        /// Only search builds of this race (T, P, Z or its name)
+        #[arg(long, value_parser = parse_race)]
+        race: Option<Race>,
+        /// Only search builds against this race
+        #[arg(long, value_parser = parse_race)]
+        opponent_race: Option<Race>,
+        /// Only search builds of this type, such as timing or all-in
+        #[arg(long = "type", value_parser = parse_build_type)]
+        build_type: Option<BuildType>,
+        /// The maximum number of results to show
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// List the stored build orders matching a query, such as
+    /// `race:Z vs:P votes>=60 has:"Roach Warren"<3:00`
+    Query {
+        /// The query, made of key:value terms, `OR`, `-` and parentheses
+        query: String,
+        /// The maximum number of build orders to show
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
+
+    /// Browse the local collection in a terminal UI
+    Browse,
+
+    /// Organise build orders into named collections, shared as bundles
+    Collection {
+        #[command(subcommand)]
+        command: CollectionCommands,
+    },
+
+    /// Star build orders
+    Star {
+        /// The IDs of the build orders
+        #[arg(required = true)]
+        ids: Vec<u32>,
+    },
+
+    /// Unstar build orders
+    Unstar {
+        /// The IDs of the build orders
+        #[arg(required = true)]
+        ids: Vec<u32>,
+    },
+
+    /// List the starred build orders found in the local collection
+    Favourites,
+
+    /// Show the private notes on a build order, or set one
+    Note {
+        /// The ID of the build order
+        id: u32,
+        /// The note, replacing the previous one (a blank note removes it)
+        note: Option<String>,
+        /// Annotate this step, starting at 1, instead of the whole build
+        #[arg(long)]
+        step: Option<usize>,
+        /// Remove the notes on the build order and its steps
+        #[arg(long, conflicts_with_all = ["note", "step"])]
+        clear: bool,
+    },
+
+    /// Report statistics over the local collection
+    Stats {
+        /// The number of leading actions forming an opener
+        #[arg(long, default_value_t = 5)]
+        opener_length: usize,
+        /// The number of entries kept in ranked lists
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// The minimum vote count for a build to be ranked by score
+        #[arg(long, default_value_t = 3)]
+        min_votes: u32,
+        /// Only include builds published on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only include builds published on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+
+    /// Label a build order with the openers it matches
+    Classify {
+        /// The ID of the build order to classify
+        id: u32,
+    },
+
+    /// List the stored build orders labelled with an opener
+    Openers {
+        /// The opener label to look for (all labelled builds if omitted)
+        label: Option<String>,
+    },
+
+    /// Import a build order from a file, in the format given by `--format` (default: text)
+    Import {
+        /// The file to import
+        file: String,
+        /// Save the imported build order to the local collection
+        #[arg(long)]
+        save: bool,
+    },
+
     /// Start the HTTP server
     Listen {
-        /// The port to run the server on
-        #[arg(short, long, default_value_t = 8080)]
-        port: u16,
+        /// The port to run the server on (default: 8080)
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// The address to bind the server to (default: 127.0.0.1)
+        #[arg(long)]
+        bind: Option<String>,
+        /// The directory holding the state and results of background jobs (default: jobs)
+        #[arg(long)]
+        jobs: Option<String>,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Manage the API keys of the server
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// Issue a new API key to a client and print it
+    Issue {
+        /// The name of the client
+        name: String,
+        /// Requests allowed per minute, instead of the configured quota
+        #[arg(long)]
+        requests_per_minute: Option<u32>,
+        /// Build pages fetched from Spawning Tool per hour, instead of the configured quota
+        #[arg(long)]
+        fetches_per_hour: Option<u32>,
+    },
+    /// Revoke the API key of a client
+    Revoke {
+        /// The name of the client
+        name: String,
+    },
+    /// List the issued API keys
+    List,
+}
+
+#[derive(Subcommand)]
+enum CollectionCommands {
+    /// Add build orders to a collection, creating it if needed
+    Add {
+        /// The name of the collection, such as "ZvP ladder"
+        name: String,
+        /// The IDs of the build orders
+        ids: Vec<u32>,
+        /// A description of the collection, when creating it
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Remove build orders from a collection, or the whole collection
+    Remove {
+        /// The name of the collection
+        name: String,
+        /// The IDs of the build orders (the collection itself if omitted)
+        ids: Vec<u32>,
+    },
+    /// List the collections, or the build orders of one
+    List {
+        /// The name of the collection (all collections if omitted)
+        name: Option<String>,
+    },
+    /// Bundle a collection into a shareable JSON file
+    Export {
+        /// The name of the collection
+        name: String,
+        /// Include the private notes on the build orders
+        #[arg(long)]
+        notes: bool,
+    },
+    /// Import a bundled collection, saving the build orders it carries under local IDs
+    Import {
+        /// The bundle file
+        file: String,
+        /// The name of the imported collection, instead of the bundled one
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective config, merged from the file, environment and flags
+    Show,
+}
+
+#[derive(Subcommand)]
+enum CrawlCommands {
+    /// Summarise the progress and gaps of a crawl
+    Status {
+        /// The crawl-state file
+        state: String,
     },
 }
 
@@ -55,8 +361,153 @@ static CLIPBOARD_EMOJI: Emoji = Emoji("📋 ", "");
 static OUTPUT_EMOJI: Emoji = Emoji("📂 ", "");
 static SERVER_EMOJI: Emoji = Emoji("🔭 ", "");
 
+/// Loads a build order from Spawning Tool if `source` is an ID, or from a JSON file otherwise.
+fn load_build(source: &str) -> Result<BuildOrder, String> {
+    match source.parse::<u32>() {
+        Ok(id) => fetch_build_order(id).map_err(|e| e.to_string()),
+        Err(_) => {
+            let content = fs::read_to_string(source).map_err(|e| e.to_string())?;
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Opens the local collection, for the commands that use it.
+fn open_store(config: &Config) -> Option<BuildStore> {
+    BuildStore::open(&config.storage.store)
+        .inspect_err(
+            |e| error!(path = config.storage.store, error = %e, "Error opening the build store"),
+        )
+        .ok()
+}
+
+/// Loads the opener rules, for the commands that label builds.
+fn load_classifier(rules: Option<&str>) -> Option<OpenerClassifier> {
+    OpenerClassifier::load(rules)
+        .inspect_err(|e| error!(error = %e, "Could not load opener rules"))
+        .ok()
+}
+
+/// Opens the favourites, collections and notes kept in the store directory.
+fn open_library(store: &BuildStore) -> Option<LibraryStore> {
+    LibraryStore::open(store.get_root().join(LIBRARY_FILE))
+        .inspect_err(|e| error!(error = %e, "Error opening the library"))
+        .ok()
+}
+
+/// Loads the build orders of `ids` from the store, warning about those missing.
+fn stored_builds(store: &BuildStore, ids: &[u32]) -> Vec<BuildOrder> {
+    ids.iter()
+        .filter_map(|id| {
+            let build_order = store.load(*id);
+            if build_order.is_none() {
+                warn!(build_id = id, "Build order is not in the local collection");
+            }
+            build_order
+        })
+        .collect()
+}
+
+/// Writes build orders to the output file, or to stdout.
+fn print_builds(build_orders: &[BuildOrder], output: Option<&str>, format: OutputFormat) {
+    let output_text = render_builds(build_orders, format);
+    match output {
+        Some(output_file) => {
+            fs::write(output_file, strip_ansi_codes(&output_text).as_ref())
+                .expect("Failed to write build orders to output file");
+            println!(
+                "{} {}{} build orders written to {}",
+                OUTPUT_EMOJI,
+                style("Success : ").green(),
+                build_orders.len(),
+                output_file
+            );
+        }
+        None => println!("{}", output_text),
+    }
+}
+
+/// Writes the build orders `fetch` hands out to the output file, or to stdout,
+/// as soon as each one is fetched, adding to an existing output file if
+/// `append` is set. Returns the outcomes of the fetch.
+fn stream_builds(
+    output: Option<&str>,
+    format: OutputFormat,
+    append: bool,
+    fetch: impl FnOnce(&mut dyn FnMut(BuildOrder)) -> FetchSummary,
+) -> io::Result<FetchSummary> {
+    match output {
+        Some(output_file) => {
+            let mut writer = if append {
+                let part_file = PartFile::append(output_file)?;
+                if part_file.has_content()? {
+                    BuildWriter::appending(part_file, format)
+                } else {
+                    BuildWriter::new(part_file, format)
+                }
+            } else {
+                BuildWriter::new(PartFile::create(output_file)?, format)
+            }
+            .without_colours();
+            let summary = fetch(&mut |build_order| {
+                writer
+                    .write(&build_order)
+                    .expect("Failed to write build order to output file")
+            });
+            writer.finish()?.commit()?;
+            Ok(summary)
+        }
+        None => {
+            let mut writer = BuildWriter::new(io::stdout(), format);
+            let summary = fetch(&mut |build_order| {
+                writer
+                    .write(&build_order)
+                    .expect("Failed to write build order to stdout")
+            });
+            writer.finish()?;
+            Ok(summary)
+        }
+    }
+}
+
+/// Applies the command-line flags over the config file and environment.
+fn apply_flags(config: &mut Config, cli: &Cli) {
+    if let Some(store) = &cli.store {
+        config.storage.store = store.clone();
+    }
+    config.log.level = config
+        .log
+        .level
+        .shifted(cli.verbose as i16 - cli.quiet as i16);
+    if let Some(log_format) = cli.log_format {
+        config.log.format = log_format;
+    }
+    if let Some(Commands::Listen { port, bind, jobs }) = &cli.command {
+        if let Some(port) = port {
+            config.server.port = *port;
+        }
+        if let Some(bind) = bind {
+            config.server.bind = bind.clone();
+        }
+        if let Some(jobs) = jobs {
+            config.storage.jobs = jobs.clone();
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
+    let mut config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    apply_flags(&mut config, &cli);
+    logging::init(&config.log);
+    config::install(config.clone());
+    let format = cli.format.unwrap_or(OutputFormat::Json);
 
     println!(
         "{} {} {}",
@@ -83,10 +534,9 @@ fn main() {
         }
         Some(Commands::Fetch { id }) => match fetch_build_order(*id) {
             Ok(build_order) => {
-                let json_output = serde_json::to_string_pretty(&build_order)
-                    .expect("Failed to serialize build order to JSON");
+                let output = render_build(&build_order, format);
                 if let Some(output_file) = &cli.output {
-                    fs::write(output_file, json_output)
+                    fs::write(output_file, strip_ansi_codes(&output).as_ref())
                         .expect("Failed to write build order to output file");
                     println!(
                         "{} {}Build order {} written to {}",
@@ -96,39 +546,88 @@ fn main() {
                         output_file
                     );
                 } else {
-                    println!("{}", json_output);
+                    println!("{}", output);
                 }
             }
-            Err(e) => eprintln!("Error fetching build order: {}", e),
+            Err(e) => error!(build_id = id, error = %e, "Error fetching build order"),
         },
         Some(Commands::FetchLatest { count }) => {
-            let build_orders = fetch_latest(*count);
-            let json_output = serde_json::to_string_pretty(&build_orders)
-                .expect("Failed to serialize build orders to JSON");
+            let summary = stream_builds(cli.output.as_deref(), format, false, |on_build| {
+                fetch_latest_with(*count, &ProgressObserver::new(), on_build)
+            })
+            .expect("Failed to write build orders to output file");
             if let Some(output_file) = &cli.output {
-                fs::write(output_file, json_output)
-                    .expect("Failed to write build orders to output file");
                 println!(
                     "{} {}Latest {} build orders written to {}",
                     OUTPUT_EMOJI,
                     style("Success : ").green(),
-                    count,
+                    summary.fetched,
                     output_file
                 );
-            } else {
-                println!("{}", json_output);
             }
         }
         None => {
-            eprintln!("No command provided. Use --help to see available commands.");
+            error!("No command provided. Use --help to see available commands.");
         }
-        Some(Commands::FetchSegment { start, end }) => {
-            let build_orders = fetch_segment(*start, *end);
-            let json_output = serde_json::to_string_pretty(&build_orders)
-                .expect("Failed to serialize build orders to JSON");
+        Some(Commands::FetchSegment {
+            start,
+            end,
+            state,
+            resume,
+        }) => {
+            if resume.is_some() && cli.output.is_some() && !format.is_appendable() {
+                error!(
+                    format = %format,
+                    "Cannot add to this output format when resuming, use a line-based format such as ndjson"
+                );
+                return;
+            }
+            if let (Some(start), Some(end)) = (start, end)
+                && start > end
+            {
+                error!(start, end, "Start index is greater than end index");
+                return;
+            }
+            let mut crawl_state = match (resume, state, start, end) {
+                (Some(path), _, _, _) => match CrawlState::open(path) {
+                    Ok(crawl_state) => Some(crawl_state),
+                    Err(e) => {
+                        error!(path, error = %e, "Error opening crawl state");
+                        return;
+                    }
+                },
+                (None, Some(path), Some(start), Some(end)) => match segment_bounds(*start, *end) {
+                    Some((start, end)) => Some(
+                        CrawlState::create(path, start, end)
+                            .expect("Failed to create crawl state file"),
+                    ),
+                    None => {
+                        error!(start, end, "No build orders in this segment");
+                        return;
+                    }
+                },
+                _ => None,
+            };
+            let (start, end) = match &crawl_state {
+                Some(crawl_state) => (crawl_state.get_start(), crawl_state.get_end()),
+                None => (start.unwrap_or_default(), end.unwrap_or_default()),
+            };
+            stream_builds(
+                cli.output.as_deref(),
+                format,
+                resume.is_some(),
+                |on_build| {
+                    let observer = ProgressObserver::new();
+                    match &mut crawl_state {
+                        Some(crawl_state) => {
+                            crawl(crawl_state, PageCache::Use, &observer, on_build)
+                        }
+                        None => fetch_segment_with(start, end, PageCache::Use, &observer, on_build),
+                    }
+                },
+            )
+            .expect("Failed to write build orders to output file");
             if let Some(output_file) = &cli.output {
-                fs::write(output_file, json_output)
-                    .expect("Failed to write build orders to output file");
                 println!(
                     "{} {}Build orders from {} to {} written to {}",
                     OUTPUT_EMOJI,
@@ -137,19 +636,688 @@ fn main() {
                     end,
                     output_file
                 );
+            }
+            if let Some(crawl_state) = &crawl_state {
+                let summary = crawl_state.summary();
+                println!(
+                    "{} {}{} of {} builds done, {} failed, crawl state in {}",
+                    OUTPUT_EMOJI,
+                    style("Info : ").blue(),
+                    summary.total - summary.failed - summary.not_attempted,
+                    summary.total,
+                    summary.failed,
+                    crawl_state.get_path().display()
+                );
+            }
+        }
+        Some(Commands::Crawl {
+            command: CrawlCommands::Status { state },
+        }) => match CrawlState::open(state) {
+            Ok(crawl_state) => {
+                let json_output = serde_json::to_string_pretty(&crawl_state.summary())
+                    .expect("Failed to serialize crawl summary to JSON");
+                if let Some(output_file) = &cli.output {
+                    fs::write(output_file, json_output)
+                        .expect("Failed to write crawl summary to output file");
+                    println!(
+                        "{} {}Summary of crawl {} written to {}",
+                        OUTPUT_EMOJI,
+                        style("Success : ").green(),
+                        state,
+                        output_file
+                    );
+                } else {
+                    println!("{}", json_output);
+                }
+            }
+            Err(e) => error!(path = state, error = %e, "Error opening crawl state"),
+        },
+        Some(Commands::Diff { a, b, json }) => match (load_build(a), load_build(b)) {
+            (Ok(left), Ok(right)) => {
+                let diff = diff_builds(&left, &right);
+                if let Some(output_file) = &cli.output {
+                    let json_output = serde_json::to_string_pretty(&diff)
+                        .expect("Failed to serialize diff to JSON");
+                    fs::write(output_file, json_output)
+                        .expect("Failed to write diff to output file");
+                    println!(
+                        "{} {}Diff between {} and {} written to {}",
+                        OUTPUT_EMOJI,
+                        style("Success : ").green(),
+                        a,
+                        b,
+                        output_file
+                    );
+                } else if *json {
+                    let json_output = serde_json::to_string_pretty(&diff)
+                        .expect("Failed to serialize diff to JSON");
+                    println!("{}", json_output);
+                } else {
+                    println!("{}", diff);
+                }
+            }
+            (Err(e), _) => error!(source = a, error = %e, "Error loading build order"),
+            (_, Err(e)) => error!(source = b, error = %e, "Error loading build order"),
+        },
+        Some(Commands::Sync { start, end }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            if start > end {
+                error!(start, end, "Start index is greater than end index");
+                return;
+            }
+            // Syncing is meant to pick up changes, so cached pages are not used
+            let saved = fetch_segment_with(
+                *start,
+                *end,
+                PageCache::Refresh,
+                &ProgressObserver::new(),
+                |build_order| {
+                    store
+                        .save(&build_order)
+                        .expect("Failed to save build order to store")
+                },
+            );
+            println!(
+                "{} {}{} build orders saved to {}",
+                OUTPUT_EMOJI,
+                style("Success : ").green(),
+                saved.fetched,
+                config.storage.store
+            );
+        }
+        Some(Commands::Similar { id, limit }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let target = match store.load(*id) {
+                Some(build_order) => Ok(build_order),
+                None => fetch_build_order(*id),
+            };
+            match target {
+                Ok(target) => {
+                    let similar = most_similar(&target, &store.load_all(), *limit);
+                    let json_output = serde_json::to_string_pretty(&similar)
+                        .expect("Failed to serialize similar builds to JSON");
+                    if let Some(output_file) = &cli.output {
+                        fs::write(output_file, json_output)
+                            .expect("Failed to write similar builds to output file");
+                        println!(
+                            "{} {}Builds similar to {} written to {}",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            id,
+                            output_file
+                        );
+                    } else {
+                        println!("{}", json_output);
+                    }
+                }
+                Err(e) => error!(build_id = id, error = %e, "Error fetching build order"),
+            }
+        }
+        Some(Commands::Duplicates { threshold }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let clusters = duplicate_clusters(&store.load_all(), *threshold);
+            let json_output = serde_json::to_string_pretty(&clusters)
+                .expect("Failed to serialize clusters to JSON");
+            if let Some(output_file) = &cli.output {
+                fs::write(output_file, json_output)
+                    .expect("Failed to write clusters to output file");
+                println!(
+                    "{} {}{} clusters of near-duplicates written to {}",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    clusters.len(),
+                    output_file
+                );
+            } else {
+                println!("{}", json_output);
+            }
+        }
+        Some(Commands::Search {
+            query,
+            race,
+            opponent_race,
+            build_type,
+            limit,
+        }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let filter = SearchFilter {
+                race: race.clone(),
+                opponent_race: opponent_race.clone(),
+                build_type: build_type.clone(),
+            };
+            let hits = search(&store.load_all(), query, &filter, *limit);
+            let json_output = serde_json::to_string_pretty(&hits)
+                .expect("Failed to serialize search results to JSON");
+            if let Some(output_file) = &cli.output {
+                fs::write(output_file, json_output)
+                    .expect("Failed to write search results to output file");
+                println!(
+                    "{} {}{} build orders matching \"{}\" written to {}",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    hits.len(),
+                    query,
+                    output_file
+                );
+            } else {
+                println!("{}", json_output);
+            }
+        }
+        Some(Commands::Query { query, limit }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let query = match Query::parse(query) {
+                Ok(query) => query,
+                Err(e) => {
+                    error!(query = query, error = %e, "Invalid query");
+                    return;
+                }
+            };
+            let corpus = store.load_all();
+            let matches: Vec<BuildOrder> = query
+                .filter(&corpus)
+                .into_iter()
+                .take(limit.unwrap_or(usize::MAX))
+                .cloned()
+                .collect();
+            let output = render_builds(&matches, format);
+            if let Some(output_file) = &cli.output {
+                fs::write(output_file, strip_ansi_codes(&output).as_ref())
+                    .expect("Failed to write build orders to output file");
+                println!(
+                    "{} {}{} build orders matching {} written to {}",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    matches.len(),
+                    query,
+                    output_file
+                );
+            } else {
+                println!("{}", output);
+            }
+        }
+        Some(Commands::Browse) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            if let Err(e) = browser::run(&store, format) {
+                error!(error = %e, "Error running the browser");
+            }
+        }
+        Some(Commands::Collection { command }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(library) = open_library(&store) else {
+                return;
+            };
+            match command {
+                CollectionCommands::Add {
+                    name,
+                    ids,
+                    description,
+                } => {
+                    let result = library.update(LOCAL_OWNER, |library| {
+                        if library.get_collection(name).is_none() {
+                            library.create_collection(name, description.clone())?;
+                        }
+                        library.add_builds(name, ids).cloned()
+                    });
+                    match result {
+                        Ok(collection) => println!(
+                            "{} {}{} now holds {} build orders",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            name,
+                            collection.get_builds().len()
+                        ),
+                        Err(e) => error!(name, error = %e, "Error adding to collection"),
+                    }
+                }
+                CollectionCommands::Remove { name, ids } => {
+                    let result = library.update(LOCAL_OWNER, |library| match ids.is_empty() {
+                        true => library.delete_collection(name).map(|_| None),
+                        false => library.remove_builds(name, ids).cloned().map(Some),
+                    });
+                    match result {
+                        Ok(Some(collection)) => println!(
+                            "{} {}{} now holds {} build orders",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            name,
+                            collection.get_builds().len()
+                        ),
+                        Ok(None) => println!(
+                            "{} {}Collection {} removed",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            name
+                        ),
+                        Err(e) => error!(name, error = %e, "Error removing from collection"),
+                    }
+                }
+                CollectionCommands::List { name } => {
+                    let library = match library.library(LOCAL_OWNER) {
+                        Ok(library) => library,
+                        Err(e) => {
+                            error!(error = %e, "Error reading the library");
+                            return;
+                        }
+                    };
+                    match name {
+                        Some(name) => match library.get_collection(name) {
+                            Some(collection) => print_builds(
+                                &stored_builds(&store, collection.get_builds()),
+                                cli.output.as_deref(),
+                                format,
+                            ),
+                            None => error!(name, "No collection is named {}", name),
+                        },
+                        None => {
+                            let json_output =
+                                serde_json::to_string_pretty(&library.get_collections())
+                                    .expect("Failed to serialize collections to JSON");
+                            println!("{}", json_output);
+                        }
+                    }
+                }
+                CollectionCommands::Export { name, notes } => {
+                    let bundle = library
+                        .library(LOCAL_OWNER)
+                        .and_then(|library| library.bundle(name, &store, *notes));
+                    let bundle = match bundle {
+                        Ok(bundle) => bundle,
+                        Err(e) => {
+                            error!(name, error = %e, "Error bundling collection");
+                            return;
+                        }
+                    };
+                    let json_output = serde_json::to_string_pretty(&bundle)
+                        .expect("Failed to serialize bundle to JSON");
+                    if let Some(output_file) = &cli.output {
+                        fs::write(output_file, json_output)
+                            .expect("Failed to write bundle to output file");
+                        println!(
+                            "{} {}Collection {} bundled with {} build orders into {}",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            name,
+                            bundle.get_builds().len(),
+                            output_file
+                        );
+                    } else {
+                        println!("{}", json_output);
+                    }
+                }
+                CollectionCommands::Import { file, name } => {
+                    let bundle: CollectionBundle = match fs::read_to_string(file)
+                        .map_err(|e| e.to_string())
+                        .and_then(|content| {
+                            serde_json::from_str(&content).map_err(|e| e.to_string())
+                        }) {
+                        Ok(bundle) => bundle,
+                        Err(e) => {
+                            error!(path = file, error = %e, "Error reading bundle");
+                            return;
+                        }
+                    };
+                    let result = library.update(LOCAL_OWNER, |library| {
+                        library
+                            .import_bundle(bundle, name.as_deref(), &store)
+                            .cloned()
+                    });
+                    match result {
+                        Ok(collection) => println!(
+                            "{} {}Collection {} imported with {} build orders",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            collection.get_name(),
+                            collection.get_builds().len()
+                        ),
+                        Err(e) => error!(path = file, error = %e, "Error importing bundle"),
+                    }
+                }
+            }
+        }
+        Some(Commands::Star { ids }) | Some(Commands::Unstar { ids }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(library) = open_library(&store) else {
+                return;
+            };
+            let star = matches!(cli.command, Some(Commands::Star { .. }));
+            let result = library.update(LOCAL_OWNER, |library| {
+                for id in ids {
+                    match star {
+                        true => library.star(*id),
+                        false => library.unstar(*id),
+                    };
+                }
+                Ok(library.get_favourites().len())
+            });
+            match result {
+                Ok(count) => println!(
+                    "{} {}{} build orders starred",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    count
+                ),
+                Err(e) => error!(error = %e, "Error updating favourites"),
+            }
+        }
+        Some(Commands::Favourites) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(library) = open_library(&store) else {
+                return;
+            };
+            match library.library(LOCAL_OWNER) {
+                Ok(library) => {
+                    let ids: Vec<u32> = library.get_favourites().iter().copied().collect();
+                    print_builds(&stored_builds(&store, &ids), cli.output.as_deref(), format);
+                }
+                Err(e) => error!(error = %e, "Error reading the library"),
+            }
+        }
+        Some(Commands::Note {
+            id,
+            note,
+            step,
+            clear,
+        }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(library) = open_library(&store) else {
+                return;
+            };
+            let result = match (note, step) {
+                _ if *clear => library.update(LOCAL_OWNER, |library| {
+                    library.clear_notes(*id);
+                    Ok(None)
+                }),
+                (Some(note), Some(step)) => {
+                    let Some(build_order) = store.load(*id) else {
+                        error!(build_id = id, "Build order is not in the local collection");
+                        return;
+                    };
+                    library.update(LOCAL_OWNER, |library| {
+                        library.set_step_note(&build_order, *step, note)?;
+                        Ok(library.get_notes(*id).cloned())
+                    })
+                }
+                (Some(note), None) => library.update(LOCAL_OWNER, |library| {
+                    library.set_note(*id, note);
+                    Ok(library.get_notes(*id).cloned())
+                }),
+                (None, _) => library
+                    .library(LOCAL_OWNER)
+                    .map(|library| library.get_notes(*id).cloned()),
+            };
+            match result {
+                Ok(notes) => {
+                    let json_output = serde_json::to_string_pretty(&notes.unwrap_or_default())
+                        .expect("Failed to serialize notes to JSON");
+                    println!("{}", json_output);
+                }
+                Err(e) => error!(build_id = id, error = %e, "Error updating notes"),
+            }
+        }
+        Some(Commands::Stats {
+            opener_length,
+            top,
+            min_votes,
+            since,
+            until,
+        }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let options = StatsOptions {
+                opener_length: *opener_length,
+                top: *top,
+                min_votes: *min_votes,
+                since: *since,
+                until: *until,
+            };
+            let stats = compute_stats(&store.load_all(), &options);
+            let json_output = serde_json::to_string_pretty(&stats)
+                .expect("Failed to serialize statistics to JSON");
+            if let Some(output_file) = &cli.output {
+                fs::write(output_file, json_output)
+                    .expect("Failed to write statistics to output file");
+                println!(
+                    "{} {}Statistics over {} build orders written to {}",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    stats.total,
+                    output_file
+                );
+            } else {
+                println!("{}", json_output);
+            }
+        }
+        Some(Commands::Classify { id }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(classifier) = load_classifier(cli.rules.as_deref()) else {
+                return;
+            };
+            let build_order = match store.load(*id) {
+                Some(build_order) => Ok(build_order),
+                None => fetch_build_order(*id),
+            };
+            match build_order {
+                Ok(build_order) => {
+                    let json_output =
+                        serde_json::to_string_pretty(&classifier.summarize(&build_order))
+                            .expect("Failed to serialize openers to JSON");
+                    if let Some(output_file) = &cli.output {
+                        fs::write(output_file, json_output)
+                            .expect("Failed to write openers to output file");
+                        println!(
+                            "{} {}Openers of build order {} written to {}",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            id,
+                            output_file
+                        );
+                    } else {
+                        println!("{}", json_output);
+                    }
+                }
+                Err(e) => error!(build_id = id, error = %e, "Error fetching build order"),
+            }
+        }
+        Some(Commands::Openers { label }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(classifier) = load_classifier(cli.rules.as_deref()) else {
+                return;
+            };
+            let classified = classifier.search(&store.load_all(), label.as_deref());
+            let json_output = serde_json::to_string_pretty(&classified)
+                .expect("Failed to serialize openers to JSON");
+            if let Some(output_file) = &cli.output {
+                fs::write(output_file, json_output)
+                    .expect("Failed to write openers to output file");
+                println!(
+                    "{} {}{} labelled build orders written to {}",
+                    OUTPUT_EMOJI,
+                    style("Success : ").green(),
+                    classified.len(),
+                    output_file
+                );
             } else {
                 println!("{}", json_output);
             }
         }
-        Some(Commands::Listen { port }) => {
+        Some(Commands::Import { file, save }) => {
+            // `--format` names the format of the file, and imports are printed as JSON
+            let from = match cli
+                .format
+                .map(|format| format.to_string().parse::<ImportFormat>())
+            {
+                None => ImportFormat::Text,
+                Some(Ok(from)) => from,
+                Some(Err(_)) => {
+                    error!(format = %format, "Cannot import this format, use text or salt");
+                    return;
+                }
+            };
+            let content = match fs::read_to_string(file) {
+                Ok(content) => content,
+                Err(e) => {
+                    error!(path = file, error = %e, "Error reading import file");
+                    return;
+                }
+            };
+            match import_build(&content, from) {
+                Ok(mut build_order) => {
+                    if *save {
+                        let Some(store) = open_store(&config) else {
+                            return;
+                        };
+                        store
+                            .save_local(&mut build_order)
+                            .expect("Failed to save build order to store");
+                        println!(
+                            "{} {}Build order saved to {} as {}",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            config.storage.store,
+                            build_order.get_id()
+                        );
+                    }
+                    let output = render_build(&build_order, OutputFormat::Json);
+                    if let Some(output_file) = &cli.output {
+                        fs::write(output_file, strip_ansi_codes(&output).as_ref())
+                            .expect("Failed to write build order to output file");
+                        println!(
+                            "{} {}Imported build order written to {}",
+                            OUTPUT_EMOJI,
+                            style("Success : ").green(),
+                            output_file
+                        );
+                    } else if !*save {
+                        println!("{}", output);
+                    }
+                }
+                Err(e) => error!(path = file, error = %e, "Error importing build order"),
+            }
+        }
+        Some(Commands::Listen { .. }) => {
+            let Some(store) = open_store(&config) else {
+                return;
+            };
+            let Some(classifier) = load_classifier(cli.rules.as_deref()) else {
+                return;
+            };
+            let jobs = match JobManager::open(
+                &config.storage.jobs,
+                store.clone(),
+                config.server.job_workers,
+            ) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    error!(path = config.storage.jobs, error = %e, "Error opening jobs directory");
+                    return;
+                }
+            };
             println!(
-                "{} {}Starting HTTP server on port {}",
+                "{} {}Starting HTTP server on {}:{}",
                 SERVER_EMOJI,
                 style("Info : ").blue(),
-                port
+                config.server.bind,
+                config.server.port
             );
-            if let Err(e) = run(*port) {
-                eprintln!("Error starting server: {}", e);
+            let keys = match KeyStore::open(store.get_root().join(KEYS_FILE)) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    error!(error = %e, "Error opening API keys");
+                    return;
+                }
+            };
+            let authenticator = Authenticator::new(config.auth.clone(), keys);
+            let Some(library) = open_library(&store) else {
+                return;
+            };
+            if let Err(e) = run(
+                config.server.clone(),
+                store,
+                classifier,
+                jobs,
+                authenticator,
+                library,
+            ) {
+                error!(error = %e, "Error starting server");
+            }
+        }
+        Some(Commands::Config {
+            command: ConfigCommands::Show,
+        }) => print!("{}", config.to_toml()),
+        Some(Commands::Keys { command }) => {
+            let keys = match KeyStore::open(Path::new(&config.storage.store).join(KEYS_FILE)) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    error!(error = %e, "Error opening API keys");
+                    return;
+                }
+            };
+            match command {
+                KeysCommands::Issue {
+                    name,
+                    requests_per_minute,
+                    fetches_per_hour,
+                } => {
+                    let quota = (requests_per_minute.is_some() || fetches_per_hour.is_some())
+                        .then_some(QuotaConfig {
+                            requests_per_minute: *requests_per_minute,
+                            fetches_per_hour: *fetches_per_hour,
+                        });
+                    match keys.issue(name, quota) {
+                        Ok((_, secret)) => {
+                            println!(
+                                "{} {}API key issued to {}, it will not be shown again:",
+                                OUTPUT_EMOJI,
+                                style("Success : ").green(),
+                                name
+                            );
+                            println!("{}", secret);
+                        }
+                        Err(e) => error!(name, error = %e, "Error issuing API key"),
+                    }
+                }
+                KeysCommands::Revoke { name } => match keys.revoke(name) {
+                    Ok(_) => println!(
+                        "{} {}API key of {} revoked",
+                        OUTPUT_EMOJI,
+                        style("Success : ").green(),
+                        name
+                    ),
+                    Err(e) => error!(name, error = %e, "Error revoking API key"),
+                },
+                KeysCommands::List => match keys.list() {
+                    Ok(keys) => {
+                        let json_output = serde_json::to_string_pretty(&keys)
+                            .expect("Failed to serialize API keys to JSON");
+                        println!("{}", json_output);
+                    }
+                    Err(e) => error!(error = %e, "Error listing API keys"),
+                },
             }
         }
     }
@@ -0,0 +1,162 @@
+use crate::build_order::BuildOrderError;
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Content type of the text exposition format served at `/metrics`
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Metrics of the process, registered once on first use.
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    fetches: IntCounterVec,
+    parse_failures: IntCounterVec,
+    cache_requests: IntCounterVec,
+    cache_hit_ratio: Gauge,
+    highest_index: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("build_warren_http_requests_total", "HTTP requests served"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "build_warren_http_request_duration_seconds",
+                "Time taken to serve HTTP requests",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let fetches = IntCounterVec::new(
+            Opts::new(
+                "build_warren_upstream_fetches_total",
+                "Build pages fetched from Spawning Tool, by outcome",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let parse_failures = IntCounterVec::new(
+            Opts::new(
+                "build_warren_parse_failures_total",
+                "Build page fields that could not be parsed",
+            ),
+            &["field"],
+        )
+        .unwrap();
+        let cache_requests = IntCounterVec::new(
+            Opts::new(
+                "build_warren_page_cache_requests_total",
+                "Build pages looked up in the page cache, by result",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let cache_hit_ratio = Gauge::new(
+            "build_warren_page_cache_hit_ratio",
+            "Share of page cache lookups served from the cache",
+        )
+        .unwrap();
+        let highest_index = IntGauge::new(
+            "build_warren_highest_index",
+            "Last known highest build ID on Spawning Tool",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(fetches.clone())).unwrap();
+        registry.register(Box::new(parse_failures.clone())).unwrap();
+        registry.register(Box::new(cache_requests.clone())).unwrap();
+        registry
+            .register(Box::new(cache_hit_ratio.clone()))
+            .unwrap();
+        registry.register(Box::new(highest_index.clone())).unwrap();
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            fetches,
+            parse_failures,
+            cache_requests,
+            cache_hit_ratio,
+            highest_index,
+        }
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Label of the outcome of fetching a build page.
+pub fn fetch_outcome<T>(result: &Result<T, BuildOrderError>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(BuildOrderError::Cloaked) => "cloaked",
+        Err(BuildOrderError::NotFound) => "not_found",
+        Err(BuildOrderError::Redirected { .. }) => "redirected",
+        Err(BuildOrderError::RateLimited { .. }) => "rate_limited",
+        Err(_) => "error",
+    }
+}
+
+/// Counts a served request under the route pattern it matched.
+pub fn record_request(method: &str, route: &str, status: u16, duration: Duration) {
+    METRICS
+        .requests
+        .with_label_values(&[method, route, &status.to_string()])
+        .inc();
+    METRICS
+        .request_duration
+        .with_label_values(&[method, route])
+        .observe(duration.as_secs_f64());
+}
+
+/// Counts a build page fetched from Spawning Tool by its outcome.
+pub fn record_fetch<T>(result: &Result<T, BuildOrderError>) {
+    METRICS
+        .fetches
+        .with_label_values(&[fetch_outcome(result)])
+        .inc();
+}
+
+/// Counts a field of a build page that could not be parsed.
+pub fn record_parse_failure(field: &str) {
+    METRICS.parse_failures.with_label_values(&[field]).inc();
+}
+
+/// Counts a page cache lookup.
+pub fn record_cache_lookup(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    METRICS.cache_requests.with_label_values(&[result]).inc();
+}
+
+pub fn set_highest_index(index: u32) {
+    METRICS.highest_index.set(index as i64);
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let hits = METRICS.cache_requests.with_label_values(&["hit"]).get();
+    let misses = METRICS.cache_requests.with_label_values(&["miss"]).get();
+    if hits + misses > 0 {
+        METRICS
+            .cache_hit_ratio
+            .set(hits as f64 / (hits + misses) as f64);
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .expect("Failed to encode metrics");
+    String::from_utf8(buffer).expect("Metrics are not valid UTF-8")
+}
@@ -0,0 +1,489 @@
+use crate::build_order::{ActionType, BuildOrder, OrderEntry};
+use crate::salt::encode_salt;
+use chrono::{NaiveTime, Timelike};
+use console::{strip_ansi_codes, style};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Formats build orders can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    Ndjson,
+    Yaml,
+    Csv,
+    Markdown,
+    Text,
+    Salt,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Text => "text",
+            OutputFormat::Salt => "salt",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<OutputFormat, Self::Err> {
+        match input {
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "text" => Ok(OutputFormat::Text),
+            "salt" => Ok(OutputFormat::Salt),
+            _ => Err(format!("Unknown output format: {}", input)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Whether builds can be appended to existing output, as when resuming a crawl.
+    pub fn is_appendable(&self) -> bool {
+        *self != OutputFormat::Json
+    }
+
+    /// Extension of the files written in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Text => "txt",
+            OutputFormat::Salt => "salt",
+        }
+    }
+}
+
+/// Formats a step time as `m:ss`, or `h:mm:ss` past the first hour.
+pub fn format_timestamp(timestamp: &NaiveTime) -> String {
+    if timestamp.hour() > 0 {
+        timestamp.format("%-H:%M:%S").to_string()
+    } else {
+        timestamp.format("%-M:%S").to_string()
+    }
+}
+
+fn entry_time(entry: &OrderEntry) -> String {
+    entry
+        .get_timestamp()
+        .map(format_timestamp)
+        .unwrap_or_default()
+}
+
+fn entry_actions(entry: &OrderEntry) -> String {
+    entry
+        .get_actions()
+        .iter()
+        .map(|action| action.get_name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn matchup(build_order: &BuildOrder) -> String {
+    format!(
+        "{}v{}",
+        build_order.get_player_race(),
+        build_order.get_opponent_race()
+    )
+}
+
+const CSV_HEADER: [&str; 9] = [
+    "build_id",
+    "build_name",
+    "matchup",
+    "step",
+    "supply",
+    "time",
+    "actions",
+    "action_types",
+    "comment",
+];
+
+fn to_csv(records: &[Vec<String>]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer
+            .write_record(record)
+            .expect("Failed to write CSV row");
+    }
+    String::from_utf8(writer.into_inner().expect("Failed to flush CSV"))
+        .expect("CSV output is not valid UTF-8")
+}
+
+fn csv_header() -> Vec<String> {
+    CSV_HEADER.iter().map(|column| column.to_string()).collect()
+}
+
+/// Writes one CSV row per step, with the build ID and name on every row.
+fn render_csv(build_order: &BuildOrder, with_header: bool) -> String {
+    let mut records = Vec::new();
+    if with_header {
+        records.push(csv_header());
+    }
+    for (step, entry) in build_order.get_entries().iter().enumerate() {
+        let action_types = entry
+            .get_actions()
+            .iter()
+            .map(|action| action.get_action_type().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        records.push(vec![
+            build_order.get_id().to_string(),
+            build_order.get_name().to_string(),
+            matchup(build_order),
+            (step + 1).to_string(),
+            entry.get_supply().to_string(),
+            entry_time(entry),
+            entry_actions(entry),
+            action_types,
+            entry.get_comment().unwrap_or("").to_string(),
+        ]);
+    }
+    to_csv(&records)
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn render_markdown(build_order: &BuildOrder) -> String {
+    let mut details = vec![
+        format!("**{}**", matchup(build_order)),
+        build_order.get_build_type().to_string(),
+    ];
+    if !build_order.get_creator().is_empty() {
+        details.push(format!("by {}", build_order.get_creator()));
+    }
+    if !build_order.get_patch().is_empty() {
+        details.push(format!("patch {}", build_order.get_patch()));
+    }
+    if let Some(difficulty) = build_order.get_difficulty() {
+        details.push(difficulty.to_string());
+    }
+    if let Some(votes) = build_order.get_votes() {
+        details.push(votes.to_string());
+    }
+
+    let mut markdown = format!(
+        "## {} (#{})\n\n{}\n\n",
+        escape_markdown(build_order.get_name()),
+        build_order.get_id(),
+        details.join(" · ")
+    );
+    if let Some(description) = build_order.get_description() {
+        markdown.push_str(&format!("{}\n\n", escape_markdown(description)));
+    }
+    if let Some(vod) = build_order.get_vod() {
+        markdown.push_str(&format!("[VOD]({})\n\n", vod));
+    }
+    markdown.push_str("| Supply | Time | Actions | Comment |\n| --- | --- | --- | --- |\n");
+    for entry in build_order.get_entries() {
+        markdown.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            entry.get_supply(),
+            entry_time(entry),
+            escape_markdown(&entry_actions(entry)),
+            escape_markdown(entry.get_comment().unwrap_or(""))
+        ));
+    }
+    markdown
+}
+
+/// Renders a build in the text notation understood by the text importer,
+/// with actions coloured by type when the terminal supports it.
+fn render_text(build_order: &BuildOrder) -> String {
+    let mut text = format!("# Build {}\n", build_order.get_id());
+    let mut header = |key: &str, value: &str| {
+        if !value.is_empty() {
+            text.push_str(&format!(
+                "{} {}\n",
+                style(format!("{}:", key)).bold(),
+                value.replace('\n', " ")
+            ));
+        }
+    };
+    header("Title", build_order.get_name());
+    header("Matchup", &matchup(build_order));
+    header("Type", &build_order.get_build_type().to_string());
+    header("Author", build_order.get_creator());
+    header("Patch", build_order.get_patch());
+    header(
+        "Difficulty",
+        &build_order
+            .get_difficulty()
+            .map(|d| d.to_string())
+            .unwrap_or_default(),
+    );
+    header(
+        "Published",
+        &build_order
+            .get_published()
+            .map(|d| d.to_string())
+            .unwrap_or_default(),
+    );
+    header(
+        "Votes",
+        &build_order
+            .get_votes()
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+    );
+    header("VOD", build_order.get_vod().unwrap_or(""));
+    header("Description", build_order.get_description().unwrap_or(""));
+    text.push('\n');
+
+    for entry in build_order.get_entries() {
+        let actions = entry
+            .get_actions()
+            .iter()
+            .map(|action| {
+                let name = style(action.get_name());
+                match action.get_action_type() {
+                    ActionType::Worker => name.dim(),
+                    ActionType::Unit => name.green(),
+                    ActionType::Building => name.yellow(),
+                    ActionType::Upgrade => name.magenta(),
+                    ActionType::Action => name.cyan(),
+                }
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let time = entry_time(entry);
+        text.push_str(&format!(
+            "{:>3} {}{}",
+            entry.get_supply(),
+            if time.is_empty() {
+                String::new()
+            } else {
+                format!("{:>5} ", style(time).dim())
+            },
+            actions
+        ));
+        if let Some(comment) = entry.get_comment() {
+            text.push_str(&format!(" {}", style(format!("# {}", comment)).italic()));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Renders a single build order in the given format.
+pub fn render_build(build_order: &BuildOrder, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(build_order)
+            .expect("Failed to serialize build order to JSON"),
+        OutputFormat::Yaml => {
+            serde_yaml::to_string(build_order).expect("Failed to serialize build order to YAML")
+        }
+        _ => render_builds(std::slice::from_ref(build_order), format),
+    }
+}
+
+/// Renders a list of build orders in the given format.
+pub fn render_builds(build_orders: &[BuildOrder], format: OutputFormat) -> String {
+    let mut writer = BuildWriter::new(Vec::new(), format);
+    for build_order in build_orders {
+        writer
+            .write(build_order)
+            .expect("Failed to render build order");
+    }
+    let output = writer.finish().expect("Failed to render build orders");
+    String::from_utf8(output).expect("Rendered build orders are not valid UTF-8")
+}
+
+/// Prefixes the first line of `text` with `first` and the other lines with `rest`.
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| format!("{}{}\n", if i == 0 { first } else { rest }, line))
+        .collect()
+}
+
+/// Writes build orders one at a time, so that a collection never has to be
+/// held in memory before it is written out.
+pub struct BuildWriter<W: Write> {
+    writer: W,
+    format: OutputFormat,
+    colours: bool,
+    started: bool,
+    count: usize,
+}
+
+impl<W: Write> BuildWriter<W> {
+    pub fn new(writer: W, format: OutputFormat) -> Self {
+        BuildWriter {
+            writer,
+            format,
+            colours: true,
+            started: false,
+            count: 0,
+        }
+    }
+
+    /// Continues output that already holds build orders, leaving out the
+    /// header a new output would start with.
+    pub fn appending(writer: W, format: OutputFormat) -> Self {
+        BuildWriter {
+            started: true,
+            ..BuildWriter::new(writer, format)
+        }
+    }
+
+    /// Leaves terminal colours out of the text format.
+    pub fn without_colours(mut self) -> Self {
+        self.colours = false;
+        self
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    /// Writes a build order and flushes it to the underlying writer.
+    pub fn write(&mut self, build_order: &BuildOrder) -> io::Result<()> {
+        let first = !self.started;
+        let output = match self.format {
+            // Indented like a pretty-printed array, for the same output as `render_build`
+            OutputFormat::Json => indent(
+                &serde_json::to_string_pretty(build_order)?,
+                if first { "[\n  " } else { ",\n  " },
+                "  ",
+            )
+            .trim_end()
+            .to_string(),
+            OutputFormat::Ndjson => serde_json::to_string(build_order)? + "\n",
+            OutputFormat::Yaml => indent(
+                &serde_yaml::to_string(build_order).map_err(io::Error::other)?,
+                "- ",
+                "  ",
+            ),
+            OutputFormat::Csv => render_csv(build_order, first),
+            OutputFormat::Markdown => {
+                let separator = if first { "" } else { "\n" };
+                format!("{}{}", separator, render_markdown(build_order))
+            }
+            OutputFormat::Text => {
+                let separator = if first { "" } else { "\n" };
+                let text = render_text(build_order);
+                if self.colours {
+                    format!("{}{}", separator, text)
+                } else {
+                    format!("{}{}", separator, strip_ansi_codes(&text))
+                }
+            }
+            OutputFormat::Salt => encode_salt(build_order) + "\n",
+        };
+        self.writer.write_all(output.as_bytes())?;
+        self.writer.flush()?;
+        self.started = true;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Closes the collection and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let footer = match (self.format, self.started) {
+            (OutputFormat::Json, false) => "[]\n".to_string(),
+            (OutputFormat::Json, true) => "\n]\n".to_string(),
+            (OutputFormat::Yaml, false) => "[]\n".to_string(),
+            (OutputFormat::Csv, false) => to_csv(&[csv_header()]),
+            _ => String::new(),
+        };
+        self.writer.write_all(footer.as_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// A file written under a `.part` name and renamed into place by
+/// [`PartFile::commit`], so an interrupted run never leaves a truncated file
+/// under the final name.
+pub struct PartFile {
+    file: BufWriter<File>,
+    part_path: PathBuf,
+    path: PathBuf,
+}
+
+impl PartFile {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let part_path = PartFile::part_path(&path);
+        Ok(PartFile {
+            file: BufWriter::new(File::create(&part_path)?),
+            part_path,
+            path,
+        })
+    }
+
+    /// Reopens the part file of an interrupted run, or the file of a completed
+    /// one, to add to it. Creates the part file if neither exists.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let part_path = PartFile::part_path(&path);
+        if !part_path.exists() && path.exists() {
+            fs::rename(&path, &part_path)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&part_path)?;
+        Ok(PartFile {
+            file: BufWriter::new(file),
+            part_path,
+            path,
+        })
+    }
+
+    fn part_path(path: &Path) -> PathBuf {
+        let mut part_path = path.to_path_buf().into_os_string();
+        part_path.push(".part");
+        PathBuf::from(part_path)
+    }
+
+    /// Whether the file already holds output.
+    pub fn has_content(&self) -> io::Result<bool> {
+        Ok(self.file.get_ref().metadata()?.len() > 0)
+    }
+
+    pub fn get_part_path(&self) -> &Path {
+        &self.part_path
+    }
+
+    /// Syncs the file to disk and moves it to its final name.
+    pub fn commit(self) -> io::Result<()> {
+        let file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.part_path, &self.path)
+    }
+}
+
+impl Write for PartFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
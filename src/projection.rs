@@ -0,0 +1,102 @@
+use crate::build_order::BuildOrder;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+/// Top-level fields of a build order to keep, as listed in a `fields=` query
+/// such as `id,name,player_race,votes`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FieldSet {
+    fields: Vec<String>,
+}
+
+/// Names of the fields of a serialized build order.
+pub fn build_fields() -> Vec<String> {
+    match serde_json::to_value(BuildOrder::new()) {
+        Ok(Value::Object(object)) => object.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl FieldSet {
+    pub fn get_fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Keeps only the fields of the set in a serialized build order.
+    pub fn project_value(&self, value: Value) -> Value {
+        match value {
+            Value::Object(mut object) => {
+                let projected: Map<String, Value> = self
+                    .fields
+                    .iter()
+                    .filter_map(|field| Some((field.clone(), object.remove(field)?)))
+                    .collect();
+                Value::Object(projected)
+            }
+            value => value,
+        }
+    }
+}
+
+/// A build order as JSON, with only the fields of `fields` when given.
+pub fn project(build_order: &BuildOrder, fields: Option<&FieldSet>) -> Value {
+    let value = serde_json::to_value(build_order).expect("Failed to serialize build order");
+    match fields {
+        Some(fields) => fields.project_value(value),
+        None => value,
+    }
+}
+
+impl FromStr for FieldSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let known = build_fields();
+        let mut fields: Vec<String> = Vec::new();
+        for field in s
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+        {
+            if !known.iter().any(|known| known == field) {
+                return Err(format!(
+                    "Unknown field: {} (expected one of {})",
+                    field,
+                    known.join(", ")
+                ));
+            }
+            if !fields.iter().any(|kept| kept == field) {
+                fields.push(field.to_string());
+            }
+        }
+        if fields.is_empty() {
+            return Err("No fields given".to_string());
+        }
+        Ok(FieldSet { fields })
+    }
+}
+
+impl TryFrom<String> for FieldSet {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for FieldSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fields.join(","))
+    }
+}
+
+/// Build orders as JSON, with only the fields of `fields` when given.
+pub fn project_all(build_orders: &[BuildOrder], fields: Option<&FieldSet>) -> Vec<Value> {
+    build_orders
+        .iter()
+        .map(|build_order| project(build_order, fields))
+        .collect()
+}
@@ -0,0 +1,618 @@
+use crate::build_order::{BuildOrder, BuildType, Difficulty, Race};
+use crate::output::format_timestamp;
+use crate::search::{parse_build_type, parse_race};
+use crate::stats::matchup;
+use chrono::{NaiveDate, NaiveTime};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Keys of the predicates a query can hold
+pub const KEYS: [&str; 13] = [
+    "race",
+    "vs",
+    "matchup",
+    "type",
+    "difficulty",
+    "creator",
+    "name",
+    "votes",
+    "voters",
+    "patch",
+    "published",
+    "id",
+    "has",
+];
+/// Deepest nesting of parentheses and negations a query can hold
+pub const MAX_DEPTH: usize = 32;
+/// Longest query accepted, in bytes
+pub const MAX_QUERY_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    /// The query ended where more was expected
+    UnexpectedEnd(String),
+    /// A character out of place, with its position in the query
+    Unexpected {
+        position: usize,
+        found: char,
+    },
+    UnknownKey(String),
+    /// An operator the key cannot be compared with, such as `creator>x`
+    InvalidOperator {
+        key: String,
+        operator: Comparison,
+    },
+    InvalidValue {
+        key: String,
+        value: String,
+    },
+    /// Parentheses and negations nested deeper than `MAX_DEPTH`
+    TooDeep,
+    /// The query is longer than `MAX_QUERY_LENGTH` bytes
+    TooLong(usize),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::UnexpectedEnd(expected) => {
+                write!(f, "Unexpected end of query, expected {}", expected)
+            }
+            QueryError::Unexpected { position, found } => {
+                write!(f, "Unexpected '{}' at position {}", found, position)
+            }
+            QueryError::UnknownKey(key) => write!(
+                f,
+                "Unknown key: {} (expected one of {})",
+                key,
+                KEYS.join(", ")
+            ),
+            QueryError::InvalidOperator { key, operator } => {
+                write!(f, "{} cannot be compared with {}", key, operator)
+            }
+            QueryError::InvalidValue { key, value } => {
+                write!(f, "Invalid value for {}: {}", key, value)
+            }
+            QueryError::TooDeep => write!(
+                f,
+                "Query is nested deeper than {} parentheses or negations",
+                MAX_DEPTH
+            ),
+            QueryError::TooLong(length) => write!(
+                f,
+                "Query is {} bytes long, at most {} are accepted",
+                length, MAX_QUERY_LENGTH
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering == Ordering::Equal,
+            Comparison::Lt => ordering == Ordering::Less,
+            Comparison::Le => ordering != Ordering::Greater,
+            Comparison::Gt => ordering == Ordering::Greater,
+            Comparison::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            Comparison::Eq => ":",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// A condition on a single property of a build order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Race(Race),
+    Opponent(Race),
+    /// Matchup such as `ZvP`, compared ignoring case
+    Matchup(String),
+    Type(BuildType),
+    Difficulty(Difficulty),
+    /// Creator name, compared ignoring case
+    Creator(String),
+    /// Text the build name contains, ignoring case
+    Name(String),
+    /// Vote score, in percent
+    Votes(Comparison, u32),
+    /// Number of votes
+    Voters(Comparison, u32),
+    /// Game version, compared on as many numbers as given, so that `5.0`
+    /// stands for every `5.0.x`
+    Patch(Comparison, Vec<u32>),
+    Published(Comparison, NaiveDate),
+    Id(Comparison, u32),
+    /// A step with the action, at a time satisfying the comparison if given
+    Has {
+        action: String,
+        time: Option<(Comparison, NaiveTime)>,
+    },
+}
+
+/// A parsed query, such as `race:Z vs:P (type:timing OR votes>=60) -creator:x`.
+/// Terms next to each other must all hold, `OR` takes either side and `-`
+/// negates a term.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Predicate(Predicate),
+}
+
+/// Version numbers of a patch such as `5.0.14`, or `None` if it is not one.
+fn parse_version(value: &str) -> Option<Vec<u32>> {
+    value.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// A step time such as `3:00` or `1:02:30`.
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    let parts: Vec<u32> = value
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [minutes, seconds] => NaiveTime::from_hms_opt(minutes / 60, minutes % 60, seconds),
+        [hours, minutes, seconds] => NaiveTime::from_hms_opt(hours, minutes, seconds),
+        _ => None,
+    }
+}
+
+impl Predicate {
+    pub fn matches(&self, build_order: &BuildOrder) -> bool {
+        let compare =
+            |comparison: &Comparison, value: u32, wanted: &u32| comparison.holds(value.cmp(wanted));
+        match self {
+            Predicate::Race(race) => build_order.get_player_race() == race,
+            Predicate::Opponent(race) => build_order.get_opponent_race() == race,
+            Predicate::Matchup(name) => matchup(build_order).eq_ignore_ascii_case(name),
+            Predicate::Type(build_type) => build_order.get_build_type() == build_type,
+            Predicate::Difficulty(difficulty) => build_order.get_difficulty() == Some(difficulty),
+            Predicate::Creator(name) => build_order.get_creator().eq_ignore_ascii_case(name),
+            Predicate::Name(text) => build_order
+                .get_name()
+                .to_lowercase()
+                .contains(&text.to_lowercase()),
+            Predicate::Votes(comparison, score) => build_order
+                .get_votes()
+                .is_some_and(|votes| compare(comparison, votes.get_score(), score)),
+            Predicate::Voters(comparison, count) => build_order
+                .get_votes()
+                .is_some_and(|votes| compare(comparison, votes.get_count(), count)),
+            Predicate::Patch(comparison, version) => parse_version(build_order.get_patch())
+                .is_some_and(|patch| {
+                    comparison.holds(patch.iter().take(version.len()).cmp(version.iter()))
+                }),
+            Predicate::Published(comparison, date) => build_order
+                .get_published()
+                .is_some_and(|published| comparison.holds(published.cmp(date))),
+            Predicate::Id(comparison, id) => compare(comparison, build_order.get_id(), id),
+            Predicate::Has { action, time } => build_order.get_entries().iter().any(|entry| {
+                entry
+                    .get_actions()
+                    .iter()
+                    .any(|candidate| candidate.get_name().trim().eq_ignore_ascii_case(action))
+                    && time.is_none_or(|(comparison, time)| {
+                        entry
+                            .get_timestamp()
+                            .is_some_and(|timestamp| comparison.holds(timestamp.cmp(&time)))
+                    })
+            }),
+        }
+    }
+}
+
+impl Query {
+    /// Parses a query. An empty query matches every build order.
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        if input.len() > MAX_QUERY_LENGTH {
+            return Err(QueryError::TooLong(input.len()));
+        }
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let query = parser.parse_or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            Some(found) => Err(QueryError::Unexpected {
+                position: parser.position,
+                found,
+            }),
+            None => Ok(query),
+        }
+    }
+
+    pub fn matches(&self, build_order: &BuildOrder) -> bool {
+        match self {
+            Query::And(queries) => queries.iter().all(|query| query.matches(build_order)),
+            Query::Or(queries) => queries.iter().any(|query| query.matches(build_order)),
+            Query::Not(query) => !query.matches(build_order),
+            Query::Predicate(predicate) => predicate.matches(build_order),
+        }
+    }
+
+    /// The build orders matching the query.
+    pub fn filter<'a>(&self, build_orders: &'a [BuildOrder]) -> Vec<&'a BuildOrder> {
+        build_orders
+            .iter()
+            .filter(|build_order| self.matches(build_order))
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// Parentheses and negations around the current term
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// Consumes `keyword` when it is the next whole word.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let end = self.position + keyword.len();
+        let matches = self.chars.len() >= end
+            && self.chars[self.position..end]
+                .iter()
+                .copied()
+                .eq(keyword.chars())
+            && self
+                .chars
+                .get(end)
+                .is_none_or(|c| c.is_whitespace() || *c == '(');
+        if matches {
+            self.position = end;
+        }
+        matches
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.parse_and()?];
+        loop {
+            self.skip_whitespace();
+            if !self.keyword("OR") {
+                break;
+            }
+            queries.push(self.parse_and()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::Or(queries)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut queries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.position;
+            if self.peek().is_none_or(|c| c == ')') || self.keyword("OR") {
+                self.position = start;
+                break;
+            }
+            self.keyword("AND");
+            queries.push(self.parse_unary()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::And(queries)
+        })
+    }
+
+    /// Parses a term, bounding the nesting so that deep queries cannot
+    /// overflow the stack.
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(QueryError::TooDeep);
+        }
+        self.depth += 1;
+        let query = self.parse_nested();
+        self.depth -= 1;
+        query
+    }
+
+    fn parse_nested(&mut self) -> Result<Query, QueryError> {
+        self.skip_whitespace();
+        if self.peek() == Some('-') || self.keyword("NOT") {
+            if self.peek() == Some('-') {
+                self.position += 1;
+            }
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        match self.peek() {
+            None => Err(QueryError::UnexpectedEnd("a term".to_string())),
+            Some('(') => {
+                self.position += 1;
+                let query = self.parse_or()?;
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(')') => {
+                        self.position += 1;
+                        Ok(query)
+                    }
+                    Some(found) => Err(QueryError::Unexpected {
+                        position: self.position,
+                        found,
+                    }),
+                    None => Err(QueryError::UnexpectedEnd("')'".to_string())),
+                }
+            }
+            Some(_) => self.parse_term().map(Query::Predicate),
+        }
+    }
+
+    fn parse_operator(&mut self) -> Option<Comparison> {
+        let (comparison, length) = match (self.peek()?, self.chars.get(self.position + 1)) {
+            ('<', Some('=')) => (Comparison::Le, 2),
+            ('>', Some('=')) => (Comparison::Ge, 2),
+            (':' | '=', _) => (Comparison::Eq, 1),
+            ('<', _) => (Comparison::Lt, 1),
+            ('>', _) => (Comparison::Gt, 1),
+            _ => return None,
+        };
+        self.position += length;
+        Some(comparison)
+    }
+
+    /// A quoted value, or the characters up to a space, a parenthesis or one
+    /// of `stops`.
+    fn parse_value(&mut self, stops: &[char]) -> Result<String, QueryError> {
+        if self.peek() == Some('"') {
+            self.position += 1;
+            let start = self.position;
+            while self.peek().is_some_and(|c| c != '"') {
+                self.position += 1;
+            }
+            if self.peek().is_none() {
+                return Err(QueryError::UnexpectedEnd("'\"'".to_string()));
+            }
+            self.position += 1;
+            return Ok(self.chars[start..self.position - 1].iter().collect());
+        }
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !"()".contains(c) && !stops.contains(&c))
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(match self.peek() {
+                Some(found) => QueryError::Unexpected {
+                    position: self.position,
+                    found,
+                },
+                None => QueryError::UnexpectedEnd("a value".to_string()),
+            });
+        }
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn parse_term(&mut self) -> Result<Predicate, QueryError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.position += 1;
+        }
+        let key: String = self.chars[start..self.position].iter().collect();
+        let operator = if key.is_empty() {
+            None
+        } else {
+            self.parse_operator()
+        };
+        let Some(operator) = operator else {
+            // A bare word or quoted text, looked for in the build names
+            self.position = start;
+            return Ok(Predicate::Name(self.parse_value(&[])?));
+        };
+        let key = key.to_lowercase();
+        let stops: &[char] = if key == "has" { &['<', '>', '@'] } else { &[] };
+        let value = self.parse_value(stops)?;
+        let invalid = || QueryError::InvalidValue {
+            key: key.clone(),
+            value: value.clone(),
+        };
+        let equality = |predicate: Predicate| match operator {
+            Comparison::Eq => Ok(predicate),
+            operator => Err(QueryError::InvalidOperator {
+                key: key.clone(),
+                operator,
+            }),
+        };
+        match key.as_str() {
+            "race" => equality(Predicate::Race(parse_race(&value).map_err(|_| invalid())?)),
+            "vs" => equality(Predicate::Opponent(
+                parse_race(&value).map_err(|_| invalid())?,
+            )),
+            "matchup" => equality(Predicate::Matchup(value.clone())),
+            "type" => equality(Predicate::Type(
+                parse_build_type(&value).map_err(|_| invalid())?,
+            )),
+            "difficulty" => {
+                let mut name = value.to_lowercase();
+                if let Some(first) = name.get_mut(..1) {
+                    first.make_ascii_uppercase();
+                }
+                equality(Predicate::Difficulty(
+                    Difficulty::from_str(&name).map_err(|_| invalid())?,
+                ))
+            }
+            "creator" => equality(Predicate::Creator(value.clone())),
+            "name" => equality(Predicate::Name(value.clone())),
+            "votes" => Ok(Predicate::Votes(
+                operator,
+                value.trim_end_matches('%').parse().map_err(|_| invalid())?,
+            )),
+            "voters" => Ok(Predicate::Voters(
+                operator,
+                value.parse().map_err(|_| invalid())?,
+            )),
+            "patch" => Ok(Predicate::Patch(
+                operator,
+                parse_version(&value).ok_or_else(invalid)?,
+            )),
+            "published" => Ok(Predicate::Published(
+                operator,
+                NaiveDate::parse_from_str(&value, "%Y-%m-%d").map_err(|_| invalid())?,
+            )),
+            "id" => Ok(Predicate::Id(
+                operator,
+                value.parse().map_err(|_| invalid())?,
+            )),
+            "has" => {
+                equality(Predicate::Has {
+                    action: value.clone(),
+                    time: None,
+                })?;
+                // `@3:00` asks for an exact time, `<3:00` or `@<3:00` compare it
+                let at = self.peek() == Some('@');
+                if at {
+                    self.position += 1;
+                }
+                let comparison = match self.parse_operator() {
+                    Some(comparison) => Some(comparison),
+                    None => at.then_some(Comparison::Eq),
+                };
+                let time = match comparison {
+                    Some(comparison) => {
+                        let time = self.parse_value(&[])?;
+                        Some((
+                            comparison,
+                            parse_time(&time).ok_or(QueryError::InvalidValue {
+                                key: key.clone(),
+                                value: time,
+                            })?,
+                        ))
+                    }
+                    None => None,
+                };
+                Ok(Predicate::Has {
+                    action: value,
+                    time,
+                })
+            }
+            _ => Err(QueryError::UnknownKey(key)),
+        }
+    }
+}
+
+/// A value as written in a query, quoted when it holds spaces or operators.
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || "()\"<>=:@".contains(c)) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = |version: &[u32]| {
+            version
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+        match self {
+            Predicate::Race(race) => write!(f, "race:{}", race),
+            Predicate::Opponent(race) => write!(f, "vs:{}", race),
+            Predicate::Matchup(name) => write!(f, "matchup:{}", quote(name)),
+            Predicate::Type(build_type) => write!(f, "type:{}", quote(&build_type.to_string())),
+            Predicate::Difficulty(difficulty) => write!(f, "difficulty:{}", difficulty),
+            Predicate::Creator(name) => write!(f, "creator:{}", quote(name)),
+            Predicate::Name(text) => write!(f, "{}", quote(text)),
+            Predicate::Votes(comparison, score) => write!(f, "votes{}{}", comparison, score),
+            Predicate::Voters(comparison, count) => write!(f, "voters{}{}", comparison, count),
+            Predicate::Patch(comparison, patch) => {
+                write!(f, "patch{}{}", comparison, version(patch))
+            }
+            Predicate::Published(comparison, date) => {
+                write!(f, "published{}{}", comparison, date.format("%Y-%m-%d"))
+            }
+            Predicate::Id(comparison, id) => write!(f, "id{}{}", comparison, id),
+            Predicate::Has { action, time } => {
+                write!(f, "has:{}", quote(action))?;
+                match time {
+                    Some((Comparison::Eq, time)) => write!(f, "@{}", format_timestamp(time)),
+                    Some((comparison, time)) => {
+                        write!(f, "{}{}", comparison, format_timestamp(time))
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grouped = |query: &Query| match query {
+            Query::And(queries) | Query::Or(queries) if queries.len() > 1 => {
+                format!("({})", query)
+            }
+            _ => query.to_string(),
+        };
+        match self {
+            Query::And(queries) => {
+                let terms: Vec<String> = queries
+                    .iter()
+                    .map(|query| match query {
+                        Query::Or(_) => grouped(query),
+                        _ => query.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", terms.join(" "))
+            }
+            Query::Or(queries) => {
+                let terms: Vec<String> = queries.iter().map(ToString::to_string).collect();
+                write!(f, "{}", terms.join(" OR "))
+            }
+            Query::Not(query) => write!(f, "-{}", grouped(query)),
+            Query::Predicate(predicate) => write!(f, "{}", predicate),
+        }
+    }
+}
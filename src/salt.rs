@@ -0,0 +1,366 @@
+use crate::action_catalog::{infer_race, lookup, strip_multiplier};
+use crate::build_order::{Action, BuildOrder, BuildOrderError, OrderEntry};
+use chrono::Timelike;
+
+/// Characters of the SALT alphabet; a character encodes its position in this string
+const SALT_CHARACTERS: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+/// Version of the SALT encoding written by the encoder
+pub const SALT_VERSION: u8 = 1;
+/// Largest value a single SALT character can hold
+pub const SALT_MAX_VALUE: u8 = 94;
+
+/// SALT item types, in the order of their type codes
+const SALT_TYPES: [&[&str]; 4] = [STRUCTURES, UNITS, MORPHS, UPGRADES];
+
+// Item codes are positions in these lists, which must only ever be appended to.
+const STRUCTURES: &[&str] = &[
+    "Armory",
+    "Barracks",
+    "Bunker",
+    "Command Center",
+    "Engineering Bay",
+    "Factory",
+    "Fusion Core",
+    "Ghost Academy",
+    "Missile Turret",
+    "Reactor",
+    "Refinery",
+    "Sensor Tower",
+    "Starport",
+    "Supply Depot",
+    "Tech Lab",
+    "Assimilator",
+    "Cybernetics Core",
+    "Dark Shrine",
+    "Fleet Beacon",
+    "Forge",
+    "Gateway",
+    "Nexus",
+    "Photon Cannon",
+    "Pylon",
+    "Robotics Bay",
+    "Robotics Facility",
+    "Stargate",
+    "Templar Archives",
+    "Twilight Council",
+    "Shield Battery",
+    "Baneling Nest",
+    "Evolution Chamber",
+    "Extractor",
+    "Hatchery",
+    "Hydralisk Den",
+    "Infestation Pit",
+    "Nydus Network",
+    "Roach Warren",
+    "Spawning Pool",
+    "Spine Crawler",
+    "Spire",
+    "Spore Crawler",
+    "Ultralisk Cavern",
+    "Lurker Den",
+    "Barracks Reactor",
+    "Barracks Tech Lab",
+    "Factory Reactor",
+    "Factory Tech Lab",
+    "Starport Reactor",
+    "Starport Tech Lab",
+];
+
+const UNITS: &[&str] = &[
+    "Banshee",
+    "Battlecruiser",
+    "Ghost",
+    "Hellion",
+    "Marauder",
+    "Marine",
+    "Medivac",
+    "Raven",
+    "Reaper",
+    "SCV",
+    "Siege Tank",
+    "Thor",
+    "Viking",
+    "Widow Mine",
+    "Hellbat",
+    "Cyclone",
+    "Liberator",
+    "Carrier",
+    "Colossus",
+    "Dark Templar",
+    "High Templar",
+    "Immortal",
+    "Mothership",
+    "Observer",
+    "Phoenix",
+    "Probe",
+    "Sentry",
+    "Stalker",
+    "Tempest",
+    "Void Ray",
+    "Warp Prism",
+    "Zealot",
+    "Oracle",
+    "Adept",
+    "Disruptor",
+    "Corruptor",
+    "Drone",
+    "Hydralisk",
+    "Mutalisk",
+    "Overlord",
+    "Queen",
+    "Roach",
+    "Ultralisk",
+    "Zergling",
+    "Infestor",
+    "Swarm Host",
+    "Viper",
+];
+
+const MORPHS: &[&str] = &[
+    "Orbital Command",
+    "Planetary Fortress",
+    "Warp Gate",
+    "Archon",
+    "Lair",
+    "Hive",
+    "Greater Spire",
+    "Baneling",
+    "Brood Lord",
+    "Overseer",
+    "Ravager",
+    "Lurker",
+];
+
+const UPGRADES: &[&str] = &[
+    "Stimpack",
+    "Combat Shield",
+    "Concussive Shells",
+    "Infernal Pre-Igniter",
+    "Hurricane Engines",
+    "Drilling Claws",
+    "Smart Servos",
+    "Cloaking Field",
+    "Hyperflight Rotors",
+    "Caduceus Reactor",
+    "Hi-Sec Auto Tracking",
+    "Neosteel Armor",
+    "Terran Infantry Weapons Level 1",
+    "Terran Infantry Weapons Level 2",
+    "Terran Infantry Weapons Level 3",
+    "Terran Infantry Armor Level 1",
+    "Terran Infantry Armor Level 2",
+    "Terran Infantry Armor Level 3",
+    "Terran Vehicle Weapons Level 1",
+    "Terran Vehicle and Ship Plating Level 1",
+    "Terran Ship Weapons Level 1",
+    "Warp Gate Research",
+    "Blink",
+    "Charge",
+    "Resonating Glaives",
+    "Psionic Storm",
+    "Shadow Stride",
+    "Extended Thermal Lance",
+    "Gravitic Boosters",
+    "Gravitic Drive",
+    "Anion Pulse-Crystals",
+    "Protoss Ground Weapons Level 1",
+    "Protoss Ground Weapons Level 2",
+    "Protoss Ground Weapons Level 3",
+    "Protoss Ground Armor Level 1",
+    "Protoss Ground Armor Level 2",
+    "Protoss Ground Armor Level 3",
+    "Protoss Shields Level 1",
+    "Protoss Air Weapons Level 1",
+    "Protoss Air Armor Level 1",
+    "Metabolic Boost",
+    "Adrenal Glands",
+    "Glial Reconstitution",
+    "Tunneling Claws",
+    "Centrifugal Hooks",
+    "Burrow",
+    "Pneumatized Carapace",
+    "Grooved Spines",
+    "Muscular Augments",
+    "Chitinous Plating",
+    "Pathogen Glands",
+    "Zerg Missile Weapons Level 1",
+    "Zerg Missile Weapons Level 2",
+    "Zerg Missile Weapons Level 3",
+    "Zerg Melee Weapons Level 1",
+    "Zerg Melee Weapons Level 2",
+    "Zerg Melee Weapons Level 3",
+    "Zerg Ground Carapace Level 1",
+    "Zerg Ground Carapace Level 2",
+    "Zerg Ground Carapace Level 3",
+    "Zerg Flyer Attacks Level 1",
+    "Zerg Flyer Carapace Level 1",
+];
+
+fn encode_char(value: u8) -> char {
+    SALT_CHARACTERS.as_bytes()[value as usize] as char
+}
+
+fn decode_char(c: char) -> Option<u8> {
+    SALT_CHARACTERS.find(c).map(|i| i as u8)
+}
+
+/// SALT type and item codes of an action name, if SALT can represent it.
+pub fn salt_code(name: &str) -> Option<(u8, u8)> {
+    let name = strip_multiplier(name);
+    SALT_TYPES
+        .iter()
+        .enumerate()
+        .find_map(|(item_type, items)| {
+            items
+                .iter()
+                .position(|item| item.eq_ignore_ascii_case(name))
+                .map(|item| (item_type as u8, item as u8))
+        })
+}
+
+/// Formats a SALT time for `OrderEntry::new`, where 0:00 stands for an untimed step.
+fn format_time(minutes: u8, seconds: u8) -> String {
+    match (minutes, seconds) {
+        (0, 0) => String::new(),
+        (m, s) if m >= 60 => format!("{}:{:02}:{:02}", m / 60, m % 60, s),
+        (m, s) => format!("{}:{:02}", m, s),
+    }
+}
+
+/// Supply, minutes and seconds of an entry, if they fit in SALT characters.
+fn salt_step_fields(entry: &OrderEntry) -> Option<(u8, u8, u8)> {
+    let (minutes, seconds) = match entry.get_timestamp() {
+        Some(timestamp) => (
+            timestamp.hour() * 60 + timestamp.minute(),
+            timestamp.second(),
+        ),
+        None => (0, 0),
+    };
+    if entry.get_supply() > SALT_MAX_VALUE || minutes > SALT_MAX_VALUE as u32 {
+        return None;
+    }
+    Some((entry.get_supply(), minutes as u8, seconds as u8))
+}
+
+/// The part of a build order SALT can represent: one action per step, catalog
+/// names, supply and minutes up to [`SALT_MAX_VALUE`], no comments.
+pub fn salt_compatible(build_order: &BuildOrder) -> BuildOrder {
+    let mut compatible = build_order.clone();
+    compatible.clear_steps();
+    for entry in build_order.get_entries() {
+        let Some((supply, minutes, seconds)) = salt_step_fields(entry) else {
+            continue;
+        };
+        for action in entry.get_actions() {
+            let Some((item_type, item)) = salt_code(action.get_name()) else {
+                continue;
+            };
+            compatible.add_step(OrderEntry::new(
+                supply,
+                format_time(minutes, seconds),
+                vec![catalog_action(
+                    SALT_TYPES[item_type as usize][item as usize],
+                )],
+                String::new(),
+            ));
+        }
+    }
+    compatible
+}
+
+fn catalog_action(name: &str) -> Action {
+    let entry = lookup(name).expect("SALT items must be in the action catalog");
+    Action::new(entry.action_type.clone(), entry.name.to_string())
+}
+
+fn clean_text(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c != '|' && *c != '~' && *c != '\n' && *c != '\r')
+        .collect()
+}
+
+/// Encodes a build order as a SALT string.
+///
+/// The string is made of `$`, the version character, the title, author and
+/// description separated by `|`, then `~` followed by five characters per step:
+/// supply, minutes, seconds, item type and item code. Actions SALT has no code
+/// for, steps beyond its value range and comments are left out.
+pub fn encode_salt(build_order: &BuildOrder) -> String {
+    let mut salt = format!(
+        "${}{}|{}|{}~",
+        encode_char(SALT_VERSION),
+        clean_text(build_order.get_name()),
+        clean_text(build_order.get_creator()),
+        clean_text(build_order.get_description().unwrap_or(""))
+    );
+    for entry in build_order.get_entries() {
+        let Some((supply, minutes, seconds)) = salt_step_fields(entry) else {
+            continue;
+        };
+        for action in entry.get_actions() {
+            if let Some((item_type, item)) = salt_code(action.get_name()) {
+                for value in [supply, minutes, seconds, item_type, item] {
+                    salt.push(encode_char(value));
+                }
+            }
+        }
+    }
+    salt
+}
+
+/// Decodes a SALT string into a build order with one action per step.
+pub fn decode_salt(salt: &str) -> Result<BuildOrder, BuildOrderError> {
+    let parse_error = |msg: &str| BuildOrderError::ParseError(format!("Invalid SALT: {}", msg));
+    let salt = salt.trim();
+    let mut chars = salt.chars();
+    if chars.next() != Some('$') {
+        return Err(parse_error("missing '$' prefix"));
+    }
+    chars
+        .next()
+        .and_then(decode_char)
+        .ok_or_else(|| parse_error("missing version"))?;
+    let (header, steps) = chars
+        .as_str()
+        .split_once('~')
+        .ok_or_else(|| parse_error("missing '~' separator"))?;
+
+    let mut build_order = BuildOrder::new();
+    let mut fields = header.split('|');
+    build_order.set_name(fields.next().unwrap_or("").to_string());
+    build_order.set_creator(fields.next().unwrap_or("").to_string());
+    if let Some(description) = fields.next().filter(|d| !d.is_empty()) {
+        build_order.set_description(description.to_string());
+    }
+
+    let values: Vec<u8> = steps
+        .chars()
+        .map(|c| {
+            decode_char(c).ok_or_else(|| parse_error(&format!("unexpected character '{}'", c)))
+        })
+        .collect::<Result<_, _>>()?;
+    if !values.len().is_multiple_of(5) {
+        return Err(parse_error("truncated step"));
+    }
+    for step in values.chunks(5) {
+        let (supply, minutes, seconds, item_type, item) =
+            (step[0], step[1], step[2], step[3], step[4]);
+        let name = SALT_TYPES
+            .get(item_type as usize)
+            .and_then(|items| items.get(item as usize))
+            .ok_or_else(|| parse_error(&format!("unknown item {}:{}", item_type, item)))?;
+        if seconds >= 60 {
+            return Err(parse_error("seconds out of range"));
+        }
+        build_order.add_step(OrderEntry::new(
+            supply,
+            format_time(minutes, seconds),
+            vec![catalog_action(name)],
+            String::new(),
+        ));
+    }
+    if let Some(race) = infer_race(&build_order) {
+        build_order.set_player_race(race);
+    }
+    Ok(build_order)
+}
@@ -0,0 +1,384 @@
+use crate::build_order::{BuildOrder, BuildType, Race};
+use crate::build_store::{BuildStore, StoreVersion};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// BM25 saturation of repeated terms
+const K1: f64 = 1.2;
+/// BM25 normalisation by document length
+const B: f64 = 0.75;
+/// Weight of a term found in the build name
+const NAME_WEIGHT: f64 = 3.0;
+/// Weight of a term found in the creator name
+const CREATOR_WEIGHT: f64 = 2.0;
+/// Weight of a term found in the description or a step comment
+const TEXT_WEIGHT: f64 = 1.0;
+/// Words too common to tell builds apart
+const STOP_WORDS: [&str; 16] = [
+    "a", "an", "and", "at", "for", "in", "into", "is", "it", "of", "on", "or", "the", "to", "vs",
+    "with",
+];
+
+fn is_consonant(word: &[u8], i: usize) -> bool {
+    match word[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(word, i - 1),
+        _ => true,
+    }
+}
+
+/// Number of vowel-consonant sequences of a stem, the `m` of Porter's algorithm.
+fn measure(word: &[u8]) -> usize {
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for i in 0..word.len() {
+        let vowel = !is_consonant(word, i);
+        if previous_vowel && !vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    count
+}
+
+fn has_vowel(word: &[u8]) -> bool {
+    (0..word.len()).any(|i| !is_consonant(word, i))
+}
+
+/// Whether a stem ends with consonant-vowel-consonant, the last not w, x or y.
+fn ends_cvc(word: &[u8]) -> bool {
+    let n = word.len();
+    n >= 3
+        && is_consonant(word, n - 3)
+        && !is_consonant(word, n - 2)
+        && is_consonant(word, n - 1)
+        && !matches!(word[n - 1], b'w' | b'x' | b'y')
+}
+
+fn ends_double_consonant(word: &[u8]) -> bool {
+    let n = word.len();
+    n >= 2 && word[n - 1] == word[n - 2] && is_consonant(word, n - 1)
+}
+
+/// Reduces an English word to its stem with the plural, tense and final `e`
+/// rules of the Porter stemmer, so that "timings", "timing" and "time" are
+/// found by one another. Words with other characters than ASCII letters are
+/// kept as they are.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    let mut w = word.as_bytes().to_vec();
+
+    // Plurals
+    if w.ends_with(b"sses") || w.ends_with(b"ies") {
+        w.truncate(w.len() - 2);
+    } else if w.ends_with(b"s") && !w.ends_with(b"ss") {
+        w.pop();
+    }
+
+    // Past tenses and gerunds
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+    } else if let Some(suffix) = [&b"ed"[..], &b"ing"[..]]
+        .into_iter()
+        .find(|suffix| w.ends_with(suffix) && has_vowel(&w[..w.len() - suffix.len()]))
+    {
+        w.truncate(w.len() - suffix.len());
+        if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+            w.push(b'e');
+        } else if ends_double_consonant(&w) && !matches!(w[w.len() - 1], b'l' | b's' | b'z') {
+            w.pop();
+        } else if measure(&w) == 1 && ends_cvc(&w) {
+            w.push(b'e');
+        }
+    }
+    if w.ends_with(b"y") && has_vowel(&w[..w.len() - 1]) {
+        *w.last_mut().unwrap() = b'i';
+    }
+
+    // Final e and double l
+    if w.ends_with(b"e") {
+        let stem = &w[..w.len() - 1];
+        let m = measure(stem);
+        if m > 1 || (m == 1 && !ends_cvc(stem)) {
+            w.pop();
+        }
+    }
+    if w.ends_with(b"ll") && measure(&w) > 1 {
+        w.pop();
+    }
+    String::from_utf8(w).unwrap_or_else(|_| word.to_string())
+}
+
+/// Stemmed search terms of a text. Hyphenated words give their parts and
+/// their joined form, so "anti-proxy" finds both "anti proxy" and "antiproxy".
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+    {
+        let parts: Vec<&str> = word.split('-').filter(|part| !part.is_empty()).collect();
+        if parts.len() > 1 {
+            terms.push(parts.concat());
+        }
+        terms.extend(
+            parts
+                .into_iter()
+                .filter(|part| !STOP_WORDS.contains(part))
+                .map(String::from),
+        );
+    }
+    terms.iter().map(|term| stem(term)).collect()
+}
+
+/// Structured criteria a search is narrowed down with.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SearchFilter {
+    pub race: Option<Race>,
+    pub opponent_race: Option<Race>,
+    pub build_type: Option<BuildType>,
+}
+
+impl SearchFilter {
+    pub fn matches(&self, build_order: &BuildOrder) -> bool {
+        self.matches_fields(
+            build_order.get_player_race(),
+            build_order.get_opponent_race(),
+            build_order.get_build_type(),
+        )
+    }
+
+    fn matches_fields(&self, race: &Race, opponent_race: &Race, build_type: &BuildType) -> bool {
+        self.race.as_ref().is_none_or(|wanted| wanted == race)
+            && self
+                .opponent_race
+                .as_ref()
+                .is_none_or(|wanted| wanted == opponent_race)
+            && self
+                .build_type
+                .as_ref()
+                .is_none_or(|wanted| wanted == build_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    pub id: u32,
+    pub name: String,
+    pub score: f64,
+}
+
+/// A build order of the index: its weighted term frequencies and length,
+/// and the fields searches are filtered on.
+struct Document {
+    id: u32,
+    name: String,
+    race: Race,
+    opponent_race: Race,
+    build_type: BuildType,
+    terms: HashMap<String, f64>,
+    length: f64,
+}
+
+/// Inverted index of the names, creators, descriptions and step comments of
+/// build orders, ranking matches with BM25.
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    /// Documents holding each term, by position in `documents`
+    postings: HashMap<String, Vec<usize>>,
+    average_length: f64,
+}
+
+impl SearchIndex {
+    pub fn new<'a>(build_orders: impl IntoIterator<Item = &'a BuildOrder>) -> Self {
+        let mut documents = Vec::new();
+        let mut postings: HashMap<String, Vec<usize>> = HashMap::new();
+        for (position, build_order) in build_orders.into_iter().enumerate() {
+            let mut terms: HashMap<String, f64> = HashMap::new();
+            let mut add = |text: &str, weight: f64| {
+                for term in tokenize(text) {
+                    *terms.entry(term).or_default() += weight;
+                }
+            };
+            add(build_order.get_name(), NAME_WEIGHT);
+            add(build_order.get_creator(), CREATOR_WEIGHT);
+            add(
+                build_order.get_description().unwrap_or_default(),
+                TEXT_WEIGHT,
+            );
+            for entry in build_order.get_entries() {
+                add(entry.get_comment().unwrap_or_default(), TEXT_WEIGHT);
+            }
+            for term in terms.keys() {
+                postings.entry(term.clone()).or_default().push(position);
+            }
+            documents.push(Document {
+                id: build_order.get_id(),
+                name: build_order.get_name().to_string(),
+                race: build_order.get_player_race().clone(),
+                opponent_race: build_order.get_opponent_race().clone(),
+                build_type: build_order.get_build_type().clone(),
+                length: terms.values().sum(),
+                terms,
+            });
+        }
+        let average_length = if documents.is_empty() {
+            0.0
+        } else {
+            documents
+                .iter()
+                .map(|document| document.length)
+                .sum::<f64>()
+                / documents.len() as f64
+        };
+        SearchIndex {
+            documents,
+            postings,
+            average_length,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Build orders matching any term of `query`, best first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        self.search_filtered(query, &SearchFilter::default(), limit)
+    }
+
+    /// Build orders matching `filter` and any term of `query`, best first.
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+        limit: usize,
+    ) -> Vec<SearchHit> {
+        let mut terms = tokenize(query);
+        terms.sort_unstable();
+        terms.dedup();
+        let total = self.documents.len() as f64;
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in &terms {
+            let Some(positions) = self.postings.get(term) else {
+                continue;
+            };
+            let frequency = positions.len() as f64;
+            let idf = (1.0 + (total - frequency + 0.5) / (frequency + 0.5)).ln();
+            for &position in positions {
+                let document = &self.documents[position];
+                if !filter.matches_fields(
+                    &document.race,
+                    &document.opponent_race,
+                    &document.build_type,
+                ) {
+                    continue;
+                }
+                let tf = document.terms[term];
+                let norm = K1 * (1.0 - B + B * document.length / self.average_length);
+                *scores.entry(position).or_default() += idf * tf * (K1 + 1.0) / (tf + norm);
+            }
+        }
+        let mut hits: Vec<SearchHit> = scores
+            .into_iter()
+            .map(|(position, score)| SearchHit {
+                id: self.documents[position].id,
+                name: self.documents[position].name.clone(),
+                score,
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Search index of a build store, kept between searches and built again
+/// when the store has changed.
+pub struct StoreIndex {
+    store: BuildStore,
+    built: Mutex<Option<(StoreVersion, Arc<SearchIndex>)>>,
+}
+
+impl StoreIndex {
+    pub fn new(store: BuildStore) -> Self {
+        StoreIndex {
+            store,
+            built: Mutex::new(None),
+        }
+    }
+
+    /// Index of the store's current contents. Reads the whole store when it
+    /// has changed since the last call.
+    pub fn current(&self) -> Arc<SearchIndex> {
+        // Read before loading, so that a save made while loading triggers
+        // another build on the next call
+        let version = self.store.version();
+        let mut built = self.built.lock().unwrap();
+        if let Some((built_version, index)) = built.as_ref()
+            && *built_version == version
+        {
+            return Arc::clone(index);
+        }
+        let index = Arc::new(SearchIndex::new(&self.store.load_all()));
+        *built = Some((version, Arc::clone(&index)));
+        index
+    }
+}
+
+/// Searches the build orders matching `filter` for `query`, best first.
+pub fn search(
+    build_orders: &[BuildOrder],
+    query: &str,
+    filter: &SearchFilter,
+    limit: usize,
+) -> Vec<SearchHit> {
+    SearchIndex::new(build_orders).search_filtered(query, filter, limit)
+}
+
+/// A race from its letter or name, such as `Z` or `zerg`.
+pub fn parse_race(value: &str) -> Result<Race, String> {
+    let letter = match value.to_ascii_lowercase().as_str() {
+        "terran" => "T".to_string(),
+        "protoss" => "P".to_string(),
+        "zerg" => "Z".to_string(),
+        "any" | "random" => "X".to_string(),
+        other => other.to_ascii_uppercase(),
+    };
+    Race::from_str(&letter).map_err(|_| format!("Unknown race: {}", value))
+}
+
+/// A build type from its name, ignoring case and punctuation, such as
+/// `timing` or `all-in`.
+pub fn parse_build_type(value: &str) -> Result<BuildType, String> {
+    let key = |name: &str| {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase()
+    };
+    [
+        BuildType::Cheese,
+        BuildType::AllIn,
+        BuildType::Timing,
+        BuildType::Economic,
+        BuildType::CoOp,
+        BuildType::None,
+    ]
+    .into_iter()
+    .find(|build_type| {
+        !key(value).is_empty() && key(&build_type.to_string()).starts_with(&key(value))
+    })
+    .ok_or_else(|| format!("Unknown build type: {}", value))
+}
@@ -0,0 +1,162 @@
+use crate::build_order::{ActionType, BuildOrder};
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Number of normalised actions compared between two builds
+pub const OPENER_LENGTH: usize = 40;
+/// Timing difference (in seconds) at which two identical actions count as a full substitution
+const TIME_TOLERANCE: f64 = 60.0;
+/// Weight of the timing difference when two actions match
+const TIME_WEIGHT: f64 = 0.5;
+/// Default similarity above which two builds are considered near-duplicates
+pub const DUPLICATE_THRESHOLD: f64 = 0.9;
+
+/// An action reduced to what matters for comparison: its name and timing.
+#[derive(Debug, Clone, PartialEq)]
+struct NormalisedAction {
+    name: String,
+    seconds: Option<u32>,
+}
+
+/// Flattens the entries of a build into its first non-worker actions.
+fn normalise(build_order: &BuildOrder) -> Vec<NormalisedAction> {
+    build_order
+        .get_entries()
+        .iter()
+        .flat_map(|entry| {
+            let seconds = entry.get_timestamp().map(|t| t.num_seconds_from_midnight());
+            entry
+                .get_actions()
+                .iter()
+                .filter(|action| action.get_action_type() != &ActionType::Worker)
+                .map(move |action| NormalisedAction {
+                    name: action.get_name().trim().to_lowercase(),
+                    seconds,
+                })
+        })
+        .take(OPENER_LENGTH)
+        .collect()
+}
+
+fn substitution_cost(a: &NormalisedAction, b: &NormalisedAction) -> f64 {
+    if a.name != b.name {
+        return 1.0;
+    }
+    match (a.seconds, b.seconds) {
+        (Some(x), Some(y)) => {
+            let delta = (x as f64 - y as f64).abs();
+            TIME_WEIGHT * (delta / TIME_TOLERANCE).min(1.0)
+        }
+        _ => 0.0,
+    }
+}
+
+/// Edit distance between two action sequences, where matching actions cost their timing gap.
+fn weighted_distance(a: &[NormalisedAction], b: &[NormalisedAction]) -> f64 {
+    let mut previous: Vec<f64> = (0..=b.len()).map(|j| j as f64).collect();
+    let mut current = vec![0.0; b.len() + 1];
+    for (i, action_a) in a.iter().enumerate() {
+        current[0] = (i + 1) as f64;
+        for (j, action_b) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + substitution_cost(action_a, action_b))
+                .min(previous[j + 1] + 1.0)
+                .min(current[j] + 1.0);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn score(a: &[NormalisedAction], b: &[NormalisedAction]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - weighted_distance(a, b) / longest as f64
+}
+
+/// Similarity between two build orders, from 0 (unrelated) to 1 (identical openers).
+pub fn similarity(a: &BuildOrder, b: &BuildOrder) -> f64 {
+    score(&normalise(a), &normalise(b))
+}
+
+fn same_matchup(a: &BuildOrder, b: &BuildOrder) -> bool {
+    a.get_player_race() == b.get_player_race() && a.get_opponent_race() == b.get_opponent_race()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SimilarBuild {
+    pub id: u32,
+    pub name: String,
+    pub score: f64,
+}
+
+/// Finds the builds of the corpus most similar to `target`, within the same matchup.
+pub fn most_similar(target: &BuildOrder, corpus: &[BuildOrder], limit: usize) -> Vec<SimilarBuild> {
+    let target_actions = normalise(target);
+    let mut results: Vec<SimilarBuild> = corpus
+        .iter()
+        .filter(|candidate| {
+            candidate.get_id() != target.get_id() && same_matchup(target, candidate)
+        })
+        .map(|candidate| SimilarBuild {
+            id: candidate.get_id(),
+            name: candidate.get_name().to_string(),
+            score: score(&target_actions, &normalise(candidate)),
+        })
+        .collect();
+    results.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    results.truncate(limit);
+    results
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut node = i;
+    while parents[node] != root {
+        let next = parents[node];
+        parents[node] = root;
+        node = next;
+    }
+    root
+}
+
+/// Groups near-duplicate builds: two builds of the same matchup whose similarity
+/// reaches `threshold` end up in the same cluster. Only clusters of two or more
+/// builds are returned, largest first, each sorted by ID.
+pub fn duplicate_clusters(corpus: &[BuildOrder], threshold: f64) -> Vec<Vec<u32>> {
+    let normalised: Vec<Vec<NormalisedAction>> = corpus.iter().map(normalise).collect();
+    let mut parents: Vec<usize> = (0..corpus.len()).collect();
+    for i in 0..corpus.len() {
+        for j in (i + 1)..corpus.len() {
+            if same_matchup(&corpus[i], &corpus[j])
+                && score(&normalised[i], &normalised[j]) >= threshold
+            {
+                let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<u32>> = Vec::new();
+    let mut cluster_of_root = HashMap::new();
+    for (i, build_order) in corpus.iter().enumerate() {
+        let root = find(&mut parents, i);
+        let index = *cluster_of_root.entry(root).or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[index].push(build_order.get_id());
+    }
+    let mut clusters: Vec<Vec<u32>> = clusters.into_iter().filter(|c| c.len() > 1).collect();
+    for cluster in clusters.iter_mut() {
+        cluster.sort_unstable();
+    }
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+    clusters
+}
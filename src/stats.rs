@@ -0,0 +1,269 @@
+use crate::build_order::{ActionType, BuildOrder};
+use async_graphql::SimpleObject;
+use chrono::{NaiveDate, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
+
+/// Structures whose first timing is tracked per matchup and patch
+pub const KEY_STRUCTURES: [&str; 16] = [
+    "Spawning Pool",
+    "Hatchery",
+    "Roach Warren",
+    "Baneling Nest",
+    "Lair",
+    "Barracks",
+    "Command Center",
+    "Factory",
+    "Starport",
+    "Gateway",
+    "Cybernetics Core",
+    "Nexus",
+    "Twilight Council",
+    "Robotics Facility",
+    "Stargate",
+    "Forge",
+];
+
+/// Parameters of a statistics report.
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct StatsOptions {
+    /// Number of leading actions forming an opener
+    pub opener_length: usize,
+    /// Number of entries kept in ranked lists
+    pub top: usize,
+    /// Minimum vote count for a build to be ranked by score
+    pub min_votes: u32,
+    /// Only include builds published on or after this date
+    pub since: Option<NaiveDate>,
+    /// Only include builds published on or before this date
+    pub until: Option<NaiveDate>,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        StatsOptions {
+            opener_length: 5,
+            top: 10,
+            min_votes: 3,
+            since: None,
+            until: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct OpenerCount {
+    pub actions: Vec<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct StructureTiming {
+    pub matchup: String,
+    pub patch: String,
+    pub structure: String,
+    /// Median time of the first such structure, in seconds
+    pub median_seconds: u32,
+    /// Mean time of the first such structure, in seconds
+    pub mean_seconds: f64,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct VoteLeader {
+    pub id: u32,
+    pub name: String,
+    pub matchup: String,
+    pub score: u32,
+    pub count: u32,
+}
+
+/// Aggregate statistics over a collection of build orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct CorpusStats {
+    pub total: usize,
+    pub by_matchup: BTreeMap<String, usize>,
+    pub by_build_type: BTreeMap<String, usize>,
+    pub by_difficulty: BTreeMap<String, usize>,
+    pub by_patch: BTreeMap<String, usize>,
+    /// Most common openers per matchup
+    pub common_openers: BTreeMap<String, Vec<OpenerCount>>,
+    pub structure_timings: Vec<StructureTiming>,
+    pub vote_leaders: Vec<VoteLeader>,
+}
+
+/// Matchup of a build order, such as `ZvP`.
+pub fn matchup(build_order: &BuildOrder) -> String {
+    format!(
+        "{}v{}",
+        build_order.get_player_race(),
+        build_order.get_opponent_race()
+    )
+}
+
+fn opener(build_order: &BuildOrder, length: usize) -> Vec<String> {
+    build_order
+        .get_entries()
+        .iter()
+        .flat_map(|entry| entry.get_actions())
+        .filter(|action| action.get_action_type() != &ActionType::Worker)
+        .take(length)
+        .map(|action| action.get_name().to_string())
+        .collect()
+}
+
+/// Time of the first occurrence of each key structure, in seconds.
+fn first_structure_times(build_order: &BuildOrder) -> HashMap<&'static str, u32> {
+    let mut times = HashMap::new();
+    for entry in build_order.get_entries() {
+        let Some(timestamp) = entry.get_timestamp() else {
+            continue;
+        };
+        for action in entry.get_actions() {
+            if let Some(structure) = KEY_STRUCTURES
+                .iter()
+                .find(|structure| **structure == action.get_name())
+            {
+                times
+                    .entry(*structure)
+                    .or_insert(timestamp.num_seconds_from_midnight());
+            }
+        }
+    }
+    times
+}
+
+fn median(values: &mut [u32]) -> u32 {
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2
+    } else {
+        values[middle]
+    }
+}
+
+fn is_in_range(build_order: &BuildOrder, options: &StatsOptions) -> bool {
+    if options.since.is_none() && options.until.is_none() {
+        return true;
+    }
+    match build_order.get_published() {
+        Some(published) => {
+            options.since.is_none_or(|since| *published >= since)
+                && options.until.is_none_or(|until| *published <= until)
+        }
+        None => false,
+    }
+}
+
+/// Computes the statistics report of a collection of build orders.
+pub fn compute_stats(corpus: &[BuildOrder], options: &StatsOptions) -> CorpusStats {
+    let builds: Vec<&BuildOrder> = corpus
+        .iter()
+        .filter(|build_order| is_in_range(build_order, options))
+        .collect();
+
+    let mut by_matchup = BTreeMap::new();
+    let mut by_build_type = BTreeMap::new();
+    let mut by_difficulty = BTreeMap::new();
+    let mut by_patch = BTreeMap::new();
+    let mut openers: BTreeMap<String, HashMap<Vec<String>, usize>> = BTreeMap::new();
+    let mut timings: BTreeMap<(String, String, &str), Vec<u32>> = BTreeMap::new();
+
+    for build_order in &builds {
+        let matchup = matchup(build_order);
+        *by_matchup.entry(matchup.clone()).or_insert(0) += 1;
+        *by_build_type
+            .entry(build_order.get_build_type().to_string())
+            .or_insert(0) += 1;
+        let difficulty = build_order
+            .get_difficulty()
+            .map_or("Unknown".to_string(), |d| d.to_string());
+        *by_difficulty.entry(difficulty).or_insert(0) += 1;
+        *by_patch
+            .entry(build_order.get_patch().to_string())
+            .or_insert(0) += 1;
+
+        let opener = opener(build_order, options.opener_length);
+        if !opener.is_empty() {
+            *openers
+                .entry(matchup.clone())
+                .or_default()
+                .entry(opener)
+                .or_insert(0) += 1;
+        }
+
+        for (structure, seconds) in first_structure_times(build_order) {
+            timings
+                .entry((
+                    matchup.clone(),
+                    build_order.get_patch().to_string(),
+                    structure,
+                ))
+                .or_default()
+                .push(seconds);
+        }
+    }
+
+    let common_openers = openers
+        .into_iter()
+        .map(|(matchup, counts)| {
+            let mut counts: Vec<OpenerCount> = counts
+                .into_iter()
+                .map(|(actions, count)| OpenerCount { actions, count })
+                .collect();
+            counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.actions.cmp(&b.actions)));
+            counts.truncate(options.top);
+            (matchup, counts)
+        })
+        .collect();
+
+    let structure_timings = timings
+        .into_iter()
+        .map(
+            |((matchup, patch, structure), mut seconds)| StructureTiming {
+                matchup,
+                patch,
+                structure: structure.to_string(),
+                mean_seconds: seconds.iter().map(|s| *s as f64).sum::<f64>() / seconds.len() as f64,
+                samples: seconds.len(),
+                median_seconds: median(&mut seconds),
+            },
+        )
+        .collect();
+
+    let mut vote_leaders: Vec<VoteLeader> = builds
+        .iter()
+        .filter_map(|build_order| {
+            let votes = build_order.get_votes()?;
+            (votes.get_count() >= options.min_votes).then(|| VoteLeader {
+                id: build_order.get_id(),
+                name: build_order.get_name().to_string(),
+                matchup: matchup(build_order),
+                score: votes.get_score(),
+                count: votes.get_count(),
+            })
+        })
+        .collect();
+    vote_leaders.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.count.cmp(&a.count))
+            .then(a.id.cmp(&b.id))
+    });
+    vote_leaders.truncate(options.top);
+
+    CorpusStats {
+        total: builds.len(),
+        by_matchup,
+        by_build_type,
+        by_difficulty,
+        by_patch,
+        common_openers,
+        structure_timings,
+        vote_leaders,
+    }
+}
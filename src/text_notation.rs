@@ -0,0 +1,171 @@
+use crate::action_catalog::{infer_race, lookup};
+use crate::build_order::{
+    Action, ActionType, BuildOrder, BuildOrderError, BuildType, Difficulty, OrderEntry, Race,
+};
+use chrono::{NaiveDate, NaiveTime};
+use std::str::FromStr;
+
+/// Markers starting a trailing comment on a step line
+const COMMENT_MARKERS: [&str; 3] = [" -- ", "#", "//"];
+
+/// Checks that a time token is a valid `m:ss` or `h:mm:ss` time of day.
+fn parse_time(token: &str) -> Option<String> {
+    let parts: Vec<&str> = token.split(':').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    let values: Vec<u32> = parts
+        .iter()
+        .map(|p| p.parse().unwrap_or(u32::MAX))
+        .collect();
+    match values[..] {
+        [minutes, seconds] => NaiveTime::from_hms_opt(0, minutes, seconds)?,
+        [hours, minutes, seconds] => NaiveTime::from_hms_opt(hours, minutes, seconds)?,
+        _ => return None,
+    };
+    Some(token.to_string())
+}
+
+fn split_comment(line: &str) -> (&str, &str) {
+    COMMENT_MARKERS
+        .iter()
+        .filter_map(|marker| line.find(marker).map(|i| (i, marker.len())))
+        .min()
+        .map_or((line, ""), |(i, len)| (&line[..i], line[i + len..].trim()))
+}
+
+fn parse_action(name: &str) -> Action {
+    let name = name.trim();
+    let action_type = lookup(name).map_or(ActionType::Action, |entry| entry.action_type.clone());
+    Action::new(action_type, name.to_string())
+}
+
+/// Parses a step line such as `16 0:48 Hatchery, Overlord # scout`.
+fn parse_step(line: &str) -> Result<OrderEntry, String> {
+    let (content, comment) = split_comment(line);
+    let content = content.trim();
+    let (supply, rest) = content
+        .split_once(char::is_whitespace)
+        .ok_or("missing actions")?;
+    let supply: u8 = supply
+        .parse()
+        .map_err(|_| format!("invalid supply '{}'", supply))?;
+    let rest = rest.trim_start();
+    let (time, actions) = match rest.split_once(char::is_whitespace) {
+        Some((token, actions)) if token.contains(':') => (
+            parse_time(token).ok_or(format!("invalid time '{}'", token))?,
+            actions,
+        ),
+        _ => (String::new(), rest),
+    };
+    let actions: Vec<Action> = actions
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(parse_action)
+        .collect();
+    if actions.is_empty() {
+        return Err("missing actions".to_string());
+    }
+    Ok(OrderEntry::new(supply, time, actions, comment.to_string()))
+}
+
+/// Parses a vote line such as `66% (3 votes)`.
+fn parse_votes(value: &str) -> Option<(u32, u32)> {
+    let (score, count) = value.split_once('%')?;
+    let count = count
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim_end_matches("votes")
+        .trim_end_matches("vote")
+        .trim();
+    Some((score.trim().parse().ok()?, count.parse().ok()?))
+}
+
+fn parse_matchup(value: &str) -> Option<(Race, Race)> {
+    let value = value.trim().to_uppercase();
+    let (player, opponent) = value.split_once('V')?;
+    Some((Race::from_str(player).ok()?, Race::from_str(opponent).ok()?))
+}
+
+/// Applies a `Key: value` header line to the build order.
+fn apply_metadata(build_order: &mut BuildOrder, key: &str, value: &str) -> Result<(), String> {
+    let value = value.trim();
+    match key.to_lowercase().as_str() {
+        "title" | "name" => build_order.set_name(value.to_string()),
+        "author" | "creator" => build_order.set_creator(value.to_string()),
+        "description" => build_order.set_description(value.to_string()),
+        "vod" => build_order.set_vod(value.to_string()),
+        "patch" => build_order.set_patch(value.to_string()),
+        "matchup" => {
+            let (player, opponent) =
+                parse_matchup(value).ok_or(format!("invalid matchup '{}'", value))?;
+            build_order.set_player_race(player);
+            build_order.set_opponent_race(opponent);
+        }
+        "type" => build_order.set_build_type(
+            BuildType::from_str(value).map_err(|_| format!("invalid build type '{}'", value))?,
+        ),
+        "difficulty" => build_order.set_difficulty(
+            Difficulty::from_str(value).map_err(|_| format!("invalid difficulty '{}'", value))?,
+        ),
+        "published" => build_order.set_published(
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| format!("invalid date '{}'", value))?,
+        ),
+        "votes" => {
+            let (score, count) = parse_votes(value).ok_or(format!("invalid votes '{}'", value))?;
+            build_order.set_votes(score, count);
+        }
+        _ => return Err(format!("unknown field '{}'", key)),
+    }
+    Ok(())
+}
+
+/// Parses a build order written in the common text notation.
+///
+/// Each step is a line made of a supply count, an optional `m:ss` time, comma
+/// separated actions and an optional trailing comment introduced by `#`, `//`
+/// or ` -- `. Header lines such as `Title: ...`, `Matchup: ZvP`, `Type: Timing
+/// Attack`, `Author: ...`, `Patch: ...`, `Difficulty: ...`, `Description: ...`,
+/// `VOD: ...`, `Published: YYYY-MM-DD` and `Votes: 66% (3 votes)` fill the
+/// build details. Blank lines and lines starting with `#` are ignored. Action
+/// names are resolved against the action catalog, and unknown names are kept
+/// as generic actions.
+pub fn parse_text_build(content: &str) -> Result<BuildOrder, BuildOrderError> {
+    let mut build_order = BuildOrder::new();
+    let mut has_matchup = false;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let result = if line.starts_with(|c: char| c.is_ascii_digit()) {
+            parse_step(line).map(|entry| build_order.add_step(entry))
+        } else {
+            match line.split_once(':') {
+                Some((key, value)) => {
+                    has_matchup |= key.trim().eq_ignore_ascii_case("matchup");
+                    apply_metadata(&mut build_order, key.trim(), value)
+                }
+                None => Err("expected a step or a 'Key: value' line".to_string()),
+            }
+        };
+        result
+            .map_err(|msg| BuildOrderError::ParseError(format!("Line {}: {}", number + 1, msg)))?;
+    }
+
+    if build_order.get_entries().is_empty() {
+        return Err(BuildOrderError::InvalidData(
+            "No build order steps found".to_string(),
+        ));
+    }
+    if !has_matchup && let Some(race) = infer_race(&build_order) {
+        build_order.set_player_race(race);
+    }
+    Ok(build_order)
+}
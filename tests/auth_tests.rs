@@ -0,0 +1,195 @@
+use build_warren::auth::{
+    AuthError, Authenticator, Caller, ClientId, KeyError, KeyStore, QuotaTracker, hash_key,
+};
+use build_warren::config::{AuthConfig, Config, QuotaConfig};
+use build_warren::jobs::JobKind;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("build_warren_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn quota(requests_per_minute: Option<u32>, fetches_per_hour: Option<u32>) -> QuotaConfig {
+    QuotaConfig {
+        requests_per_minute,
+        fetches_per_hour,
+    }
+}
+
+#[test]
+fn test_keys_are_issued_and_revoked() {
+    let path = temp_dir("keys_issue").join("keys.json");
+    let keys = KeyStore::open(&path).expect("Failed to open key store");
+    assert!(keys.list().unwrap().is_empty());
+
+    let (key, secret) = keys.issue("site", None).expect("Failed to issue key");
+    assert_eq!(key.get_name(), "site");
+    assert!(secret.starts_with("bw_"));
+    assert_eq!(
+        keys.issue("site", None).unwrap_err(),
+        KeyError::Duplicate("site".to_string())
+    );
+
+    // Only the hash of the key is written
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains(&secret));
+    assert!(content.contains(&hash_key(&secret)));
+
+    let reopened = KeyStore::open(&path).unwrap();
+    assert_eq!(reopened.find(&secret).unwrap(), Some(key));
+    assert_eq!(reopened.find("bw_unknown").unwrap(), None);
+
+    let revoked = reopened.revoke("site").expect("Failed to revoke key");
+    assert!(revoked.is_revoked());
+    assert_eq!(
+        reopened.revoke("site").unwrap_err(),
+        KeyError::NotFound("site".to_string())
+    );
+    // Revocations made elsewhere are picked up when the file changes
+    assert_eq!(keys.find(&secret).unwrap(), None);
+    assert_eq!(keys.list().unwrap().len(), 1);
+}
+
+#[test]
+fn test_authentication_by_key_and_address() {
+    let dir = temp_dir("keys_authenticate");
+    let keys = KeyStore::open(dir.join("keys.json")).unwrap();
+    let (_, secret) = keys.issue("site", None).unwrap();
+    let mut config = AuthConfig::default();
+    config
+        .keys
+        .insert("bot".to_string(), "configured-key".to_string());
+    let authenticator = Authenticator::new(config.clone(), keys);
+
+    let caller = authenticator
+        .authenticate(Some(&secret), "1.2.3.4")
+        .unwrap();
+    assert_eq!(caller.get_id(), &ClientId::Key("site".to_string()));
+    assert_eq!(caller.get_quota(), &config.quota);
+    let caller = authenticator
+        .authenticate(Some("configured-key"), "1.2.3.4")
+        .unwrap();
+    assert_eq!(caller.get_id(), &ClientId::Key("bot".to_string()));
+    assert_eq!(caller.get_key_name(), Some("bot"));
+    assert!(caller.require_key().is_ok());
+    assert!(caller.owns(Some("bot")));
+    assert!(!caller.owns(Some("site")));
+    assert!(!caller.owns(None));
+    let caller = authenticator.authenticate(None, "1.2.3.4").unwrap();
+    assert_eq!(caller.get_id(), &ClientId::Anonymous("1.2.3.4".to_string()));
+    assert_eq!(caller.get_quota(), &config.anonymous_quota);
+    // Anonymous callers may only read, and own nothing
+    assert_eq!(caller.require_key(), Err(AuthError::MissingKey));
+    assert!(!caller.owns(None));
+    assert!(Caller::unlimited().owns(Some("bot")));
+    assert!(Caller::unlimited().require_key().is_ok());
+    assert_eq!(
+        authenticator
+            .authenticate(Some("bw_unknown"), "1.2.3.4")
+            .err(),
+        Some(AuthError::InvalidKey)
+    );
+
+    config.allow_anonymous = false;
+    let authenticator = Authenticator::new(config, KeyStore::open(dir.join("keys.json")).unwrap());
+    assert_eq!(
+        authenticator.authenticate(None, "1.2.3.4").err(),
+        Some(AuthError::MissingKey)
+    );
+}
+
+#[test]
+fn test_quotas_are_enforced_per_client() {
+    let dir = temp_dir("keys_quota");
+    let keys = KeyStore::open(dir.join("keys.json")).unwrap();
+    let (_, secret) = keys.issue("site", Some(quota(None, Some(3)))).unwrap();
+    let config = AuthConfig {
+        quota: quota(Some(100), Some(1000)),
+        anonymous_quota: quota(Some(2), Some(1)),
+        ..AuthConfig::default()
+    };
+    let authenticator = Authenticator::new(config, keys);
+
+    assert!(authenticator.authenticate(None, "1.2.3.4").is_ok());
+    assert!(authenticator.authenticate(None, "1.2.3.4").is_ok());
+    match authenticator.authenticate(None, "1.2.3.4") {
+        Err(AuthError::QuotaExceeded { retry_after, .. }) => {
+            assert!(retry_after > 0 && retry_after <= 60)
+        }
+        other => panic!(
+            "Expected the request quota to be exceeded, got {:?}",
+            other.err()
+        ),
+    }
+    // Other addresses have quotas of their own
+    let caller = authenticator.authenticate(None, "5.6.7.8").unwrap();
+    assert!(caller.charge_fetches(1).is_ok());
+    assert!(matches!(
+        caller.charge_fetches(1),
+        Err(AuthError::QuotaExceeded { .. })
+    ));
+
+    // The key's own fetch quota replaces the configured one, its request
+    // quota falls back on it
+    let caller = authenticator
+        .authenticate(Some(&secret), "1.2.3.4")
+        .unwrap();
+    assert_eq!(caller.get_quota(), &quota(Some(100), Some(3)));
+    assert!(matches!(
+        caller.charge_fetches(4),
+        Err(AuthError::QuotaExceeded { .. })
+    ));
+    assert!(caller.charge_fetches(3).is_ok());
+    assert!(caller.charge_fetches(1).is_err());
+}
+
+#[test]
+fn test_quota_tracker_forgets_least_recently_seen_clients() {
+    let tracker = QuotaTracker::default();
+    let quota = quota(Some(1), None);
+    let client = |name: &str| ClientId::Anonymous(name.to_string());
+    assert!(tracker.take_request(&client("a"), &quota).is_ok());
+    assert!(tracker.take_request(&client("b"), &quota).is_ok());
+    for i in 0..9_998 {
+        assert!(
+            tracker
+                .take_request(&client(&i.to_string()), &quota)
+                .is_ok()
+        );
+    }
+    // Seeing "a" again keeps it tracked, so "b" is the one forgotten
+    assert!(tracker.take_request(&client("a"), &quota).is_err());
+    assert!(tracker.take_request(&client("new"), &quota).is_ok());
+    assert!(tracker.take_request(&client("b"), &quota).is_ok());
+    assert!(tracker.take_request(&client("a"), &quota).is_err());
+}
+
+#[test]
+fn test_job_fetches_and_auth_config() {
+    assert_eq!(JobKind::FetchSegment { start: 5, end: 10 }.fetches(), 6);
+    assert_eq!(JobKind::Sync { start: 10, end: 5 }.fetches(), 0);
+    assert_eq!(JobKind::FetchLatest { count: 3 }.fetches(), 3);
+
+    let config = Config::from_toml(
+        r#"
+        [server]
+        cors_origins = ["https://example.org"]
+
+        [auth]
+        allow_anonymous = false
+        keys = { site = "secret" }
+
+        [auth.anonymous_quota]
+        requests_per_minute = 5
+        "#,
+    )
+    .expect("Failed to parse config");
+    assert_eq!(config.server.cors_origins, vec!["https://example.org"]);
+    assert!(!config.auth.allow_anonymous);
+    assert_eq!(config.auth.keys["site"], "secret");
+    assert_eq!(config.auth.anonymous_quota, quota(Some(5), None));
+    assert_eq!(config.auth.quota, AuthConfig::default().quota);
+}
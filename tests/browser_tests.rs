@@ -0,0 +1,154 @@
+use build_warren::browser::{Browser, Command, Focus};
+use build_warren::build_order::{
+    Action, ActionType, BuildOrder, BuildType, Difficulty, OrderEntry, Race,
+};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use std::time::{Duration, Instant};
+
+fn build(id: u32, race: Race, build_type: BuildType, score: u32) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(format!("Build {}", id));
+    build_order.set_player_race(race);
+    build_order.set_opponent_race(Race::Protoss);
+    build_order.set_build_type(build_type);
+    build_order.set_difficulty(Difficulty::Medium);
+    build_order.set_description("Hold the first push".to_string());
+    build_order.set_votes(score, 10);
+    for (supply, time, name) in [(14, "0:18", "Pylon"), (16, "0:40", "Gateway")] {
+        build_order.add_step(OrderEntry::new(
+            supply,
+            time.to_string(),
+            vec![Action::new(ActionType::Building, name.to_string())],
+            String::new(),
+        ));
+    }
+    build_order
+}
+
+fn browser() -> Browser {
+    Browser::new(vec![
+        build(1, Race::Protoss, BuildType::Timing, 90),
+        build(2, Race::Zerg, BuildType::Economic, 55),
+        build(3, Race::Protoss, BuildType::Economic, 70),
+    ])
+}
+
+fn press(browser: &mut Browser, keys: &str) -> Command {
+    keys.chars()
+        .map(|c| browser.handle_key(KeyEvent::from(KeyCode::Char(c))))
+        .last()
+        .unwrap_or(Command::None)
+}
+
+fn visible_ids(browser: &Browser) -> Vec<u32> {
+    browser
+        .visible()
+        .iter()
+        .map(|build| build.get_id())
+        .collect()
+}
+
+fn screen(browser: &mut Browser, now: Instant) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(120, 24)).unwrap();
+    terminal
+        .draw(|frame| browser.render(frame, now))
+        .expect("Failed to draw the browser");
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_filters_narrow_the_list() {
+    let mut browser = browser();
+    assert_eq!(visible_ids(&browser), [1, 2, 3]);
+
+    press(&mut browser, "m"); // PvP
+    assert_eq!(visible_ids(&browser), [1, 3]);
+    press(&mut browser, "tttt"); // Economic
+    assert_eq!(visible_ids(&browser), [3]);
+    press(&mut browser, "c");
+    press(&mut browser, "vvv"); // Votes >= 70
+    assert_eq!(visible_ids(&browser), [1, 3]);
+    press(&mut browser, "ddd"); // Hard
+    assert!(visible_ids(&browser).is_empty());
+    assert!(browser.selected().is_none());
+    press(&mut browser, "c");
+
+    press(&mut browser, "/race:Z");
+    assert_eq!(visible_ids(&browser), [1, 2, 3]);
+    browser.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(visible_ids(&browser), [2]);
+    press(&mut browser, "/race:");
+    browser.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert!(browser.get_status().unwrap().starts_with("Invalid query"));
+    assert_eq!(visible_ids(&browser), [2]);
+}
+
+#[test]
+fn test_keys_move_and_ask_for_commands() {
+    let mut browser = browser();
+    assert_eq!(browser.selected().unwrap().get_id(), 1);
+    press(&mut browser, "jj");
+    assert_eq!(browser.selected().unwrap().get_id(), 3);
+    press(&mut browser, "j");
+    assert_eq!(browser.selected().unwrap().get_id(), 3);
+    press(&mut browser, "g");
+    assert_eq!(browser.selected().unwrap().get_id(), 1);
+
+    // The selection is kept while it matches the filters
+    press(&mut browser, "G");
+    press(&mut browser, "m");
+    assert_eq!(browser.selected().unwrap().get_id(), 3);
+
+    browser.handle_key(KeyEvent::from(KeyCode::Tab));
+    assert_eq!(browser.get_focus(), Focus::Detail);
+    press(&mut browser, "j");
+    assert_eq!(browser.selected().unwrap().get_id(), 3);
+
+    assert_eq!(press(&mut browser, "r"), Command::Refresh(3));
+    assert_eq!(press(&mut browser, "e"), Command::Export(3));
+    assert_eq!(press(&mut browser, "q"), Command::Quit);
+
+    let mut refreshed = build(3, Race::Protoss, BuildType::Economic, 75);
+    refreshed.set_name("Build 3, revised".to_string());
+    browser.replace(refreshed);
+    assert_eq!(browser.selected().unwrap().get_name(), "Build 3, revised");
+}
+
+#[test]
+fn test_render_list_details_and_timer() {
+    let mut browser = browser();
+    let now = Instant::now();
+    let screen_text = screen(&mut browser, now);
+    assert!(screen_text.contains("Matchup: All"));
+    assert!(screen_text.contains("PvP  90% Build 1"));
+    assert!(screen_text.contains("ZvP  55% Build 2"));
+    assert!(screen_text.contains("Build 1 (1)"));
+    assert!(screen_text.contains("Votes: 90% (10 votes)"));
+    assert!(screen_text.contains("Hold the first push"));
+    assert!(screen_text.contains(" 14  0:18 Pylon"));
+
+    browser.toggle_timer(now);
+    assert!(browser.is_timing());
+    assert_eq!(
+        browser.elapsed(now + Duration::from_secs(30)),
+        Some(Duration::from_secs(30))
+    );
+    let screen_text = screen(&mut browser, now + Duration::from_secs(30));
+    assert!(screen_text.contains("Timer 0:30"));
+
+    // Moving to another build stops the timer
+    press(&mut browser, "j");
+    assert!(!browser.is_timing());
+
+    press(&mut browser, "ddd");
+    assert!(screen(&mut browser, now).contains("No build order matches the filters"));
+}
@@ -0,0 +1,91 @@
+use build_warren::build_diff::{DiffKind, diff_builds};
+use build_warren::build_order::{Action, ActionType, BuildOrder, OrderEntry};
+
+fn build(id: u32, steps: &[(u8, &str, &str)]) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    for (supply, time, name) in steps {
+        build_order.add_step(OrderEntry::new(
+            *supply,
+            time.to_string(),
+            vec![Action::new(ActionType::Building, name.to_string())],
+            String::new(),
+        ));
+    }
+    build_order
+}
+
+#[test]
+fn test_diff_identical_builds() {
+    let left = build(1, &[(14, "0:18", "Supply Depot"), (16, "0:40", "Barracks")]);
+    let diff = diff_builds(&left, &left);
+    assert_eq!(diff.get_summary().matched, 2);
+    assert!(
+        diff.get_steps()
+            .iter()
+            .all(|step| step.get_kind() == DiffKind::Matched && step.get_time_delta() == Some(0))
+    );
+}
+
+#[test]
+fn test_diff_insert_remove_and_timing() {
+    let left = build(
+        1,
+        &[
+            (14, "0:18", "Supply Depot"),
+            (16, "0:40", "Barracks"),
+            (16, "0:45", "Refinery"),
+        ],
+    );
+    let right = build(
+        2,
+        &[
+            (14, "0:20", "Supply Depot"),
+            (16, "0:42", "Barracks"),
+            (19, "1:30", "Command Center"),
+        ],
+    );
+    let diff = diff_builds(&left, &right);
+    let summary = diff.get_summary();
+    assert_eq!(summary.matched, 2);
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.removed, 1);
+    assert_eq!(summary.reordered, 0);
+    assert_eq!(diff.get_steps()[0].get_time_delta(), Some(2));
+}
+
+#[test]
+fn test_diff_reordered_step() {
+    let left = build(
+        1,
+        &[
+            (16, "0:50", "Hatchery"),
+            (18, "1:00", "Extractor"),
+            (17, "1:05", "Spawning Pool"),
+        ],
+    );
+    let right = build(
+        2,
+        &[
+            (16, "0:50", "Hatchery"),
+            (17, "0:58", "Spawning Pool"),
+            (18, "1:04", "Extractor"),
+        ],
+    );
+    let diff = diff_builds(&left, &right);
+    assert_eq!(diff.get_summary().reordered, 1);
+    assert_eq!(diff.get_summary().inserted, 0);
+    assert_eq!(diff.get_summary().removed, 0);
+    let reordered = diff
+        .get_steps()
+        .iter()
+        .find(|step| step.get_kind() == DiffKind::Reordered)
+        .unwrap();
+    assert_eq!(
+        reordered.get_right().unwrap().get_actions(),
+        "Spawning Pool"
+    );
+    assert_eq!(reordered.get_left().unwrap().get_index(), 2);
+    assert_eq!(reordered.get_right().unwrap().get_index(), 1);
+    assert_eq!(reordered.get_time_delta(), Some(-7));
+}
@@ -1,5 +1,10 @@
-use build_warren::build_order::{BuildType, Difficulty, Race};
-use build_warren::build_parser::parse_build_order;
+use build_warren::build_order::{BuildOrderError, BuildType, Difficulty, Race};
+use build_warren::build_parser::{
+    fetch_build_order_from, fetch_build_order_from_async, parse_build_order,
+};
+use build_warren::handlers::FetchSummary;
+use build_warren::http_client::AsyncHttpClient;
+use mockito::Server;
 
 fn open_file(file_name: &str) -> String {
     std::fs::read_to_string(file_name).expect("Failed to read file")
@@ -59,3 +64,111 @@ fn test_parse_oldestbuild() {
     assert!(build_order.get_published().is_none());
     assert_eq!(build_order.get_entries().len(), 107);
 }
+
+#[test]
+fn test_fetch_build_order_statuses() {
+    let mut server = Server::new();
+    let base_url = server.url() + "/build/";
+    let _ok = server
+        .mock("GET", "/build/193576/")
+        .with_status(200)
+        .with_body(open_file("tests/examples/clemvsmaxpax.html"))
+        .create();
+    let _cloaked = server
+        .mock("GET", "/build/1/")
+        .with_status(302)
+        .with_header("Location", "/accounts/login/?next=/build/1/")
+        .create();
+    let _moved = server
+        .mock("GET", "/build/2/")
+        .with_status(301)
+        .with_header("location", "/build/3/")
+        .create();
+    let _deleted = server.mock("GET", "/build/4/").with_status(404).create();
+    let _limited = server
+        .mock("GET", "/build/5/")
+        .with_status(429)
+        .with_header("Retry-After", "30")
+        .create();
+    let _error = server.mock("GET", "/build/6/").with_status(500).create();
+
+    let fetch = |id| fetch_build_order_from(&base_url, id);
+    assert_eq!(fetch(193576).unwrap().get_id(), 193576);
+    assert_eq!(fetch(1).unwrap_err(), BuildOrderError::Cloaked);
+    assert_eq!(
+        fetch(2).unwrap_err(),
+        BuildOrderError::Redirected {
+            location: "/build/3/".to_string()
+        }
+    );
+    assert_eq!(fetch(4).unwrap_err(), BuildOrderError::NotFound);
+    assert_eq!(
+        fetch(5).unwrap_err(),
+        BuildOrderError::RateLimited {
+            retry_after: Some(30)
+        }
+    );
+    assert!(matches!(fetch(6), Err(BuildOrderError::HttpError(_))));
+
+    let mut summary = FetchSummary::default();
+    for id in [193576, 1, 2, 4, 5, 6] {
+        summary.record(&fetch(id));
+    }
+    assert_eq!(
+        summary,
+        FetchSummary {
+            fetched: 1,
+            cloaked: 1,
+            not_found: 1,
+            redirected: 1,
+            rate_limited: 1,
+            failed: 1,
+        }
+    );
+}
+
+#[actix_web::test]
+async fn test_fetch_build_order_async() {
+    let mut server = Server::new_async().await;
+    let base_url = server.url() + "/build/";
+    let _ok = server
+        .mock("GET", "/build/193576/")
+        .with_status(200)
+        .with_body(open_file("tests/examples/clemvsmaxpax.html"))
+        .create_async()
+        .await;
+    let _cloaked = server
+        .mock("GET", "/build/1/")
+        .with_status(302)
+        .with_header("Location", "/accounts/login/?next=/build/1/")
+        .create_async()
+        .await;
+    let _deleted = server
+        .mock("GET", "/build/4/")
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let client = AsyncHttpClient::new();
+    let build_order = fetch_build_order_from_async(&client, &base_url, 193576)
+        .await
+        .expect("Failed to fetch build order");
+    let expected = parse_build_order(&open_file("tests/examples/clemvsmaxpax.html"), 193576)
+        .expect("Failed to parse build order");
+    assert_eq!(
+        serde_json::to_value(&build_order).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+    assert_eq!(
+        fetch_build_order_from_async(&client, &base_url, 1)
+            .await
+            .unwrap_err(),
+        BuildOrderError::Cloaked
+    );
+    assert_eq!(
+        fetch_build_order_from_async(&client, &base_url, 4)
+            .await
+            .unwrap_err(),
+        BuildOrderError::NotFound
+    );
+}
@@ -0,0 +1,70 @@
+use build_warren::build_order::BuildOrder;
+use build_warren::build_store::{BuildStore, LOCAL_ID_BASE};
+
+#[test]
+fn test_store_round_trip() {
+    let root = std::env::temp_dir().join(format!("build_warren_store_{}", std::process::id()));
+    let store = BuildStore::open(&root).expect("Failed to open store");
+
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(42);
+    build_order.set_name("Hatch first".to_string());
+    store
+        .save(&build_order)
+        .expect("Failed to save build order");
+    build_order.set_id(7);
+    store
+        .save(&build_order)
+        .expect("Failed to save build order");
+
+    assert!(store.contains(42));
+    assert!(!store.contains(43));
+    assert_eq!(store.ids(), vec![7, 42]);
+    assert_eq!(store.load(42).unwrap().get_name(), "Hatch first");
+    assert_eq!(store.load_all().len(), 2);
+    // Saves go through temporary files, none of which are left behind
+    let mut files: Vec<String> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["42.json", "7.json"]);
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_concurrent_local_saves_get_distinct_ids() {
+    let root = std::env::temp_dir().join(format!("build_warren_local_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let store = BuildStore::open(&root).expect("Failed to open store");
+
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                (0..5)
+                    .map(|index| {
+                        let mut build_order = BuildOrder::new();
+                        build_order.set_name(format!("Import {} {}", thread, index));
+                        let id = store.save_local(&mut build_order).unwrap();
+                        assert_eq!(build_order.get_id(), id);
+                        (id, build_order.get_name().to_string())
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let saved: Vec<(u32, String)> = threads
+        .into_iter()
+        .flat_map(|thread| thread.join().unwrap())
+        .collect();
+
+    let ids: Vec<u32> = (LOCAL_ID_BASE..LOCAL_ID_BASE + 40).collect();
+    assert_eq!(store.ids(), ids);
+    // No import overwrote another
+    for (id, name) in saved {
+        assert_eq!(store.load(id).unwrap().get_name(), name);
+    }
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,97 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, OrderEntry, Race};
+use build_warren::classifier::OpenerClassifier;
+
+fn build(race: Race, steps: &[(u8, &str, &str)]) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_player_race(race);
+    build_order.set_opponent_race(Race::Protoss);
+    for (supply, name, comment) in steps {
+        build_order.add_step(OrderEntry::new(
+            *supply,
+            String::new(),
+            vec![Action::new(ActionType::Building, name.to_string())],
+            comment.to_string(),
+        ));
+    }
+    build_order
+}
+
+#[test]
+fn test_default_rules_load() {
+    let classifier = OpenerClassifier::default();
+    assert!(classifier.get_rules().len() >= 5);
+}
+
+#[test]
+fn test_classify_zerg_openers() {
+    let classifier = OpenerClassifier::default();
+    let hatch_pool_gas = build(
+        Race::Zerg,
+        &[
+            (16, "Hatchery", ""),
+            (17, "Spawning Pool", ""),
+            (18, "Extractor", ""),
+        ],
+    );
+    assert_eq!(classifier.classify(&hatch_pool_gas), vec!["Hatch-Pool-Gas"]);
+
+    let pool_first = build(
+        Race::Zerg,
+        &[(17, "Spawning Pool", ""), (18, "Hatchery", "")],
+    );
+    assert_eq!(classifier.classify(&pool_first), vec!["Pool first"]);
+}
+
+#[test]
+fn test_classify_terran_openers() {
+    let classifier = OpenerClassifier::default();
+    let reaper_expand = build(
+        Race::Terran,
+        &[
+            (14, "Supply Depot", ""),
+            (16, "Barracks", ""),
+            (16, "Refinery", ""),
+            (19, "Reaper", ""),
+            (20, "Command Center", ""),
+        ],
+    );
+    assert!(classifier.has_label(&reaper_expand, "reaper expand"));
+
+    let three_rax = build(
+        Race::Terran,
+        &[
+            (14, "Barracks", ""),
+            (15, "Barracks", ""),
+            (16, "Barracks", ""),
+            (17, "Reaper", ""),
+        ],
+    );
+    assert_eq!(classifier.classify(&three_rax), vec!["3-Rax Reaper"]);
+}
+
+#[test]
+fn test_custom_rules_with_mentions() {
+    let classifier = OpenerClassifier::from_toml(
+        r#"
+        [[opener]]
+        name = "Proxy Gate"
+        race = "Protoss"
+        min_counts = { Gateway = 1 }
+        mentions = ["proxy"]
+        "#,
+    )
+    .expect("Failed to parse rules");
+    let proxied = build(
+        Race::Protoss,
+        &[(14, "Gateway", "Proxy near their natural")],
+    );
+    let home = build(Race::Protoss, &[(14, "Gateway", "")]);
+    assert_eq!(classifier.classify(&proxied), vec!["Proxy Gate"]);
+    assert!(classifier.classify(&home).is_empty());
+    assert_eq!(
+        classifier
+            .search(&[proxied, home], Some("Proxy Gate"))
+            .len(),
+        1
+    );
+}
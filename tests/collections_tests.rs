@@ -0,0 +1,218 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, OrderEntry};
+use build_warren::build_store::{BuildStore, LOCAL_ID_BASE};
+use build_warren::collections::{
+    CollectionBundle, CollectionError, LIBRARY_FILE, LOCAL_OWNER, Library, LibraryStore,
+};
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("build_warren_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn build(id: u32, name: &str) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(name.to_string());
+    for (supply, time, action) in [(14, "0:18", "Pylon"), (16, "0:40", "Gateway")] {
+        build_order.add_step(OrderEntry::new(
+            supply,
+            time.to_string(),
+            vec![Action::new(ActionType::Building, action.to_string())],
+            String::new(),
+        ));
+    }
+    build_order
+}
+
+#[test]
+fn test_favourites_collections_and_notes() {
+    let mut library = Library::default();
+    assert!(library.star(193576));
+    assert!(!library.star(193576));
+    assert!(library.is_favourite(193576));
+    assert!(library.unstar(193576));
+    assert!(!library.is_favourite(193576));
+
+    library
+        .create_collection("ZvP ladder", Some("Roach openers".to_string()))
+        .unwrap();
+    assert_eq!(
+        library.create_collection("ZvP ladder", None).unwrap_err(),
+        CollectionError::Duplicate("ZvP ladder".to_string())
+    );
+    assert!(matches!(
+        library.create_collection("a/b", None).unwrap_err(),
+        CollectionError::InvalidName(_)
+    ));
+    let collection = library.add_builds("ZvP ladder", &[3, 1, 3, 2]).unwrap();
+    assert_eq!(collection.get_builds(), [3, 1, 2]);
+    assert_eq!(collection.get_description(), Some("Roach openers"));
+    let collection = library.remove_builds("ZvP ladder", &[1]).unwrap();
+    assert_eq!(collection.get_builds(), [3, 2]);
+    // Adding to a missing collection creates it
+    library.add_builds("tournament prep", &[7]).unwrap();
+    let names: Vec<&str> = library
+        .get_collections()
+        .iter()
+        .map(|collection| collection.get_name())
+        .collect();
+    assert_eq!(names, ["ZvP ladder", "tournament prep"]);
+    assert_eq!(
+        library.remove_builds("macro", &[1]).unwrap_err(),
+        CollectionError::NotFound("macro".to_string())
+    );
+
+    let build_order = build(5, "Gate expand");
+    library.set_note(5, "Good against early pools");
+    library
+        .set_step_note(&build_order, 2, "Chrono the nexus")
+        .unwrap();
+    assert_eq!(
+        library.set_step_note(&build_order, 3, "x").unwrap_err(),
+        CollectionError::NoSuchStep { id: 5, step: 3 }
+    );
+    assert!(library.set_step_note(&build_order, 0, "x").is_err());
+    let notes = library.get_notes(5).unwrap();
+    assert_eq!(notes.get_note(), Some("Good against early pools"));
+    assert_eq!(notes.get_step(2), Some("Chrono the nexus"));
+
+    // Blank notes remove them, and builds left without notes are forgotten
+    library.set_note(5, " ");
+    library.set_step_note(&build_order, 2, "").unwrap();
+    assert_eq!(library.get_notes(5), None);
+}
+
+#[test]
+fn test_libraries_are_kept_per_owner() {
+    let dir = temp_dir("library_owners");
+    let store = BuildStore::open(&dir).unwrap();
+    let path = dir.join(LIBRARY_FILE);
+    let libraries = LibraryStore::open(&path).expect("Failed to open library");
+    assert!(libraries.library(LOCAL_OWNER).unwrap().is_empty());
+
+    libraries
+        .update(LOCAL_OWNER, |library| {
+            library.star(1);
+            library.add_builds("ZvP ladder", &[1, 2]).cloned()
+        })
+        .unwrap();
+    libraries
+        .update("community_site", |library| {
+            library.set_note(1, "Shared account");
+            Ok(())
+        })
+        .unwrap();
+    // A failed change is not saved
+    let error = libraries.update(LOCAL_OWNER, |library| {
+        library.star(2);
+        library.delete_collection("macro")
+    });
+    assert!(error.is_err());
+
+    let reopened = LibraryStore::open(&path).unwrap();
+    let local = reopened.library(LOCAL_OWNER).unwrap();
+    assert_eq!(local.get_favourites().iter().collect::<Vec<_>>(), [&1]);
+    assert_eq!(local.get_notes(1), None);
+    let site = reopened.library("community_site").unwrap();
+    assert!(site.get_collections().is_empty());
+    assert!(site.get_notes(1).is_some());
+
+    // The library file is not mistaken for a build
+    store.save(&build(1, "Gate expand")).unwrap();
+    assert_eq!(store.ids(), [1]);
+    // Changes made elsewhere are picked up when the file changes
+    reopened
+        .update(LOCAL_OWNER, |library| Ok(library.unstar(1)))
+        .unwrap();
+    assert!(
+        libraries
+            .library(LOCAL_OWNER)
+            .unwrap()
+            .get_favourites()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_bundles_round_trip() {
+    let sender_store = BuildStore::open(temp_dir("bundle_sender")).unwrap();
+    sender_store.save(&build(193576, "Gate expand")).unwrap();
+    sender_store
+        .save(&build(LOCAL_ID_BASE, "Imported cannon rush"))
+        .unwrap();
+    let mut sender = Library::default();
+    sender
+        .add_builds("PvP", &[193576, LOCAL_ID_BASE, 42, LOCAL_ID_BASE + 5])
+        .unwrap();
+    sender.set_note(193576, "Private");
+
+    // Builds from Spawning Tool are only referenced by ID
+    let bundle = sender.bundle("PvP", &sender_store, false).unwrap();
+    let bundled: Vec<u32> = bundle.get_builds().iter().map(|b| b.get_id()).collect();
+    assert_eq!(bundled, [LOCAL_ID_BASE]);
+    assert!(bundle.get_notes().is_empty());
+    assert_eq!(
+        sender.bundle("ZvP", &sender_store, false).unwrap_err(),
+        CollectionError::NotFound("ZvP".to_string())
+    );
+    let with_notes = sender.bundle("PvP", &sender_store, true).unwrap();
+    assert_eq!(with_notes.get_notes().len(), 1);
+
+    // A forged copy of a Spawning Tool build is not saved
+    let mut json = serde_json::to_value(&with_notes).unwrap();
+    let mut forged = serde_json::to_value(build(193844, "Forged")).unwrap();
+    forged["id"] = 193844.into();
+    json["builds"].as_array_mut().unwrap().push(forged);
+    json["collection"]["builds"]
+        .as_array_mut()
+        .unwrap()
+        .push(193844.into());
+
+    // The receiver already has a different local build under the same ID
+    let receiver_store = BuildStore::open(temp_dir("bundle_receiver")).unwrap();
+    receiver_store
+        .save(&build(LOCAL_ID_BASE, "Own build"))
+        .unwrap();
+    let mut receiver = Library::default();
+    let bundle: CollectionBundle = serde_json::from_value(json).unwrap();
+    let collection = receiver
+        .import_bundle(bundle.clone(), None, &receiver_store)
+        .unwrap();
+    // The local build missing from the bundle is dropped
+    assert_eq!(
+        collection.get_builds(),
+        [193576, LOCAL_ID_BASE + 1, 42, 193844]
+    );
+    assert!(!receiver_store.contains(193576));
+    assert!(!receiver_store.contains(193844));
+    assert_eq!(
+        receiver_store.load(LOCAL_ID_BASE + 1).unwrap().get_name(),
+        "Imported cannon rush"
+    );
+    assert_eq!(
+        receiver_store.load(LOCAL_ID_BASE).unwrap().get_name(),
+        "Own build"
+    );
+    assert_eq!(
+        receiver.get_notes(193576).unwrap().get_note(),
+        Some("Private")
+    );
+
+    assert_eq!(
+        receiver
+            .import_bundle(bundle.clone(), None, &receiver_store)
+            .unwrap_err(),
+        CollectionError::Duplicate("PvP".to_string())
+    );
+    // Importing again reuses the copies already saved
+    let collection = receiver
+        .import_bundle(bundle, Some("PvP copy"), &receiver_store)
+        .unwrap();
+    assert_eq!(
+        collection.get_builds(),
+        [193576, LOCAL_ID_BASE + 1, 42, 193844]
+    );
+}
@@ -0,0 +1,117 @@
+use build_warren::build_parser::{BUILD_URL, CACHE_TTL};
+use build_warren::config::{Config, LogLevel};
+use build_warren::index_manager::LOWEST_INDEX;
+use std::str::FromStr;
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_default_config() {
+    let config = Config::layered(None, Vec::new()).expect("Failed to build config");
+    assert_eq!(config, Config::default());
+    assert_eq!(config.spawning_tool.build_url, BUILD_URL);
+    assert_eq!(config.spawning_tool.lowest_index, LOWEST_INDEX);
+    assert_eq!(config.server.bind, "127.0.0.1");
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.storage.cache, None);
+    assert_eq!(config.storage.cache_ttl, CACHE_TTL);
+    assert_eq!(config.log.level, LogLevel::Info);
+}
+
+#[test]
+fn test_config_file_overrides_defaults() {
+    let config = Config::from_toml(
+        r#"
+        [server]
+        bind = "0.0.0.0"
+        tls_cert = "cert.pem"
+        tls_key = "key.pem"
+
+        [storage]
+        cache = "cache"
+        "#,
+    )
+    .expect("Failed to parse config");
+    assert_eq!(config.server.bind, "0.0.0.0");
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.server.tls_cert.as_deref(), Some("cert.pem"));
+    assert_eq!(config.storage.cache.as_deref(), Some("cache"));
+    assert_eq!(config.storage.store, "builds");
+}
+
+#[test]
+fn test_env_overrides_config_file() {
+    let config = Config::layered(
+        Some("[server]\nport = 3000\nbind = \"0.0.0.0\"\n"),
+        vars(&[
+            ("BUILD_WARREN_SERVER_PORT", "9000"),
+            ("BUILD_WARREN_SPAWNING_TOOL_REQUESTS_PER_SECOND", "2.5"),
+            ("BUILD_WARREN_STORAGE_CACHE", "/tmp/pages"),
+            ("BUILD_WARREN_LOG_LEVEL", "debug"),
+            ("BUILD_WARREN_CONFIG", "ignored.toml"),
+            ("HOME", "/root"),
+        ]),
+    )
+    .expect("Failed to build config");
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.bind, "0.0.0.0");
+    assert_eq!(config.spawning_tool.requests_per_second, Some(2.5));
+    assert_eq!(config.storage.cache.as_deref(), Some("/tmp/pages"));
+    assert_eq!(config.log.level, LogLevel::Debug);
+}
+
+#[test]
+fn test_env_values_fall_back_to_strings() {
+    let config = Config::layered(
+        None,
+        vars(&[
+            ("BUILD_WARREN_STORAGE_STORE", "2024"),
+            ("BUILD_WARREN_SERVER_BIND", "1"),
+            ("BUILD_WARREN_STORAGE_JOBS", "true"),
+            ("BUILD_WARREN_SERVER_PORT", "9000"),
+        ]),
+    )
+    .expect("Failed to build config");
+    assert_eq!(config.storage.store, "2024");
+    assert_eq!(config.server.bind, "1");
+    assert_eq!(config.storage.jobs, "true");
+    assert_eq!(config.server.port, 9000);
+}
+
+#[test]
+fn test_invalid_config_is_rejected() {
+    assert!(Config::from_toml("[server]\nprot = 80\n").is_err());
+    assert!(Config::from_toml("[server]\nport = \"eighty\"\n").is_err());
+    assert!(Config::layered(None, vars(&[("BUILD_WARREN_SERVER_PORT", "eighty")])).is_err());
+}
+
+#[test]
+fn test_config_to_toml_round_trip() {
+    let mut config = Config::default();
+    config.server.tls_cert = Some("cert.pem".to_string());
+    config.spawning_tool.requests_per_second = Some(4.0);
+    config.log.level = LogLevel::Warn;
+    assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+}
+
+#[test]
+fn test_log_level_from_str() {
+    for level in [
+        LogLevel::Off,
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ] {
+        assert_eq!(LogLevel::from_str(&level.to_string()), Ok(level));
+    }
+    assert_eq!(LogLevel::from_str("WARNING"), Ok(LogLevel::Warn));
+    assert!(LogLevel::Error < LogLevel::Warn);
+    assert!(LogLevel::from_str("loud").is_err());
+}
@@ -0,0 +1,116 @@
+use build_warren::build_order::{BuildOrder, BuildOrderError};
+use build_warren::crawl_state::{CrawlOutcome, CrawlState};
+use std::io::Write;
+
+fn state_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "build_warren_{}_{}.jsonl",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn test_crawl_state_resume() {
+    let path = state_path("crawl_resume");
+    let mut state = CrawlState::create(&path, 10, 17).expect("Failed to create crawl state");
+    assert_eq!(state.pending_ids(), (10..=17).collect::<Vec<_>>());
+
+    state.record(10, CrawlOutcome::Ok).unwrap();
+    state.record(11, CrawlOutcome::Cloaked).unwrap();
+    state.record(12, CrawlOutcome::NotFound).unwrap();
+    state
+        .record(
+            13,
+            CrawlOutcome::Failed {
+                reason: "timeout".to_string(),
+            },
+        )
+        .unwrap();
+    state.record(15, CrawlOutcome::Ok).unwrap();
+    drop(state);
+
+    let mut state = CrawlState::open(&path).expect("Failed to open crawl state");
+    assert_eq!((state.get_start(), state.get_end()), (10, 17));
+    assert_eq!(state.get_outcome(11), Some(&CrawlOutcome::Cloaked));
+    assert_eq!(state.pending_ids(), vec![13, 14, 16, 17]);
+
+    // A later record replaces the failure
+    state.record(13, CrawlOutcome::Ok).unwrap();
+    drop(state);
+    let state = CrawlState::open(&path).unwrap();
+    assert_eq!(state.pending_ids(), vec![14, 16, 17]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_crawl_state_summary() {
+    let path = state_path("crawl_summary");
+    let mut state = CrawlState::create(&path, 1, 10).unwrap();
+    for id in [1, 2, 3, 6] {
+        state.record(id, CrawlOutcome::Ok).unwrap();
+    }
+    state.record(4, CrawlOutcome::Cloaked).unwrap();
+    state
+        .record(
+            7,
+            CrawlOutcome::Failed {
+                reason: "HTTP Error: reset".to_string(),
+            },
+        )
+        .unwrap();
+
+    let summary = state.summary();
+    assert_eq!(summary.total, 10);
+    assert_eq!(summary.ok, 4);
+    assert_eq!(summary.cloaked, 1);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.not_attempted, 4);
+    assert_eq!(summary.progress, 50.0);
+    assert_eq!(summary.gaps, vec![(5, 5), (7, 10)]);
+    assert_eq!(summary.failures[&7], "HTTP Error: reset");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_crawl_state_torn_line() {
+    let path = state_path("crawl_torn");
+    let mut state = CrawlState::create(&path, 1, 3).unwrap();
+    state.record(1, CrawlOutcome::Ok).unwrap();
+    drop(state);
+    // Simulate a crash in the middle of writing a record
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    write!(file, "{{\"id\":2,\"outc").unwrap();
+    drop(file);
+
+    let mut state = CrawlState::open(&path).expect("Torn line should be skipped");
+    assert_eq!(state.pending_ids(), vec![2, 3]);
+    state.record(2, CrawlOutcome::Ok).unwrap();
+    drop(state);
+    let state = CrawlState::open(&path).expect("Records after a torn line should be readable");
+    assert_eq!(state.pending_ids(), vec![3]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_crawl_outcome_from_result() {
+    let outcome = |error: BuildOrderError| CrawlOutcome::from(&Err::<BuildOrder, _>(error));
+    assert_eq!(outcome(BuildOrderError::Cloaked), CrawlOutcome::Cloaked);
+    assert_eq!(outcome(BuildOrderError::NotFound), CrawlOutcome::NotFound);
+    assert_eq!(
+        outcome(BuildOrderError::Redirected {
+            location: "/build/3/".to_string()
+        }),
+        CrawlOutcome::Redirected {
+            location: "/build/3/".to_string()
+        }
+    );
+    let limited = outcome(BuildOrderError::RateLimited { retry_after: None });
+    assert!(!limited.is_complete(), "Rate limited builds are retried");
+}
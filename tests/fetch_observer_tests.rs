@@ -0,0 +1,67 @@
+use build_warren::build_order::BuildOrderError;
+use build_warren::build_parser::{PageCache, parse_build_order};
+use build_warren::fetch_observer::{ChannelObserver, FetchEvent, FetchObserver, notify};
+use build_warren::handlers::{FetchSummary, fetch_ids_with};
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+#[test]
+fn test_notify_dispatches_results() {
+    let (observer, events) = ChannelObserver::new();
+    let build_order = parse_build_order(&open_file("tests/examples/clemvsmaxpax.html"), 193576)
+        .expect("Failed to parse build order");
+
+    let results = [
+        (193576, Ok(build_order)),
+        (7, Err(BuildOrderError::Cloaked)),
+        (8, Err(BuildOrderError::NotFound)),
+    ];
+    let mut summary = FetchSummary::default();
+    observer.started(Some(results.len() as u64));
+    for (id, result) in &results {
+        summary.record(result);
+        notify(&observer, *id, result);
+    }
+    observer.finished(&summary);
+    drop(observer);
+
+    let events: Vec<FetchEvent> = events.iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            FetchEvent::Started { total: Some(3) },
+            FetchEvent::Fetched { id: 193576 },
+            FetchEvent::SkippedCloaked { id: 7 },
+            FetchEvent::Failed {
+                id: 8,
+                error: BuildOrderError::NotFound
+            },
+            FetchEvent::Finished { summary },
+        ]
+    );
+    assert_eq!(summary.fetched, 1);
+    assert_eq!(summary.cloaked, 1);
+    assert_eq!(summary.not_found, 1);
+}
+
+#[test]
+fn test_fetch_ids_reports_empty_run() {
+    let (observer, events) = ChannelObserver::new();
+    let summary = fetch_ids_with(&[], PageCache::Use, &observer, |_, _| {
+        panic!("No build to fetch")
+    });
+    drop(observer);
+
+    assert_eq!(summary, FetchSummary::default());
+    assert_eq!(
+        events.iter().collect::<Vec<_>>(),
+        vec![
+            FetchEvent::Started { total: Some(0) },
+            FetchEvent::Finished {
+                summary: FetchSummary::default()
+            },
+        ]
+    );
+}
@@ -0,0 +1,215 @@
+use async_graphql::{Request, Variables};
+use build_warren::build_order::{Action, ActionType, BuildOrder, BuildType, OrderEntry, Race};
+use build_warren::build_store::BuildStore;
+use build_warren::classifier::OpenerClassifier;
+use build_warren::graphql::{BuildWarrenSchema, Corpus, schema};
+use build_warren::http_client::AsyncHttpClient;
+use chrono::NaiveDate;
+use serde_json::{Value, json};
+
+fn build(id: u32, race: Race, creator: &str, votes: (u32, u32)) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(format!("Build {}", id));
+    build_order.set_player_race(race);
+    build_order.set_opponent_race(Race::Protoss);
+    build_order.set_build_type(BuildType::Economic);
+    build_order.set_creator(creator.to_string());
+    build_order.set_patch("5.0.14".to_string());
+    build_order.set_published(NaiveDate::from_ymd_opt(2025, 6, id).unwrap());
+    build_order.set_votes(votes.0, votes.1);
+    build_order.add_step(OrderEntry::new(
+        12,
+        "0:12".to_string(),
+        vec![Action::new(ActionType::Worker, "Probe".to_string())],
+        String::new(),
+    ));
+    build_order.add_step(OrderEntry::new(
+        14,
+        "1:05".to_string(),
+        vec![Action::new(ActionType::Building, "Pylon".to_string())],
+        "wall".to_string(),
+    ));
+    build_order
+}
+
+fn temp_store(name: &str) -> BuildStore {
+    let dir = std::env::temp_dir().join(format!("build_warren_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = BuildStore::open(&dir).expect("Failed to open store");
+    for build_order in [
+        build(1, Race::Protoss, "harstem", (90, 10)),
+        build(2, Race::Zerg, "lambo", (50, 4)),
+        build(3, Race::Protoss, "harstem", (70, 6)),
+        build(4, Race::Protoss, "MaxPax", (80, 2)),
+    ] {
+        store.save(&build_order).unwrap();
+    }
+    store
+}
+
+async fn execute(schema: &BuildWarrenSchema, store: &BuildStore, query: &str) -> Value {
+    execute_with(schema, store, query, json!({})).await
+}
+
+async fn execute_with(
+    schema: &BuildWarrenSchema,
+    store: &BuildStore,
+    query: &str,
+    variables: Value,
+) -> Value {
+    let request = Request::new(query)
+        .variables(Variables::from_json(variables))
+        .data(Corpus::new(store.load_all()));
+    serde_json::to_value(schema.execute(request).await).unwrap()
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response["data"]["builds"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"]["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_builds_are_filtered_and_nested() {
+    let store = temp_store("graphql_filter");
+    let schema = schema(OpenerClassifier::default(), AsyncHttpClient::new());
+
+    let response = execute(
+        &schema,
+        &store,
+        r#"{
+            builds(filter: {race: PROTOSS, minVotes: 5}) {
+                totalCount
+                edges { node { id matchup creator { name buildCount } } }
+            }
+        }"#,
+    )
+    .await;
+    assert_eq!(response["data"]["builds"]["totalCount"], 2);
+    assert_eq!(ids(&response), [1, 3]);
+    assert_eq!(
+        response["data"]["builds"]["edges"][0]["node"]["creator"],
+        json!({"name": "harstem", "buildCount": 2})
+    );
+    assert_eq!(
+        response["data"]["builds"]["edges"][0]["node"]["matchup"],
+        "PvP"
+    );
+
+    let response = execute(
+        &schema,
+        &store,
+        r#"{
+            build(id: 2) { name entries(limit: 1) { supply time seconds actions { name } } }
+            creator(name: "HARSTEM") { name }
+            matchups { name buildCount }
+        }"#,
+    )
+    .await;
+    assert_eq!(
+        response["data"]["build"],
+        json!({
+            "name": "Build 2",
+            "entries": [{"supply": 12, "time": "0:12", "seconds": 12, "actions": [{"name": "Probe"}]}]
+        })
+    );
+    assert_eq!(response["data"]["creator"]["name"], "harstem");
+    assert_eq!(
+        response["data"]["matchups"],
+        json!([{"name": "PvP", "buildCount": 3}, {"name": "ZvP", "buildCount": 1}])
+    );
+}
+
+#[actix_web::test]
+async fn test_builds_are_paginated() {
+    let store = temp_store("graphql_pages");
+    let schema = schema(OpenerClassifier::default(), AsyncHttpClient::new());
+    let query = r#"query Page($after: String) {
+        builds(first: 2, after: $after) {
+            pageInfo { hasNextPage endCursor }
+            edges { node { id } }
+        }
+    }"#;
+
+    let first = execute(&schema, &store, query).await;
+    assert_eq!(ids(&first), [1, 2]);
+    assert_eq!(first["data"]["builds"]["pageInfo"]["hasNextPage"], true);
+    let cursor = first["data"]["builds"]["pageInfo"]["endCursor"].clone();
+
+    let second = execute_with(&schema, &store, query, json!({"after": cursor})).await;
+    assert_eq!(ids(&second), [3, 4]);
+    assert_eq!(second["data"]["builds"]["pageInfo"]["hasNextPage"], false);
+
+    let last = execute(
+        &schema,
+        &store,
+        "{ builds(last: 1) { edges { node { id } } } }",
+    )
+    .await;
+    assert_eq!(ids(&last), [4]);
+    let too_many = execute(&schema, &store, "{ builds(first: 500) { totalCount } }").await;
+    assert!(
+        too_many["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("at most")
+    );
+}
+
+#[actix_web::test]
+async fn test_expensive_queries_are_rejected() {
+    let store = temp_store("graphql_limits");
+    let schema = schema(OpenerClassifier::default(), AsyncHttpClient::new());
+
+    let mut nested = "id".to_string();
+    for _ in 0..4 {
+        nested = format!(
+            "creator {{ builds {{ edges {{ node {{ {} }} }} }} }}",
+            nested
+        );
+    }
+    let response = execute(
+        &schema,
+        &store,
+        &format!("{{ builds {{ edges {{ node {{ {} }} }} }} }}", nested),
+    )
+    .await;
+    assert_eq!(response["data"], Value::Null);
+    assert!(!response["errors"].as_array().unwrap().is_empty());
+
+    let response = execute(
+        &schema,
+        &store,
+        "{ builds(first: 100) { edges { node { entries(limit: 500) { actions { name } } } } } }",
+    )
+    .await;
+    assert_eq!(response["errors"][0]["message"], "Query is too complex.");
+}
+
+#[actix_web::test]
+async fn test_stats_over_the_collection() {
+    let store = temp_store("graphql_stats");
+    let schema = schema(OpenerClassifier::default(), AsyncHttpClient::new());
+
+    let response = execute(
+        &schema,
+        &store,
+        "{ stats(minVotes: 5) { total byMatchup voteLeaders { id score count } } }",
+    )
+    .await;
+    assert_eq!(response["errors"], Value::Null, "{}", response);
+    let stats = &response["data"]["stats"];
+    assert_eq!(stats["total"], 4);
+    assert_eq!(stats["byMatchup"], json!({"PvP": 3, "ZvP": 1}));
+    let leaders: Vec<u64> = stats["voteLeaders"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|leader| leader["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(leaders, [1, 3]);
+}
@@ -0,0 +1,199 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, VARY};
+use actix_web::middleware::from_fn;
+use actix_web::test::{TestRequest, call_service, init_service, read_body};
+use actix_web::{App, HttpResponse, web};
+use build_warren::api::error_status;
+use build_warren::build_order::BuildOrderError;
+use build_warren::build_parser::parse_build_order;
+use build_warren::config::ServerConfig;
+use build_warren::http_cache::{
+    ServedTags, VARY_BY_KEY, cache_control, conditional_get, etag, etag_matches,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+#[test]
+fn test_etags_follow_content() {
+    let html_content = open_file("tests/examples/clemvsmaxpax.html");
+    let build_order = parse_build_order(&html_content, 193576).unwrap();
+    let body = serde_json::to_vec(&build_order).unwrap();
+
+    let tag = etag(&body);
+    assert!(tag.starts_with("W/\"") && tag.ends_with('"'));
+    assert_eq!(
+        tag,
+        etag(&serde_json::to_vec(&build_order.clone()).unwrap())
+    );
+    assert_ne!(tag, etag(b"{}"));
+
+    assert!(etag_matches(&tag, &tag));
+    assert!(etag_matches(&format!("\"other\", {}", tag), &tag));
+    assert!(etag_matches(tag.trim_start_matches("W/"), &tag));
+    assert!(etag_matches("*", &tag));
+    assert!(!etag_matches("\"other\"", &tag));
+}
+
+#[test]
+fn test_cache_control_per_route() {
+    let config = ServerConfig {
+        build_max_age: 3600,
+        listing_max_age: 30,
+        ..ServerConfig::default()
+    };
+    assert_eq!(
+        cache_control("/build/{id}", &config).unwrap(),
+        "public, max-age=3600"
+    );
+    assert_eq!(
+        cache_control("/latest/{count}", &config).unwrap(),
+        "public, max-age=30"
+    );
+    assert_eq!(
+        cache_control("/count", &config).unwrap(),
+        "public, max-age=30"
+    );
+    assert_eq!(cache_control("/jobs/{id}", &config).unwrap(), "no-cache");
+    assert_eq!(
+        cache_control("/collections/{name}", &config).unwrap(),
+        "private, no-cache"
+    );
+    assert_eq!(cache_control("/openapi.json", &config), None);
+}
+
+#[actix_web::test]
+async fn test_conditional_get() {
+    let app = init_service(
+        App::new()
+            .wrap(from_fn(conditional_get))
+            .route(
+                "/build/{id}",
+                web::get().to(|| async { HttpResponse::Ok().json([1, 2, 3]) }),
+            )
+            .route(
+                "/jobs",
+                web::post().to(|| async { HttpResponse::Ok().json([1]) }),
+            )
+            .route(
+                "/stats",
+                web::get().to(|| async { HttpResponse::NotFound().finish() }),
+            )
+            .route(
+                "/diff/{a}/{b}",
+                web::get().to(|| async {
+                    let error = BuildOrderError::HttpError("timed out".to_string());
+                    HttpResponse::build(error_status(&error)).json(Err::<(), _>(error))
+                }),
+            ),
+    )
+    .await;
+
+    let response = call_service(&app, TestRequest::get().uri("/build/1").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tag = response.headers().get(ETAG).unwrap().clone();
+    assert_eq!(
+        response.headers().get(CACHE_CONTROL).unwrap(),
+        &format!("public, max-age={}", ServerConfig::default().build_max_age)
+    );
+    assert_eq!(response.headers().get(VARY).unwrap(), VARY_BY_KEY);
+    assert_eq!(read_body(response).await, "[1,2,3]");
+
+    let request = TestRequest::get()
+        .uri("/build/1")
+        .insert_header((IF_NONE_MATCH, tag.clone()))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG), Some(&tag));
+    assert!(read_body(response).await.is_empty());
+
+    let request = TestRequest::get()
+        .uri("/build/1")
+        .insert_header((IF_NONE_MATCH, "W/\"stale\""))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Only successful reads are tagged
+    let response = call_service(&app, TestRequest::post().uri("/jobs").to_request()).await;
+    assert!(response.headers().get(ETAG).is_none());
+    let response = call_service(&app, TestRequest::get().uri("/stats").to_request()).await;
+    assert!(response.headers().get(ETAG).is_none());
+    // Failed fetches are never kept by caches
+    let response = call_service(&app, TestRequest::get().uri("/diff/1/2").to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert!(response.headers().get(CACHE_CONTROL).is_none());
+}
+
+#[actix_web::test]
+async fn test_fresh_tags_skip_the_handler() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&fetches);
+    let app = init_service(
+        App::new()
+            .wrap(from_fn(conditional_get))
+            .app_data(web::Data::new(ServedTags::default()))
+            .route(
+                "/build/{id}",
+                web::get().to(move || {
+                    let fetches = fetches.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { HttpResponse::Ok().json([fetches]) }
+                }),
+            ),
+    )
+    .await;
+
+    let response = call_service(&app, TestRequest::get().uri("/build/1").to_request()).await;
+    let tag = response.headers().get(ETAG).unwrap().clone();
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    // Revalidating is answered from the tag served before, without fetching
+    let request = TestRequest::get()
+        .uri("/build/1")
+        .insert_header((IF_NONE_MATCH, tag.clone()))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG), Some(&tag));
+    assert_eq!(response.headers().get(VARY).unwrap(), VARY_BY_KEY);
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    // Other tags and other paths still run the handler
+    let request = TestRequest::get()
+        .uri("/build/1")
+        .insert_header((IF_NONE_MATCH, "W/\"stale\""))
+        .to_request();
+    assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::get()
+        .uri("/build/2")
+        .insert_header((IF_NONE_MATCH, tag))
+        .to_request();
+    assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_error_statuses() {
+    assert_eq!(
+        error_status(&BuildOrderError::NotFound),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        error_status(&BuildOrderError::Cloaked),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        error_status(&BuildOrderError::RateLimited {
+            retry_after: Some(60)
+        }),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert_eq!(
+        error_status(&BuildOrderError::ParseError("no steps".to_string())),
+        StatusCode::BAD_GATEWAY
+    );
+}
@@ -1,4 +1,5 @@
-use build_warren::index_manager::get_highest_index;
+use build_warren::http_client::AsyncHttpClient;
+use build_warren::index_manager::{get_highest_index, get_highest_index_async};
 use mockito::Server;
 
 #[test]
@@ -29,3 +30,18 @@ fn test_get_highest_index() {
         highest_index
     );
 }
+
+#[actix_web::test]
+async fn test_get_highest_index_async() {
+    let mut server = Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/build/")
+        .with_status(200)
+        .with_body(r#"<tbody><tr><td><a href="/build/193844/">Build 193844</a></td></tr></tbody>"#)
+        .create_async()
+        .await;
+
+    let url = server.url() + "/build/";
+    let client = AsyncHttpClient::new();
+    assert_eq!(get_highest_index_async(&client, &url).await, 193844);
+}
@@ -0,0 +1,145 @@
+use build_warren::build_parser::parse_build_order;
+use build_warren::build_store::BuildStore;
+use build_warren::jobs::{JobKind, JobManager, JobStatus};
+use build_warren::output::{OutputFormat, render_builds};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("build_warren_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_job_kind_from_json() {
+    let kind: JobKind = serde_json::from_str(r#"{"kind": "fetch_segment", "start": 5, "end": 10}"#)
+        .expect("Failed to parse job kind");
+    assert_eq!(kind, JobKind::FetchSegment { start: 5, end: 10 });
+    let kind: JobKind = serde_json::from_str(r#"{"kind": "fetch_latest", "count": 3}"#).unwrap();
+    assert_eq!(kind, JobKind::FetchLatest { count: 3 });
+}
+
+#[test]
+fn test_jobs_are_cancelled_and_persisted() {
+    let dir = temp_dir("jobs_persist");
+    let store = BuildStore::open(dir.join("builds")).unwrap();
+
+    // Without workers, submitted jobs stay queued
+    let jobs = JobManager::open(dir.join("jobs"), store.clone(), 0).unwrap();
+    let first = jobs
+        .submit(JobKind::FetchSegment { start: 5, end: 10 }, None)
+        .unwrap();
+    let second = jobs
+        .submit(
+            JobKind::Sync { start: 5, end: 10 },
+            Some("site".to_string()),
+        )
+        .unwrap();
+    assert_eq!(first.get_status(), JobStatus::Queued);
+    assert_eq!(second.get_id(), first.get_id() + 1);
+
+    let cancelled = jobs.cancel(first.get_id()).unwrap().unwrap();
+    assert_eq!(cancelled.get_status(), JobStatus::Cancelled);
+    assert!(cancelled.get_finished().is_some());
+    assert!(jobs.cancel(99).unwrap().is_none());
+    drop(jobs);
+
+    // Jobs are saved whole, and a damaged job file is skipped
+    let names: Vec<String> = std::fs::read_dir(dir.join("jobs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert!(names.iter().all(|name| !name.ends_with(".tmp")));
+    std::fs::write(dir.join("jobs").join("7.json"), "{\"id\": 7").unwrap();
+    let jobs = JobManager::open(dir.join("jobs"), store, 0).unwrap();
+    assert_eq!(jobs.list().len(), 2);
+    assert_eq!(
+        jobs.get(first.get_id()).unwrap().get_status(),
+        JobStatus::Cancelled
+    );
+    assert_eq!(
+        jobs.get(second.get_id()).unwrap().get_status(),
+        JobStatus::Queued
+    );
+    assert_eq!(jobs.get(second.get_id()).unwrap().get_owner(), Some("site"));
+    assert_eq!(jobs.get(first.get_id()).unwrap().get_owner(), None);
+    let third = jobs
+        .submit(JobKind::FetchLatest { count: 1 }, None)
+        .unwrap();
+    assert_eq!(third.get_id(), second.get_id() + 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_empty_segment_job_completes() {
+    let dir = temp_dir("jobs_empty");
+    let store = BuildStore::open(dir.join("builds")).unwrap();
+    let jobs = JobManager::open(dir.join("jobs"), store, 1).unwrap();
+    let job = jobs
+        .submit(JobKind::FetchSegment { start: 10, end: 5 }, None)
+        .unwrap();
+
+    let mut status = job.get_status();
+    for _ in 0..100 {
+        status = jobs.get(job.get_id()).unwrap().get_status();
+        if status.is_finished() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(status, JobStatus::Completed);
+    let job = jobs.get(job.get_id()).unwrap();
+    assert_eq!(job.get_total(), Some(0));
+    assert_eq!(job.get_checked(), 0);
+    assert_eq!(jobs.results(job.get_id(), 0, 10).unwrap().unwrap().total, 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_job_results_are_paged() {
+    let dir = temp_dir("jobs_results");
+    let store = BuildStore::open(dir.join("builds")).unwrap();
+    let jobs = JobManager::open(dir.join("jobs"), store, 0).unwrap();
+    let job = jobs
+        .submit(JobKind::FetchSegment { start: 5, end: 10 }, None)
+        .unwrap();
+
+    let build_orders = vec![
+        parse_build_order(&open_file("tests/examples/clemvsmaxpax.html"), 193576).unwrap(),
+        parse_build_order(&open_file("tests/examples/oldestbuild.html"), 5).unwrap(),
+    ];
+    std::fs::write(
+        jobs.get_root().join(format!("{}.ndjson", job.get_id())),
+        render_builds(&build_orders, OutputFormat::Ndjson),
+    )
+    .unwrap();
+
+    let page = jobs.results(job.get_id(), 1, 10).unwrap().unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.offset, 1);
+    assert_eq!(page.build_orders.len(), 1);
+    assert_eq!(page.build_orders[0].get_id(), 5);
+    // Results without an index are indexed on first read
+    let index = jobs.get_root().join(format!("{}.index", job.get_id()));
+    assert_eq!(std::fs::metadata(&index).unwrap().len(), 16);
+    let page = jobs.results(job.get_id(), 0, 1).unwrap().unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.build_orders.len(), 1);
+    assert_eq!(page.build_orders[0].get_id(), 193576);
+    assert!(
+        jobs.results(job.get_id(), 5, 10)
+            .unwrap()
+            .unwrap()
+            .build_orders
+            .is_empty()
+    );
+    assert!(jobs.results(99, 0, 10).unwrap().is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
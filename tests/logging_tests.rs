@@ -0,0 +1,44 @@
+use build_warren::config::{Config, LogFormat, LogLevel};
+use build_warren::logging::filter_directive;
+use std::str::FromStr;
+
+#[test]
+fn test_log_level_shifted_by_verbosity_flags() {
+    assert_eq!(LogLevel::Info.shifted(1), LogLevel::Debug);
+    assert_eq!(LogLevel::Info.shifted(2), LogLevel::Trace);
+    assert_eq!(LogLevel::Info.shifted(5), LogLevel::Trace);
+    assert_eq!(LogLevel::Info.shifted(-1), LogLevel::Warn);
+    assert_eq!(LogLevel::Info.shifted(-2), LogLevel::Error);
+    assert_eq!(LogLevel::Info.shifted(-5), LogLevel::Off);
+    assert_eq!(LogLevel::Warn.shifted(0), LogLevel::Warn);
+}
+
+#[test]
+fn test_log_format_from_str() {
+    assert_eq!(LogFormat::from_str("text"), Ok(LogFormat::Text));
+    assert_eq!(LogFormat::from_str("JSON"), Ok(LogFormat::Json));
+    assert!(LogFormat::from_str("xml").is_err());
+    assert_eq!(LogFormat::Json.to_string(), "json");
+}
+
+#[test]
+fn test_log_format_from_config_and_environment() {
+    let config = Config::from_toml("[log]\nformat = \"json\"\n").expect("Failed to parse config");
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert_eq!(config.log.level, LogLevel::Info);
+
+    let config = Config::layered(
+        None,
+        vec![("BUILD_WARREN_LOG_FORMAT".to_string(), "json".to_string())],
+    )
+    .expect("Failed to build config");
+    assert_eq!(config.log.format, LogFormat::Json);
+}
+
+#[test]
+fn test_filter_directive_limits_dependencies_to_warnings() {
+    assert_eq!(filter_directive(LogLevel::Off), "off");
+    assert_eq!(filter_directive(LogLevel::Error), "error");
+    assert_eq!(filter_directive(LogLevel::Warn), "warn,build_warren=warn");
+    assert_eq!(filter_directive(LogLevel::Debug), "warn,build_warren=debug");
+}
@@ -0,0 +1,94 @@
+use build_warren::build_order::BuildOrderError;
+use build_warren::build_parser::{fetch_build_order_from, parse_build_order};
+use build_warren::index_manager::get_highest_index;
+use build_warren::metrics::{fetch_outcome, record_cache_lookup, render};
+use mockito::Server;
+
+/// Value of a sample of the rendered metrics, such as `name{label="value"}`.
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        let (name, value) = line.rsplit_once(' ')?;
+        (name == series).then(|| value.parse().unwrap())
+    })
+}
+
+#[test]
+fn test_fetch_outcome_labels() {
+    assert_eq!(fetch_outcome(&Ok::<(), _>(())), "ok");
+    assert_eq!(
+        fetch_outcome::<()>(&Err(BuildOrderError::Cloaked)),
+        "cloaked"
+    );
+    assert_eq!(
+        fetch_outcome::<()>(&Err(BuildOrderError::RateLimited { retry_after: None })),
+        "rate_limited"
+    );
+    assert_eq!(
+        fetch_outcome::<()>(&Err(BuildOrderError::HttpError("timeout".to_string()))),
+        "error"
+    );
+}
+
+#[test]
+fn test_parse_failures_are_counted_by_field() {
+    let result = parse_build_order("<html><body>Not a build</body></html>", 1);
+    assert!(matches!(result, Err(BuildOrderError::ParseError(_))));
+    let metrics = render();
+    assert!(
+        sample(
+            &metrics,
+            r#"build_warren_parse_failures_total{field="header"}"#
+        )
+        .unwrap()
+            >= 1.0
+    );
+}
+
+#[test]
+fn test_upstream_fetches_are_counted_by_outcome() {
+    let mut server = Server::new();
+    let _missing = server.mock("GET", "/build/7/").with_status(404).create();
+    let _cloaked = server
+        .mock("GET", "/build/8/")
+        .with_status(302)
+        .with_header("Location", "/accounts/login/?next=/build/8/")
+        .create();
+    let url = server.url() + "/build/";
+
+    assert!(fetch_build_order_from(&url, 7).is_err());
+    assert!(fetch_build_order_from(&url, 8).is_err());
+    let metrics = render();
+    let count = |outcome: &str| {
+        sample(
+            &metrics,
+            &format!(
+                r#"build_warren_upstream_fetches_total{{outcome="{}"}}"#,
+                outcome
+            ),
+        )
+        .unwrap_or(0.0)
+    };
+    assert!(count("not_found") >= 1.0);
+    assert!(count("cloaked") >= 1.0);
+}
+
+#[test]
+fn test_highest_index_and_cache_ratio() {
+    let mut server = Server::new();
+    let _mock = server
+        .mock("GET", "/build/")
+        .with_status(200)
+        .with_body(r#"<tbody><tr><td><a href="/build/193844/">Build 193844</a></td></tr></tbody>"#)
+        .create();
+    assert_eq!(get_highest_index(&(server.url() + "/build/")), 193844);
+
+    record_cache_lookup(true);
+    record_cache_lookup(false);
+    let metrics = render();
+    assert_eq!(
+        sample(&metrics, "build_warren_highest_index"),
+        Some(193844.0)
+    );
+    let ratio = sample(&metrics, "build_warren_page_cache_hit_ratio").unwrap();
+    assert!(ratio > 0.0 && ratio < 1.0);
+}
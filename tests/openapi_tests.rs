@@ -0,0 +1,89 @@
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{TestRequest, call_service, init_service};
+use actix_web::{App, HttpResponse, web};
+use build_warren::api::{ApiDoc, routes};
+use build_warren::build_parser::parse_build_order;
+use std::collections::BTreeSet;
+use utoipa::OpenApi;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Operations of the OpenAPI document, as (method, path).
+fn documented_routes() -> BTreeSet<(String, String)> {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let mut routes = BTreeSet::new();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for method in METHODS {
+            if item.get(method).is_some() {
+                routes.insert((method.to_string(), path.clone()));
+            }
+        }
+    }
+    routes
+}
+
+/// A path matching `pattern`, with `1` for every parameter.
+fn sample_path(pattern: &str) -> String {
+    let mut path = String::new();
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').expect("Unclosed parameter") + open;
+        path.push_str(&rest[..open]);
+        path.push('1');
+        rest = &rest[close + 1..];
+    }
+    path + rest
+}
+
+#[actix_web::test]
+async fn test_documented_routes_are_served() {
+    // Requests no route matches get a teapot, anything else reached a handler
+    let app = init_service(
+        App::new()
+            .configure(routes)
+            .default_service(web::to(HttpResponse::ImATeapot)),
+    )
+    .await;
+    let documented = documented_routes();
+    assert!(documented.len() > 10, "Failed to find the routes");
+    for (method, path) in documented {
+        let uri = sample_path(&path);
+        let request = TestRequest::default()
+            .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+            .uri(&uri)
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_ne!(
+            response.status(),
+            StatusCode::IM_A_TEAPOT,
+            "{} {} is documented but not served",
+            method,
+            path
+        );
+    }
+    let response = call_service(&app, TestRequest::get().uri("/undocumented").to_request()).await;
+    assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+}
+
+#[test]
+fn test_build_order_schema_matches_json() {
+    let build_order = parse_build_order(
+        &std::fs::read_to_string("tests/examples/clemvsmaxpax.html").unwrap(),
+        193576,
+    )
+    .unwrap();
+    let json = serde_json::to_value(&build_order).unwrap();
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let schemas = &spec["components"]["schemas"];
+
+    let keys = |value: &serde_json::Value| -> BTreeSet<String> {
+        value.as_object().unwrap().keys().cloned().collect()
+    };
+    assert_eq!(keys(&schemas["BuildOrder"]["properties"]), keys(&json));
+    let entry = &json["entries"][0];
+    assert_eq!(keys(&schemas["OrderEntry"]["properties"]), keys(entry));
+    assert_eq!(
+        keys(&schemas["Action"]["properties"]),
+        keys(&entry["actions"][0])
+    );
+}
@@ -0,0 +1,211 @@
+use build_warren::build_order::BuildOrder;
+use build_warren::build_parser::parse_build_order;
+use build_warren::output::{BuildWriter, OutputFormat, PartFile, render_build, render_builds};
+use build_warren::text_notation::parse_text_build;
+use std::str::FromStr;
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+fn example_builds() -> Vec<BuildOrder> {
+    vec![
+        parse_build_order(&open_file("tests/examples/clemvsmaxpax.html"), 193576)
+            .expect("Failed to parse build order"),
+        parse_build_order(&open_file("tests/examples/oldestbuild.html"), 5)
+            .expect("Failed to parse build order"),
+    ]
+}
+
+fn step_count(build_orders: &[BuildOrder]) -> usize {
+    build_orders.iter().map(|b| b.get_entries().len()).sum()
+}
+
+#[test]
+fn test_output_format_from_str() {
+    for format in [
+        OutputFormat::Json,
+        OutputFormat::Ndjson,
+        OutputFormat::Yaml,
+        OutputFormat::Csv,
+        OutputFormat::Markdown,
+        OutputFormat::Text,
+        OutputFormat::Salt,
+    ] {
+        assert_eq!(OutputFormat::from_str(&format.to_string()), Ok(format));
+    }
+    assert_eq!(OutputFormat::from_str("md"), Ok(OutputFormat::Markdown));
+    assert!(OutputFormat::from_str("xml").is_err());
+}
+
+#[test]
+fn test_render_json_and_ndjson() {
+    let build_orders = example_builds();
+    let json = render_builds(&build_orders, OutputFormat::Json);
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, serde_json::to_value(&build_orders).unwrap());
+
+    let ndjson = render_builds(&build_orders, OutputFormat::Ndjson);
+    let lines: Vec<&str> = ndjson.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(first, serde_json::to_value(&build_orders[0]).unwrap());
+}
+
+#[test]
+fn test_render_yaml() {
+    let build_orders = example_builds();
+    let yaml = render_build(&build_orders[0], OutputFormat::Yaml);
+    let parsed: BuildOrder = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(
+        serde_json::to_value(&parsed).unwrap(),
+        serde_json::to_value(&build_orders[0]).unwrap()
+    );
+}
+
+#[test]
+fn test_render_csv() {
+    let build_orders = example_builds();
+    let csv = render_builds(&build_orders, OutputFormat::Csv);
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    assert_eq!(&headers[0], "build_id");
+    assert_eq!(&headers[1], "build_name");
+
+    let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+    assert_eq!(rows.len(), step_count(&build_orders));
+    assert_eq!(&rows[0][0], "193576");
+    assert_eq!(&rows[0][1], build_orders[0].get_name());
+    assert_eq!(&rows[0][3], "1");
+    assert_eq!(
+        &rows[0][4],
+        build_orders[0].get_entries()[0].get_supply().to_string()
+    );
+    assert_eq!(&rows.last().unwrap()[0], "5");
+}
+
+#[test]
+fn test_render_markdown() {
+    let build_orders = example_builds();
+    let markdown = render_build(&build_orders[0], OutputFormat::Markdown);
+    assert!(markdown.starts_with(&format!("## {} (#193576)", build_orders[0].get_name())));
+    let table_rows = markdown
+        .lines()
+        .filter(|line| line.starts_with("| "))
+        .count();
+    // Header and separator rows plus one row per step
+    assert_eq!(table_rows, build_orders[0].get_entries().len() + 2);
+}
+
+#[test]
+fn test_render_text_round_trip() {
+    for build_order in example_builds() {
+        let text = render_build(&build_order, OutputFormat::Text);
+        let text = console::strip_ansi_codes(&text);
+        let parsed = parse_text_build(&text).expect("Failed to parse text output");
+
+        assert_eq!(parsed.get_name(), build_order.get_name());
+        assert_eq!(parsed.get_creator(), build_order.get_creator());
+        assert_eq!(parsed.get_player_race(), build_order.get_player_race());
+        assert_eq!(parsed.get_opponent_race(), build_order.get_opponent_race());
+        assert_eq!(parsed.get_build_type(), build_order.get_build_type());
+        assert_eq!(parsed.get_votes(), build_order.get_votes());
+        assert_eq!(parsed.get_published(), build_order.get_published());
+        assert_eq!(parsed.get_entries().len(), build_order.get_entries().len());
+        for (parsed, original) in parsed.get_entries().iter().zip(build_order.get_entries()) {
+            assert_eq!(parsed.get_supply(), original.get_supply());
+            assert_eq!(parsed.get_timestamp(), original.get_timestamp());
+            assert_eq!(parsed.get_comment(), original.get_comment());
+            let names = |entry: &build_warren::build_order::OrderEntry| {
+                entry
+                    .get_actions()
+                    .iter()
+                    .map(|a| a.get_name().trim().to_string())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(names(parsed), names(original));
+        }
+    }
+}
+
+#[test]
+fn test_render_salt() {
+    let build_orders = example_builds();
+    let salt = render_builds(&build_orders, OutputFormat::Salt);
+    assert_eq!(salt.lines().count(), 2);
+    assert!(salt.lines().all(|line| line.starts_with('$')));
+}
+
+#[test]
+fn test_build_writer_matches_collected_output() {
+    let build_orders = example_builds();
+    let json = render_builds(&build_orders, OutputFormat::Json);
+    assert_eq!(
+        json,
+        serde_json::to_string_pretty(&build_orders).unwrap() + "\n"
+    );
+    assert_eq!(render_builds(&[], OutputFormat::Json), "[]\n");
+    assert_eq!(
+        render_builds(&[], OutputFormat::Csv).lines().count(),
+        1,
+        "An empty CSV keeps its header"
+    );
+
+    let yaml = render_builds(&build_orders, OutputFormat::Yaml);
+    let parsed: Vec<BuildOrder> = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(
+        serde_json::to_value(&parsed).unwrap(),
+        serde_json::to_value(&build_orders).unwrap()
+    );
+}
+
+#[test]
+fn test_build_writer_part_file() {
+    let build_orders = example_builds();
+    let dir = std::env::temp_dir().join(format!("build_warren_output_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("builds.ndjson");
+
+    let part_file = PartFile::create(&path).unwrap();
+    let part_path = part_file.get_part_path().to_path_buf();
+    let mut writer = BuildWriter::new(part_file, OutputFormat::Ndjson);
+    writer.write(&build_orders[0]).unwrap();
+
+    // Each build is on disk as soon as it is written, under the part name only
+    assert!(!path.exists());
+    let partial = std::fs::read_to_string(&part_path).unwrap();
+    assert_eq!(partial.lines().count(), 1);
+
+    writer.write(&build_orders[1]).unwrap();
+    assert_eq!(writer.get_count(), 2);
+    writer.finish().unwrap().commit().unwrap();
+
+    assert!(!part_path.exists());
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(content, render_builds(&build_orders, OutputFormat::Ndjson));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_build_writer_resumes_part_file() {
+    let build_orders = example_builds();
+    let dir = std::env::temp_dir().join(format!("build_warren_resume_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("builds.csv");
+
+    // An interrupted run leaves its part file behind
+    let mut writer = BuildWriter::new(PartFile::create(&path).unwrap(), OutputFormat::Csv);
+    writer.write(&build_orders[0]).unwrap();
+    drop(writer);
+
+    let part_file = PartFile::append(&path).unwrap();
+    assert!(part_file.has_content().unwrap());
+    let mut writer = BuildWriter::appending(part_file, OutputFormat::Csv);
+    writer.write(&build_orders[1]).unwrap();
+    writer.finish().unwrap().commit().unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(content, render_builds(&build_orders, OutputFormat::Csv));
+    assert!(!OutputFormat::Json.is_appendable());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
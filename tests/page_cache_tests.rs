@@ -0,0 +1,56 @@
+use build_warren::build_parser::{PageCache, fetch_build_order, fetch_build_order_with};
+use build_warren::config::{self, Config};
+use mockito::Server;
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+#[test]
+fn test_page_cache() {
+    let mut server = Server::new();
+    let cache =
+        std::env::temp_dir().join(format!("build_warren_page_cache_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache);
+    let mut config = Config::default();
+    config.spawning_tool.build_url = server.url() + "/build/";
+    config.storage.cache = Some(cache.to_string_lossy().to_string());
+    assert!(config::install(config));
+
+    let page = server
+        .mock("GET", "/build/193576/")
+        .with_status(200)
+        .with_body(open_file("tests/examples/clemvsmaxpax.html"))
+        .expect(3)
+        .create();
+    let broken = server
+        .mock("GET", "/build/7/")
+        .with_status(200)
+        .with_body("<html>Maintenance</html>")
+        .expect(2)
+        .create();
+
+    // Pages that do not parse are not cached
+    assert!(fetch_build_order(7).is_err());
+    assert!(fetch_build_order(7).is_err());
+    broken.assert();
+    assert!(!cache.join("7.html").exists());
+
+    let path = cache.join("193576.html");
+    assert_eq!(fetch_build_order(193576).unwrap().get_id(), 193576);
+    assert!(path.exists());
+    assert_eq!(fetch_build_order(193576).unwrap().get_id(), 193576);
+    // Refreshing skips the cache
+    fetch_build_order_with(193576, PageCache::Refresh).unwrap();
+    // A damaged page is fetched again
+    std::fs::write(&path, "<html>").unwrap();
+    assert_eq!(fetch_build_order(193576).unwrap().get_id(), 193576);
+    page.assert();
+
+    let names: Vec<String> = std::fs::read_dir(&cache)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names, ["193576.html"]);
+    std::fs::remove_dir_all(&cache).unwrap();
+}
@@ -0,0 +1,59 @@
+use build_warren::build_parser::parse_build_order;
+use build_warren::projection::{FieldSet, build_fields, project, project_all};
+use serde_json::json;
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+#[test]
+fn test_field_set_from_str() {
+    let fields: FieldSet = "id, name,votes,,id"
+        .parse()
+        .expect("Failed to parse fields");
+    assert_eq!(fields.get_fields(), ["id", "name", "votes"]);
+    assert_eq!(fields.to_string(), "id,name,votes");
+    assert!(
+        "id,steps"
+            .parse::<FieldSet>()
+            .unwrap_err()
+            .contains("steps")
+    );
+    assert!("".parse::<FieldSet>().is_err());
+
+    // Query strings are deserialized through the same checks
+    let fields: FieldSet = serde_json::from_value(json!("player_race")).unwrap();
+    assert_eq!(fields.get_fields(), ["player_race"]);
+    assert!(serde_json::from_value::<FieldSet>(json!("race")).is_err());
+}
+
+#[test]
+fn test_build_fields_match_serialized_build() {
+    let fields = build_fields();
+    for field in ["id", "name", "player_race", "votes", "entries"] {
+        assert!(fields.contains(&field.to_string()), "Missing {}", field);
+    }
+}
+
+#[test]
+fn test_project_keeps_requested_fields() {
+    let html_content = open_file("tests/examples/clemvsmaxpax.html");
+    let build_order = parse_build_order(&html_content, 193576).unwrap();
+    let fields: FieldSet = "id,name,player_race".parse().unwrap();
+
+    let projected = project(&build_order, Some(&fields));
+    assert_eq!(
+        projected,
+        json!({"id": 193576, "name": "Clem 3reapers 2hellions TvP", "player_race": "Terran"})
+    );
+    let full = project(&build_order, None);
+    assert_eq!(full, serde_json::to_value(&build_order).unwrap());
+    assert!(
+        serde_json::to_string(&projected).unwrap().len() * 10
+            < serde_json::to_string(&full).unwrap().len()
+    );
+
+    let all = project_all(&[build_order.clone(), build_order], Some(&fields));
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[1], projected);
+}
@@ -0,0 +1,199 @@
+use actix_web::test::{TestRequest, call_and_read_body_json, init_service};
+use actix_web::{App, web};
+use build_warren::api::{MAX_BUILDS_LIMIT, routes};
+use build_warren::build_order::{Action, ActionType, BuildOrder, BuildType, OrderEntry, Race};
+use build_warren::build_store::BuildStore;
+use build_warren::query::{Comparison, MAX_DEPTH, MAX_QUERY_LENGTH, Predicate, Query, QueryError};
+use chrono::{NaiveDate, NaiveTime};
+
+fn zvp(id: u32, build_type: BuildType, score: u32, patch: &str, warren_time: &str) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(format!("Roach build {}", id));
+    build_order.set_player_race(Race::Zerg);
+    build_order.set_opponent_race(Race::Protoss);
+    build_order.set_build_type(build_type);
+    build_order.set_creator("Herkoss".to_string());
+    build_order.set_patch(patch.to_string());
+    build_order.set_published(NaiveDate::from_ymd_opt(2025, 3, id).unwrap());
+    build_order.set_votes(score, 10);
+    build_order.add_step(OrderEntry::new(
+        17,
+        "1:00".to_string(),
+        vec![Action::new(
+            ActionType::Building,
+            "Spawning Pool".to_string(),
+        )],
+        String::new(),
+    ));
+    build_order.add_step(OrderEntry::new(
+        28,
+        warren_time.to_string(),
+        vec![Action::new(
+            ActionType::Building,
+            "Roach Warren".to_string(),
+        )],
+        String::new(),
+    ));
+    build_order
+}
+
+fn corpus() -> Vec<BuildOrder> {
+    vec![
+        zvp(1, BuildType::Timing, 75, "5.0.14", "2:45"),
+        zvp(2, BuildType::Timing, 40, "5.0.14", "3:30"),
+        zvp(3, BuildType::Economic, 90, "5.0.11", "4:10"),
+        zvp(4, BuildType::Timing, 80, "4.11.4", "2:50"),
+    ]
+}
+
+fn matching(query: &str) -> Vec<u32> {
+    let corpus = corpus();
+    let query: Query = query.parse().expect("Failed to parse query");
+    query
+        .filter(&corpus)
+        .iter()
+        .map(|build_order| build_order.get_id())
+        .collect()
+}
+
+#[test]
+fn test_parse_query() {
+    let query = Query::parse(r#"race:Z vs:P type:timing votes>=60 has:"Roach Warren"<3:00"#)
+        .expect("Failed to parse query");
+    assert_eq!(
+        query,
+        Query::And(vec![
+            Query::Predicate(Predicate::Race(Race::Zerg)),
+            Query::Predicate(Predicate::Opponent(Race::Protoss)),
+            Query::Predicate(Predicate::Type(BuildType::Timing)),
+            Query::Predicate(Predicate::Votes(Comparison::Ge, 60)),
+            Query::Predicate(Predicate::Has {
+                action: "Roach Warren".to_string(),
+                time: Some((Comparison::Lt, NaiveTime::from_hms_opt(0, 3, 0).unwrap())),
+            }),
+        ])
+    );
+
+    let query = Query::parse("(type:timing OR votes>85) -patch<5").unwrap();
+    assert_eq!(
+        query,
+        Query::And(vec![
+            Query::Or(vec![
+                Query::Predicate(Predicate::Type(BuildType::Timing)),
+                Query::Predicate(Predicate::Votes(Comparison::Gt, 85)),
+            ]),
+            Query::Not(Box::new(Query::Predicate(Predicate::Patch(
+                Comparison::Lt,
+                vec![5]
+            )))),
+        ])
+    );
+    assert_eq!(
+        query.to_string(),
+        "(type:\"Timing Attack\" OR votes>85) -patch<5"
+    );
+    assert_eq!(query.to_string().parse::<Query>().unwrap(), query);
+    assert_eq!(Query::parse("").unwrap(), Query::And(Vec::new()));
+}
+
+#[test]
+fn test_invalid_queries() {
+    assert_eq!(
+        Query::parse("colour:red").unwrap_err(),
+        QueryError::UnknownKey("colour".to_string())
+    );
+    assert_eq!(
+        Query::parse("race:Q").unwrap_err(),
+        QueryError::InvalidValue {
+            key: "race".to_string(),
+            value: "Q".to_string()
+        }
+    );
+    assert_eq!(
+        Query::parse("creator>x").unwrap_err(),
+        QueryError::InvalidOperator {
+            key: "creator".to_string(),
+            operator: Comparison::Gt
+        }
+    );
+    assert!(matches!(
+        Query::parse("(race:Z").unwrap_err(),
+        QueryError::UnexpectedEnd(_)
+    ));
+    assert_eq!(
+        Query::parse("race:Z)").unwrap_err(),
+        QueryError::Unexpected {
+            position: 6,
+            found: ')'
+        }
+    );
+    assert!(matches!(
+        Query::parse("has:\"Roach Warren").unwrap_err(),
+        QueryError::UnexpectedEnd(_)
+    ));
+    assert!(Query::parse("has:Pylon<soon").is_err());
+
+    // Deep nesting is refused before it can overflow the stack
+    let nested = |depth: usize| format!("{}race:Z{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(Query::parse(&nested(MAX_DEPTH - 1)).is_ok());
+    assert_eq!(
+        Query::parse(&nested(1000)).unwrap_err(),
+        QueryError::TooDeep
+    );
+    assert_eq!(
+        Query::parse(&"-".repeat(1000)).unwrap_err(),
+        QueryError::TooDeep
+    );
+    assert_eq!(
+        Query::parse(&"a".repeat(MAX_QUERY_LENGTH + 1)).unwrap_err(),
+        QueryError::TooLong(MAX_QUERY_LENGTH + 1)
+    );
+}
+
+#[test]
+fn test_query_matches_builds() {
+    assert_eq!(matching(""), [1, 2, 3, 4]);
+    assert_eq!(
+        matching(r#"race:Z vs:P type:timing votes>=60 has:"Roach Warren"<3:00"#),
+        [1, 4]
+    );
+    assert_eq!(matching("patch>=5.0 creator:herkoss"), [1, 2, 3]);
+    assert_eq!(matching("patch:5.0.14"), [1, 2]);
+    assert_eq!(matching("patch<=5.0"), [1, 2, 3, 4]);
+    assert_eq!(matching("type:economic OR votes<50"), [2, 3]);
+    assert_eq!(matching("-type:timing"), [3]);
+    assert_eq!(matching("NOT (votes>70 OR id:2)"), Vec::<u32>::new());
+    assert_eq!(matching("has:pylon"), Vec::<u32>::new());
+    assert_eq!(matching("has:\"spawning pool\"@1:00 roach"), [1, 2, 3, 4]);
+    assert_eq!(matching("\"build 3\""), [3]);
+    assert_eq!(matching("published<2025-03-03 matchup:zvp"), [1, 2]);
+    assert_eq!(matching("voters>=10 id>2"), [3, 4]);
+    assert_eq!(matching("race:T OR vs:Z"), Vec::<u32>::new());
+}
+
+#[actix_web::test]
+async fn test_listed_builds_are_capped() {
+    let dir =
+        std::env::temp_dir().join(format!("build_warren_builds_limit_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = BuildStore::open(&dir).unwrap();
+    for id in 1..=MAX_BUILDS_LIMIT as u32 + 1 {
+        let mut build_order = BuildOrder::new();
+        build_order.set_id(id);
+        store.save(&build_order).unwrap();
+    }
+    let app = init_service(App::new().app_data(web::Data::new(store)).configure(routes)).await;
+
+    let request = TestRequest::get()
+        .uri("/builds?limit=1000&fields=id")
+        .to_request();
+    let builds: Vec<serde_json::Value> = call_and_read_body_json(&app, request).await;
+    assert_eq!(builds.len(), MAX_BUILDS_LIMIT);
+    let request = TestRequest::get()
+        .uri("/builds?offset=500&fields=id")
+        .to_request();
+    let builds: Vec<serde_json::Value> = call_and_read_body_json(&app, request).await;
+    assert_eq!(builds.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
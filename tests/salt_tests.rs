@@ -0,0 +1,63 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, OrderEntry, Race};
+use build_warren::build_parser::parse_build_order;
+use build_warren::salt::{decode_salt, encode_salt, salt_compatible};
+
+fn open_file(file_name: &str) -> String {
+    std::fs::read_to_string(file_name).expect("Failed to read file")
+}
+
+fn assert_round_trip(build_order: &BuildOrder) {
+    let salt = encode_salt(build_order);
+    let decoded = decode_salt(&salt).expect("Failed to decode SALT");
+    let expected = salt_compatible(build_order);
+
+    assert_eq!(decoded.get_name(), build_order.get_name());
+    assert_eq!(decoded.get_creator(), build_order.get_creator());
+    assert_eq!(decoded.get_description(), build_order.get_description());
+    assert!(!expected.get_entries().is_empty());
+    assert_eq!(decoded.get_entries(), expected.get_entries());
+    assert_eq!(encode_salt(&decoded), salt);
+}
+
+#[test]
+fn test_salt_round_trip_clemvsmaxpax() {
+    let html_content = open_file("tests/examples/clemvsmaxpax.html");
+    let build_order =
+        parse_build_order(&html_content, 193576).expect("Failed to parse build order");
+    assert_round_trip(&build_order);
+    let decoded = decode_salt(&encode_salt(&build_order)).unwrap();
+    assert_eq!(decoded.get_player_race(), &Race::Terran);
+}
+
+#[test]
+fn test_salt_round_trip_oldestbuild() {
+    let html_content = open_file("tests/examples/oldestbuild.html");
+    let build_order = parse_build_order(&html_content, 5).expect("Failed to parse build order");
+    assert_round_trip(&build_order);
+}
+
+#[test]
+fn test_encode_salt() {
+    let mut build_order = BuildOrder::new();
+    build_order.set_name("Pool | first".to_string());
+    build_order.set_creator("someone".to_string());
+    build_order.add_step(OrderEntry::new(
+        17,
+        "1:05".to_string(),
+        vec![
+            Action::new(ActionType::Building, "Spawning Pool".to_string()),
+            Action::new(ActionType::Action, "Scout".to_string()),
+        ],
+        "comment".to_string(),
+    ));
+    let salt = encode_salt(&build_order);
+    assert_eq!(salt, "$!Pool  first|someone|~1!%\u{20}F");
+}
+
+#[test]
+fn test_decode_salt_errors() {
+    assert!(decode_salt("no prefix").is_err());
+    assert!(decode_salt("$!title|author|").is_err());
+    assert!(decode_salt("$!title|author|~1!%").is_err());
+    assert!(decode_salt("$!title|author|~1!% ~").is_err());
+}
@@ -0,0 +1,154 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, BuildType, OrderEntry, Race};
+use build_warren::build_store::BuildStore;
+use build_warren::search::{
+    SearchFilter, SearchIndex, StoreIndex, parse_build_type, parse_race, search, stem, tokenize,
+};
+use std::sync::Arc;
+
+fn build(id: u32, name: &str, race: Race, build_type: BuildType, comment: &str) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(name.to_string());
+    build_order.set_player_race(race);
+    build_order.set_opponent_race(Race::Protoss);
+    build_order.set_build_type(build_type);
+    build_order.set_creator("Harstem".to_string());
+    build_order.add_step(OrderEntry::new(
+        13,
+        "0:18".to_string(),
+        vec![Action::new(ActionType::Unit, "Overlord".to_string())],
+        comment.to_string(),
+    ));
+    build_order
+}
+
+fn corpus() -> Vec<BuildOrder> {
+    vec![
+        build(1, "Roach Ravager timing", Race::Zerg, BuildType::Timing, ""),
+        build(
+            2,
+            "Hatch first into roaches",
+            Race::Zerg,
+            BuildType::Economic,
+            "scout for anti-proxy",
+        ),
+        build(
+            3,
+            "Standard macro",
+            Race::Zerg,
+            BuildType::Economic,
+            "Ravagers timed with the hit",
+        ),
+        build(
+            4,
+            "Proxy 2 gate",
+            Race::Protoss,
+            BuildType::Cheese,
+            "proxy pylon",
+        ),
+    ]
+}
+
+fn ids(hits: &[build_warren::search::SearchHit]) -> Vec<u32> {
+    hits.iter().map(|hit| hit.id).collect()
+}
+
+#[test]
+fn test_stem() {
+    for (word, expected) in [
+        ("roaches", "roach"),
+        ("roach", "roach"),
+        ("timings", "time"),
+        ("timing", "time"),
+        ("timed", "time"),
+        ("ravagers", "ravager"),
+        ("hopping", "hop"),
+        ("expanding", "expand"),
+        ("proxy", "proxi"),
+        ("proxies", "proxi"),
+        ("agreed", "agre"),
+        ("17", "17"),
+    ] {
+        assert_eq!(stem(word), expected, "{}", word);
+    }
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(
+        tokenize("Scout for the Anti-Proxy, then expand"),
+        ["scout", "antiproxi", "anti", "proxi", "then", "expand"]
+    );
+    assert!(tokenize(" - , ").is_empty());
+}
+
+#[test]
+fn test_search_ranks_matches() {
+    let corpus = corpus();
+    let index = SearchIndex::new(&corpus);
+    assert_eq!(index.len(), 4);
+
+    // Matches in the name outrank matches in comments
+    let hits = index.search("roach ravager timing", 10);
+    assert_eq!(ids(&hits), [1, 3, 2]);
+    assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    assert_eq!(hits[0].name, "Roach Ravager timing");
+
+    assert_eq!(ids(&index.search("anti-proxy", 10))[0], 2);
+    assert_eq!(ids(&index.search("harstem", 10)).len(), 4);
+    assert!(index.search("the of", 10).is_empty());
+    assert!(index.search("carrier", 10).is_empty());
+    assert_eq!(index.search("roach ravager timing", 1).len(), 1);
+}
+
+#[test]
+fn test_search_with_filters() {
+    let corpus = corpus();
+    let filter = SearchFilter {
+        race: Some(Race::Zerg),
+        ..SearchFilter::default()
+    };
+    assert_eq!(ids(&search(&corpus, "proxy", &filter, 10)), [2]);
+    let filter = SearchFilter {
+        build_type: Some(BuildType::Economic),
+        ..SearchFilter::default()
+    };
+    assert_eq!(ids(&search(&corpus, "roach ravager", &filter, 10)), [2, 3]);
+    assert_eq!(
+        ids(&search(&corpus, "proxy", &SearchFilter::default(), 10)),
+        [4, 2]
+    );
+
+    assert_eq!(parse_race("Z"), Ok(Race::Zerg));
+    assert_eq!(parse_race("protoss"), Ok(Race::Protoss));
+    assert!(parse_race("zerglings").is_err());
+    assert_eq!(parse_build_type("timing"), Ok(BuildType::Timing));
+    assert_eq!(parse_build_type("all-in"), Ok(BuildType::AllIn));
+    assert_eq!(parse_build_type("Co-op"), Ok(BuildType::CoOp));
+    assert!(parse_build_type("").is_err());
+}
+
+#[test]
+fn test_store_index_follows_saves() {
+    let dir = std::env::temp_dir().join(format!("build_warren_store_index_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = BuildStore::open(&dir).unwrap();
+    let mut corpus = corpus();
+    let last = corpus.pop().unwrap();
+    for build_order in &corpus {
+        store.save(build_order).unwrap();
+    }
+    let index = StoreIndex::new(store.clone());
+    let first = index.current();
+    assert_eq!(first.len(), 3);
+    assert!(Arc::ptr_eq(&first, &index.current()));
+
+    store.save(&last).unwrap();
+    let second = index.current();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(
+        ids(&second.search_filtered("proxy", &SearchFilter::default(), 10)),
+        [4, 2]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
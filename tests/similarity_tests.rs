@@ -0,0 +1,74 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, OrderEntry, Race};
+use build_warren::similarity::{duplicate_clusters, most_similar, similarity};
+
+fn zvp(id: u32, steps: &[(&str, &str)]) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_player_race(Race::Zerg);
+    build_order.set_opponent_race(Race::Protoss);
+    for (time, name) in steps {
+        build_order.add_step(OrderEntry::new(
+            16,
+            time.to_string(),
+            vec![Action::new(ActionType::Building, name.to_string())],
+            String::new(),
+        ));
+    }
+    build_order
+}
+
+fn hatch_pool_gas(id: u32, pool_time: &str) -> BuildOrder {
+    zvp(
+        id,
+        &[
+            ("0:12", "Overlord"),
+            ("0:50", "Hatchery"),
+            ("1:05", "Extractor"),
+            (pool_time, "Spawning Pool"),
+        ],
+    )
+}
+
+#[test]
+fn test_similarity_bounds() {
+    let a = hatch_pool_gas(1, "1:10");
+    assert_eq!(similarity(&a, &a), 1.0);
+    let b = zvp(2, &[("0:30", "Spawning Pool"), ("0:40", "Zergling")]);
+    assert!(similarity(&a, &b) < 0.5);
+}
+
+#[test]
+fn test_timing_lowers_similarity() {
+    let a = hatch_pool_gas(1, "1:10");
+    let close = hatch_pool_gas(2, "1:12");
+    let late = hatch_pool_gas(3, "2:10");
+    assert!(similarity(&a, &close) > similarity(&a, &late));
+    assert!(similarity(&a, &late) < 1.0);
+}
+
+#[test]
+fn test_most_similar_ranks_and_filters_matchup() {
+    let target = hatch_pool_gas(1, "1:10");
+    let mut other_matchup = hatch_pool_gas(4, "1:10");
+    other_matchup.set_opponent_race(Race::Terran);
+    let corpus = vec![
+        target.clone(),
+        zvp(2, &[("0:30", "Spawning Pool"), ("0:40", "Zergling")]),
+        hatch_pool_gas(3, "1:14"),
+        other_matchup,
+    ];
+    let results = most_similar(&target, &corpus, 10);
+    let ids: Vec<u32> = results.iter().map(|r| r.id).collect();
+    assert_eq!(ids, vec![3, 2]);
+}
+
+#[test]
+fn test_duplicate_clusters() {
+    let corpus = vec![
+        hatch_pool_gas(1, "1:10"),
+        hatch_pool_gas(2, "1:11"),
+        zvp(3, &[("0:30", "Spawning Pool"), ("0:40", "Zergling")]),
+        hatch_pool_gas(4, "1:12"),
+    ];
+    assert_eq!(duplicate_clusters(&corpus, 0.9), vec![vec![1, 2, 4]]);
+}
@@ -0,0 +1,80 @@
+use build_warren::build_order::{Action, ActionType, BuildOrder, BuildType, OrderEntry, Race};
+use build_warren::stats::{StatsOptions, compute_stats};
+use chrono::NaiveDate;
+
+fn zvp(id: u32, patch: &str, pool_time: &str, votes: Option<(u32, u32)>) -> BuildOrder {
+    let mut build_order = BuildOrder::new();
+    build_order.set_id(id);
+    build_order.set_name(format!("Build {}", id));
+    build_order.set_player_race(Race::Zerg);
+    build_order.set_opponent_race(Race::Protoss);
+    build_order.set_build_type(BuildType::Economic);
+    build_order.set_patch(patch.to_string());
+    build_order.set_published(NaiveDate::from_ymd_opt(2025, 5, id).unwrap());
+    if let Some((score, count)) = votes {
+        build_order.set_votes(score, count);
+    }
+    for (time, action_type, name) in [
+        ("0:12", ActionType::Worker, "Drone"),
+        ("0:18", ActionType::Unit, "Overlord"),
+        ("0:50", ActionType::Building, "Hatchery"),
+        (pool_time, ActionType::Building, "Spawning Pool"),
+    ] {
+        build_order.add_step(OrderEntry::new(
+            16,
+            time.to_string(),
+            vec![Action::new(action_type, name.to_string())],
+            String::new(),
+        ));
+    }
+    build_order
+}
+
+#[test]
+fn test_compute_stats() {
+    let corpus = vec![
+        zvp(1, "5.0.11", "1:00", Some((90, 10))),
+        zvp(2, "5.0.11", "1:10", Some((95, 2))),
+        zvp(3, "5.0.11", "1:20", Some((60, 5))),
+        zvp(4, "5.0.12", "1:05", None),
+    ];
+    let stats = compute_stats(&corpus, &StatsOptions::default());
+
+    assert_eq!(stats.total, 4);
+    assert_eq!(stats.by_matchup.get("ZvP"), Some(&4));
+    assert_eq!(stats.by_build_type.get("Economic"), Some(&4));
+    assert_eq!(stats.by_difficulty.get("Unknown"), Some(&4));
+    assert_eq!(stats.by_patch.get("5.0.11"), Some(&3));
+
+    let openers = stats.common_openers.get("ZvP").unwrap();
+    assert_eq!(openers[0].count, 4);
+    assert_eq!(
+        openers[0].actions,
+        vec!["Overlord", "Hatchery", "Spawning Pool"]
+    );
+
+    let pool = stats
+        .structure_timings
+        .iter()
+        .find(|t| t.structure == "Spawning Pool" && t.patch == "5.0.11")
+        .unwrap();
+    assert_eq!(pool.median_seconds, 70);
+    assert_eq!(pool.samples, 3);
+
+    let leaders: Vec<u32> = stats.vote_leaders.iter().map(|l| l.id).collect();
+    assert_eq!(leaders, vec![1, 3]);
+}
+
+#[test]
+fn test_compute_stats_date_range() {
+    let corpus = vec![
+        zvp(1, "5.0.11", "1:00", None),
+        zvp(2, "5.0.11", "1:10", None),
+        zvp(3, "5.0.11", "1:20", None),
+    ];
+    let options = StatsOptions {
+        since: NaiveDate::from_ymd_opt(2025, 5, 2),
+        ..StatsOptions::default()
+    };
+    assert_eq!(compute_stats(&corpus, &options).total, 2);
+}
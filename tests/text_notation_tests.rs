@@ -0,0 +1,76 @@
+use build_warren::build_import::{ImportFormat, import_build};
+use build_warren::build_order::{ActionType, BuildType, Race};
+use build_warren::text_notation::parse_text_build;
+
+#[test]
+fn test_parse_text_build() {
+    let content = "
+        Title: 17 Hatch 18 Gas 17 Pool
+        Matchup: ZvP
+        Type: Economic
+        Author: someone
+
+        13 0:12 Overlord # send to the natural
+        16 0:48 Hatchery
+        18 1:00 Extractor
+        17 1:10 Spawning Pool, Drone
+        19 Overlords
+        20 2:05 Queen x2 -- both hatcheries
+    ";
+    let build_order = parse_text_build(content).expect("Failed to parse text build");
+    assert_eq!(build_order.get_name(), "17 Hatch 18 Gas 17 Pool");
+    assert_eq!(build_order.get_player_race(), &Race::Zerg);
+    assert_eq!(build_order.get_opponent_race(), &Race::Protoss);
+    assert_eq!(build_order.get_build_type(), &BuildType::Economic);
+    assert_eq!(build_order.get_creator(), "someone");
+
+    let entries = build_order.get_entries();
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries[0].get_supply(), 13);
+    assert_eq!(entries[0].get_timestamp().unwrap().to_string(), "00:00:12");
+    assert_eq!(entries[0].get_comment(), Some("send to the natural"));
+    assert_eq!(
+        entries[0].get_actions()[0].get_action_type(),
+        &ActionType::Unit
+    );
+    assert_eq!(entries[3].get_actions().len(), 2);
+    assert_eq!(
+        entries[3].get_actions()[1].get_action_type(),
+        &ActionType::Worker
+    );
+    assert!(entries[4].get_timestamp().is_none());
+    assert_eq!(
+        entries[4].get_actions()[0].get_action_type(),
+        &ActionType::Unit
+    );
+    assert_eq!(entries[5].get_actions()[0].get_name(), "Queen x2");
+    assert_eq!(entries[5].get_comment(), Some("both hatcheries"));
+}
+
+#[test]
+fn test_parse_text_build_infers_race() {
+    let build_order =
+        parse_text_build("14 0:18 Supply Depot\n16 0:40 Barracks\n16 0:45 Refinery").unwrap();
+    assert_eq!(build_order.get_player_race(), &Race::Terran);
+    assert_eq!(build_order.get_opponent_race(), &Race::Any);
+}
+
+#[test]
+fn test_parse_text_build_errors() {
+    let error = parse_text_build("14 0:18 Overlord\n16 0:75 Hatchery").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Parse Error: Line 2: invalid time '0:75'"
+    );
+    for time in ["60:00", "25:00:00", "1:60:00"] {
+        let error = parse_text_build(&format!("16 {} Hatchery", time)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Parse Error: Line 1: invalid time '{}'", time)
+        );
+    }
+    assert!(import_build("16 60:00 Hatchery", ImportFormat::Text).is_err());
+    assert!(parse_text_build("16 23:59:59 Hatchery").is_ok());
+    assert!(parse_text_build("Title: nothing").is_err());
+    assert!(parse_text_build("Race: Zerg\n14 Overlord").is_err());
+}